@@ -0,0 +1,107 @@
+///
+/// capture.rs
+///
+/// Captures two virtual buses, each loaded at the frame rate of a saturated 1 Mbit/s bus, to
+/// pcapng files with `crosscan::capture::Capture` and checks that no frame is lost, exiting with
+/// status 1 otherwise. Requires CAP_NET_ADMIN and the vcan kernel module.
+///
+#[cfg(target_os = "linux")]
+mod bench {
+    use crosscan::{capture::Capture, lin_can::LinuxCan, pcap::PcapReader, testing};
+    use socketcan::{EmbeddedFrame, Socket, StandardId};
+    use std::time::{Duration, Instant};
+
+    const INTERFACES: [&str; 2] = ["crosscan_cap0", "crosscan_cap1"];
+    // A classic frame with 8 data bytes and a standard ID takes at least 111 bit times
+    const FRAMES_PER_SECOND: u64 = 1_000_000 / 111;
+    const RUN_TIME: Duration = Duration::from_secs(10);
+    const TICK: Duration = Duration::from_millis(1);
+    const RECEIVE_BUFFER: usize = 4 * 1024 * 1024;
+
+    // Sends frames at FRAMES_PER_SECOND for RUN_TIME and returns how many were sent
+    fn spawn_writer(interface: &'static str) -> std::thread::JoinHandle<u64> {
+        std::thread::spawn(move || {
+            let socket = socketcan::CanSocket::open(interface).unwrap();
+            let id = StandardId::new(0x123).unwrap();
+            let start = Instant::now();
+            let mut sent = 0u64;
+            let mut tick = start;
+            while tick < start + RUN_TIME {
+                let due = (tick - start).as_micros() as u64 * FRAMES_PER_SECOND / 1_000_000;
+                while sent < due {
+                    let frame = socketcan::CanFrame::new(id, &sent.to_le_bytes()).unwrap();
+                    match socket.write_frame(&frame) {
+                        Ok(()) => sent += 1,
+                        Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                            std::thread::yield_now()
+                        }
+                        Err(e) => panic!("Write failed: {}", e),
+                    }
+                }
+                tick += TICK;
+                std::thread::sleep(tick.saturating_duration_since(Instant::now()));
+            }
+            sent
+        })
+    }
+
+    pub fn main() {
+        let mut vcans = Vec::new();
+        for interface in INTERFACES {
+            match testing::create_vcan(interface) {
+                Ok(vcan) => vcans.push(vcan),
+                Err(e) => {
+                    println!("Skipping capture benchmark: {}", e);
+                    return;
+                }
+            }
+        }
+
+        let paths: Vec<_> = INTERFACES
+            .iter()
+            .map(|name| std::env::temp_dir().join(format!("{}.pcapng", name)))
+            .collect();
+        let captures: Vec<_> = INTERFACES
+            .iter()
+            .zip(&paths)
+            .map(|(interface, path)| {
+                let file = std::fs::File::create(path).unwrap();
+                Capture::start(interface, file, |can: &mut LinuxCan| {
+                    can.set_receive_buffer(RECEIVE_BUFFER).map(|_| ())
+                })
+                .unwrap()
+            })
+            .collect();
+
+        let writers: Vec<_> = INTERFACES.into_iter().map(spawn_writer).collect();
+        let sent: Vec<u64> = writers.into_iter().map(|w| w.join().unwrap()).collect();
+        // Let the capture threads drain the socket queues
+        std::thread::sleep(Duration::from_millis(200));
+
+        let mut lossless = true;
+        for (((interface, capture), path), sent) in
+            INTERFACES.iter().zip(captures).zip(&paths).zip(sent)
+        {
+            let stats = capture.stop().unwrap();
+            let in_file = PcapReader::new(std::fs::File::open(path).unwrap())
+                .unwrap()
+                .count() as u64;
+            std::fs::remove_file(path).unwrap();
+            println!(
+                "{:<16} sent {:>8}  captured {:>8}  in file {:>8}  dropped {:>6}",
+                interface, sent, stats.frames, in_file, stats.dropped
+            );
+            lossless &= stats.dropped == 0 && in_file == sent;
+        }
+        if !lossless {
+            eprintln!("FRAMES LOST");
+            std::process::exit(1);
+        }
+        println!("No frames lost");
+    }
+}
+
+fn main() {
+    #[cfg(target_os = "linux")]
+    bench::main();
+}
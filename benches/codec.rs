@@ -0,0 +1,145 @@
+///
+/// codec.rs
+///
+/// Criterion benchmarks of the frame codecs, filter matching and the in-memory bus.
+/// Filter matching is only measured with the profiles feature enabled.
+///
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use crosscan::{CanInterface, can::CanFrame, slcan, testing::TestBus};
+use std::hint::black_box;
+
+const BUS_BATCH: usize = 1024;
+
+fn sample_frames() -> Vec<CanFrame> {
+    vec![
+        CanFrame::new(0x123, &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]).unwrap(),
+        CanFrame::new_eff(0x18FE_F100, &[0xFF, 0x00, 0x7D]).unwrap(),
+        CanFrame::new_remote(0x7DF, 8, false).unwrap(),
+    ]
+}
+
+fn bincode_wire(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bincode");
+    let config = bincode::config::standard();
+    for frame in sample_frames() {
+        let encoded = bincode::serde::encode_to_vec(&frame, config).unwrap();
+        let name = format!("{:X}", frame.id());
+        group.bench_function(format!("encode/{}", name), |b| {
+            b.iter(|| bincode::serde::encode_to_vec(black_box(&frame), config).unwrap())
+        });
+        group.bench_function(format!("decode/{}", name), |b| {
+            b.iter(|| {
+                bincode::serde::decode_from_slice::<CanFrame, _>(black_box(&encoded), config)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn slcan_codec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("slcan");
+    for frame in sample_frames() {
+        let encoded = slcan::encode(&frame).unwrap();
+        let name = format!("{:X}", frame.id());
+        group.bench_function(format!("encode/{}", name), |b| {
+            b.iter(|| slcan::encode(black_box(&frame)).unwrap())
+        });
+        group.bench_function(format!("decode/{}", name), |b| {
+            b.iter(|| slcan::decode(black_box(&encoded)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn raw_layout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("linux_raw");
+    for frame in sample_frames() {
+        let raw = frame.to_linux_raw();
+        let name = format!("{:X}", frame.id());
+        group.bench_function(format!("encode/{}", name), |b| {
+            b.iter(|| black_box(&frame).to_linux_raw())
+        });
+        group.bench_function(format!("decode/{}", name), |b| {
+            b.iter(|| CanFrame::from_linux_raw(black_box(&raw)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "profiles")]
+fn filter_matching(c: &mut Criterion) {
+    use crosscan::profiles::{Profile, ProfileFilter};
+
+    let profile = Profile {
+        channel: "can0".to_string(),
+        backend: None,
+        bitrate: None,
+        filters: (0..16)
+            .map(|i| ProfileFilter {
+                id: 0x100 + i * 0x10,
+                mask: 0x7F0,
+                extended: Some(false),
+            })
+            .collect(),
+    };
+    let frames = sample_frames();
+    let mut group = c.benchmark_group("filter");
+    group.throughput(Throughput::Elements(frames.len() as u64));
+    group.bench_function("profile_16_filters", |b| {
+        b.iter(|| {
+            frames
+                .iter()
+                .filter(|f| profile.accepts(black_box(f)))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "profiles"))]
+fn filter_matching(_: &mut Criterion) {}
+
+fn virtual_bus(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let frame = sample_frames().remove(0);
+    let batch = vec![frame.clone(); BUS_BATCH];
+
+    let mut group = c.benchmark_group("test_bus");
+    group.throughput(Throughput::Elements(BUS_BATCH as u64));
+    group.bench_function("inject_read_frames", |b| {
+        b.iter_batched(
+            TestBus::new,
+            |mut bus| {
+                for _ in 0..BUS_BATCH {
+                    bus.inject(frame.clone());
+                }
+                let mut received = 0;
+                while received < BUS_BATCH {
+                    received += rt.block_on(bus.read_frames()).unwrap().len();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("write_frames", |b| {
+        b.iter_batched(
+            TestBus::new,
+            |mut bus| rt.block_on(bus.write_frames(black_box(&batch))).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bincode_wire,
+    slcan_codec,
+    raw_layout,
+    filter_matching,
+    virtual_bus
+);
+criterion_main!(benches);
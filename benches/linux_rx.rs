@@ -0,0 +1,131 @@
+///
+/// linux_rx.rs
+///
+/// Compares receive throughput and CPU cost of a per-frame read socket against LinuxCan's batched
+/// recvmmsg reader. Requires CAP_NET_ADMIN and the vcan kernel module.
+///
+#[cfg(target_os = "linux")]
+mod bench {
+    use crosscan::{CanInterface, FramesDropped, lin_can::LinuxCan, testing};
+    use socketcan::{EmbeddedFrame, Socket, StandardId};
+    use std::time::{Duration, Instant};
+
+    const INTERFACE: &str = "crosscan_rx";
+    const FRAME_COUNT: usize = 200_000;
+    const IDLE_TIMEOUT: Duration = Duration::from_millis(200);
+
+    struct Report {
+        received: usize,
+        elapsed: Duration,
+        cpu: Duration,
+    }
+
+    fn thread_cpu_time() -> Duration {
+        // SAFETY: getrusage only writes into the provided struct
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) };
+        let to_duration = |tv: libc::timeval| {
+            Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+        };
+        to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+    }
+
+    fn spawn_writer() -> std::thread::JoinHandle<()> {
+        std::thread::spawn(|| {
+            let socket = socketcan::CanSocket::open(INTERFACE).unwrap();
+            let id = StandardId::new(0x123).unwrap();
+            let frame = socketcan::CanFrame::new(id, &[0xAA; 8]).unwrap();
+            let mut sent = 0;
+            while sent < FRAME_COUNT {
+                match socket.write_frame(&frame) {
+                    Ok(()) => sent += 1,
+                    Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => std::thread::yield_now(),
+                    Err(e) => panic!("Write failed: {}", e),
+                }
+            }
+        })
+    }
+
+    trait Reader {
+        /// Reads once and returns the number of frames received
+        async fn read(&mut self) -> std::io::Result<usize>;
+    }
+
+    impl Reader for socketcan::tokio::CanSocket {
+        async fn read(&mut self) -> std::io::Result<usize> {
+            self.read_frame().await.map(|_| 1)
+        }
+    }
+
+    impl Reader for LinuxCan {
+        async fn read(&mut self) -> std::io::Result<usize> {
+            match self.read_frames().await {
+                Ok(frames) => Ok(frames.len()),
+                // Overflows are expected under full load, the lost frames are simply not counted
+                Err(e) if e.get_ref().is_some_and(|e| e.is::<FramesDropped>()) => Ok(0),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    async fn run<R: Reader>(reader: &mut R) -> Report {
+        let writer = spawn_writer();
+        let start = Instant::now();
+        let cpu_start = thread_cpu_time();
+
+        let mut received = 0;
+        while received < FRAME_COUNT {
+            match tokio::time::timeout(IDLE_TIMEOUT, reader.read()).await {
+                Ok(result) => received += result.unwrap(),
+                // Frames were dropped by the kernel, nothing more will arrive
+                Err(_) => break,
+            }
+        }
+
+        let report = Report {
+            received,
+            elapsed: start.elapsed(),
+            cpu: thread_cpu_time() - cpu_start,
+        };
+        writer.join().unwrap();
+        report
+    }
+
+    fn print(name: &str, report: &Report) {
+        println!(
+            "{:<24} {:>8} frames  {:>10.0} frames/s  {:>8.3} us cpu/frame",
+            name,
+            report.received,
+            report.received as f64 / report.elapsed.as_secs_f64(),
+            report.cpu.as_secs_f64() * 1e6 / report.received.max(1) as f64,
+        );
+    }
+
+    pub fn main() {
+        let _vcan = match testing::create_vcan(INTERFACE) {
+            Ok(vcan) => vcan,
+            Err(e) => {
+                println!("Skipping rx benchmark: {}", e);
+                return;
+            }
+        };
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let mut socket = socketcan::tokio::CanSocket::open(INTERFACE).unwrap();
+            print("per-frame read", &run(&mut socket).await);
+
+            let mut can = LinuxCan::open(INTERFACE).await.unwrap();
+            print("LinuxCan::read_frames", &run(&mut can).await);
+        });
+    }
+}
+
+fn main() {
+    #[cfg(target_os = "linux")]
+    bench::main();
+}
@@ -0,0 +1,111 @@
+///
+/// spsc_latency.rs
+///
+/// Compares the latency from a frame arriving at an interface to a consumer thread seeing it,
+/// between a tokio mpsc channel and `crosscan::spsc::FastReader`.
+///
+use crosscan::{CanInterface, can::CanFrame, spsc::FastReader, testing::TestBus};
+use std::time::{Duration, Instant};
+
+const FRAME_COUNT: usize = 20_000;
+const INTERVAL: Duration = Duration::from_micros(100);
+const RING_CAPACITY: usize = 1024;
+
+// Injects FRAME_COUNT frames carrying their send time in nanoseconds since `base`
+fn spawn_sender(bus: TestBus, base: Instant) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for _ in 0..FRAME_COUNT {
+            let sent = base.elapsed().as_nanos() as u64;
+            bus.inject(CanFrame::new(0x100, &sent.to_le_bytes()).unwrap());
+            std::thread::sleep(INTERVAL);
+        }
+    })
+}
+
+fn latency(frame: &CanFrame, base: Instant) -> Duration {
+    let sent = u64::from_le_bytes(frame.data().try_into().unwrap());
+    base.elapsed() - Duration::from_nanos(sent)
+}
+
+fn report(name: &str, mut latencies: Vec<Duration>) {
+    latencies.sort_unstable();
+    let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+    println!(
+        "{:<20} p50 {:>8.1?}  p99 {:>8.1?}  max {:>8.1?}",
+        name,
+        percentile(50),
+        percentile(99),
+        latencies[latencies.len() - 1]
+    );
+}
+
+// Reader task forwarding into a tokio mpsc channel; the consumer either parks or spins
+fn run_mpsc(rt: &tokio::runtime::Runtime, spin: bool) -> Vec<Duration> {
+    let bus = TestBus::new();
+    let (tx, mut rx) = tokio::sync::mpsc::channel(RING_CAPACITY);
+    let mut can = bus.clone();
+    rt.spawn(async move {
+        while let Ok(frames) = can.read_frames().await {
+            for frame in frames {
+                if tx.send(frame).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let base = Instant::now();
+    let sender = spawn_sender(bus, base);
+    let mut latencies = Vec::with_capacity(FRAME_COUNT);
+    while latencies.len() < FRAME_COUNT {
+        let frame = if spin {
+            match rx.try_recv() {
+                Ok(frame) => frame,
+                Err(_) => {
+                    std::hint::spin_loop();
+                    continue;
+                }
+            }
+        } else {
+            rx.blocking_recv().unwrap()
+        };
+        latencies.push(latency(&frame, base));
+    }
+    sender.join().unwrap();
+    latencies
+}
+
+fn run_ring(rt: &tokio::runtime::Runtime) -> Vec<Duration> {
+    let bus = TestBus::new();
+    let mut reader = {
+        let _guard = rt.enter();
+        FastReader::spawn(bus.clone(), RING_CAPACITY)
+    };
+
+    let base = Instant::now();
+    let sender = spawn_sender(bus, base);
+    let mut latencies = Vec::with_capacity(FRAME_COUNT);
+    while latencies.len() < FRAME_COUNT {
+        if let Some(frame) = reader.read_spin(Duration::from_secs(1)).unwrap() {
+            latencies.push(latency(&frame, base));
+        }
+    }
+    sender.join().unwrap();
+    assert_eq!(reader.dropped(), 0);
+    latencies
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap();
+    println!(
+        "{} frames every {:?}, interface to consumer thread",
+        FRAME_COUNT, INTERVAL
+    );
+    report("mpsc blocking_recv", run_mpsc(&rt, false));
+    report("mpsc try_recv spin", run_mpsc(&rt, true));
+    report("spsc ring spin", run_ring(&rt));
+}
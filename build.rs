@@ -0,0 +1,58 @@
+///
+/// build.rs
+///
+/// Generates the gRPC service code for the grpc feature.
+///
+fn main() {
+    #[cfg(feature = "grpc")]
+    generate_grpc();
+}
+
+// The service is described in Rust so no protoc is needed; keep in sync with proto/crosscan.proto
+#[cfg(feature = "grpc")]
+fn generate_grpc() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::proto::{}", input))
+            .output_type(format!("crate::proto::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+    };
+    let service = Service::builder()
+        .name("CanBus")
+        .package("crosscan")
+        .method(
+            method(
+                "subscribe",
+                "Subscribe",
+                "SubscribeRequest",
+                "InterfaceEvent",
+            )
+            .server_streaming()
+            .build(),
+        )
+        .method(method("send", "Send", "SendRequest", "SendResponse").build())
+        .method(
+            method(
+                "get_config",
+                "GetConfig",
+                "GetConfigRequest",
+                "InterfaceConfig",
+            )
+            .build(),
+        )
+        .method(
+            method(
+                "list_channels",
+                "ListChannels",
+                "ListChannelsRequest",
+                "ListChannelsResponse",
+            )
+            .build(),
+        )
+        .build();
+    Builder::new().compile(&[service]);
+}
@@ -0,0 +1,20 @@
+///
+/// build.rs
+///
+/// Generates include/crosscan.h from the exported C API.
+///
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Invalid cbindgen.toml");
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(format!("{}/include/crosscan.h", crate_dir));
+        }
+        // Don't fail the build on parse errors, rustc reports them with better context
+        Err(e) => println!("cargo:warning=Unable to generate crosscan.h: {}", e),
+    }
+}
@@ -0,0 +1,91 @@
+#ifndef CROSSCAN_H
+#define CROSSCAN_H
+
+/* Generated by cbindgen from crosscan-ffi. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result codes returned by the crosscan functions
+ */
+typedef enum CrosscanResult {
+  CROSSCAN_RESULT_OK = 0,
+  CROSSCAN_RESULT_NULL_POINTER = -1,
+  CROSSCAN_RESULT_INVALID_ARGUMENT = -2,
+  CROSSCAN_RESULT_IO = -3,
+  CROSSCAN_RESULT_TIMEOUT = -4,
+  CROSSCAN_RESULT_FRAMES_DROPPED = -5,
+  CROSSCAN_RESULT_INVALID_FRAME = -6,
+} CrosscanResult;
+
+/**
+ * An open CAN interface
+ */
+typedef struct CrosscanHandle CrosscanHandle;
+
+/**
+ * A classic CAN frame
+ */
+typedef struct CrosscanFrame {
+  uint32_t id;
+  uint8_t data[8];
+  uint8_t dlc;
+  bool is_extended;
+  bool is_rtr;
+  bool is_error;
+} CrosscanFrame;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Opens the CAN interface `interface`. Returns NULL on failure, see `crosscan_last_error()`.
+ *
+ * # Safety
+ * `interface` must be a valid NUL terminated string.
+ */
+struct CrosscanHandle *crosscan_open(const char *interface);
+
+/**
+ * Reads a frame into `frame`, waiting at most `timeout_ms` milliseconds (negative waits forever)
+ *
+ * # Safety
+ * `handle` must come from `crosscan_open()` and `frame` must point to writable memory.
+ */
+enum CrosscanResult crosscan_read(struct CrosscanHandle *handle,
+                                  struct CrosscanFrame *frame,
+                                  int32_t timeout_ms);
+
+/**
+ * Writes `frame` to the interface
+ *
+ * # Safety
+ * `handle` must come from `crosscan_open()` and `frame` must point to a valid frame.
+ */
+enum CrosscanResult crosscan_write(struct CrosscanHandle *handle,
+                                   const struct CrosscanFrame *frame);
+
+/**
+ * Closes the interface and frees the handle. NULL is ignored.
+ *
+ * # Safety
+ * `handle` must come from `crosscan_open()` and must not be used afterwards.
+ */
+void crosscan_close(struct CrosscanHandle *handle);
+
+/**
+ * Returns a description of the last error on this thread
+ *
+ * The string stays valid until the next crosscan call on the same thread.
+ */
+const char *crosscan_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CROSSCAN_H */
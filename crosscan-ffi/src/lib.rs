@@ -0,0 +1,227 @@
+///
+/// crosscan-ffi
+///
+/// C API for the crosscan platform backends. Every call blocks the calling thread; a handle
+/// must not be used from several threads at the same time.
+///
+use crosscan::{CanInterface, FramesDropped, can::CanFrame};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::io::ErrorKind;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+type PlatformCan = crosscan::lin_can::LinuxCan;
+#[cfg(target_os = "windows")]
+type PlatformCan = crosscan::win_can::WindowsCan;
+
+/// Result codes returned by the crosscan functions
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrosscanResult {
+    Ok = 0,
+    NullPointer = -1,
+    InvalidArgument = -2,
+    Io = -3,
+    Timeout = -4,
+    FramesDropped = -5,
+    InvalidFrame = -6,
+}
+
+/// A classic CAN frame
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CrosscanFrame {
+    pub id: u32,
+    pub data: [u8; 8],
+    pub dlc: u8,
+    pub is_extended: bool,
+    pub is_rtr: bool,
+    pub is_error: bool,
+}
+
+/// An open CAN interface
+pub struct CrosscanHandle {
+    runtime: tokio::runtime::Runtime,
+    can: PlatformCan,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+fn result_from_io(error: std::io::Error) -> CrosscanResult {
+    let result = if error.get_ref().is_some_and(|e| e.is::<FramesDropped>()) {
+        CrosscanResult::FramesDropped
+    } else {
+        match error.kind() {
+            ErrorKind::TimedOut => CrosscanResult::Timeout,
+            ErrorKind::InvalidInput => CrosscanResult::InvalidArgument,
+            _ => CrosscanResult::Io,
+        }
+    };
+    set_last_error(error);
+    result
+}
+
+impl From<&CanFrame> for CrosscanFrame {
+    fn from(frame: &CanFrame) -> Self {
+        let mut data = [0u8; 8];
+        data[..frame.data().len()].copy_from_slice(frame.data());
+        Self {
+            id: frame.id(),
+            data,
+            dlc: frame.dlc() as u8,
+            is_extended: frame.is_extended(),
+            is_rtr: frame.is_rtr(),
+            is_error: frame.is_error(),
+        }
+    }
+}
+
+impl TryFrom<&CrosscanFrame> for CanFrame {
+    type Error = &'static str;
+
+    fn try_from(frame: &CrosscanFrame) -> Result<Self, Self::Error> {
+        if frame.is_error {
+            CanFrame::new_error(frame.id)
+        } else if frame.is_rtr {
+            CanFrame::new_remote(frame.id, frame.dlc as usize, frame.is_extended)
+        } else {
+            let data = frame
+                .data
+                .get(..frame.dlc as usize)
+                .ok_or("CAN data must be <= 8 bytes")?;
+            if frame.is_extended {
+                CanFrame::new_eff(frame.id, data)
+            } else {
+                CanFrame::new(frame.id, data)
+            }
+        }
+    }
+}
+
+/// Opens the CAN interface `interface`. Returns NULL on failure, see `crosscan_last_error()`.
+///
+/// # Safety
+/// `interface` must be a valid NUL terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crosscan_open(interface: *const c_char) -> *mut CrosscanHandle {
+    if interface.is_null() {
+        set_last_error("interface is NULL");
+        return std::ptr::null_mut();
+    }
+    // SAFETY: checked for NULL, validity is guaranteed by the caller
+    let Ok(interface) = unsafe { CStr::from_ptr(interface) }.to_str() else {
+        set_last_error("interface is not valid UTF-8");
+        return std::ptr::null_mut();
+    };
+
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            set_last_error(e);
+            return std::ptr::null_mut();
+        }
+    };
+    match runtime.block_on(PlatformCan::open(interface)) {
+        Ok(can) => Box::into_raw(Box::new(CrosscanHandle { runtime, can })),
+        Err(e) => {
+            set_last_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Reads a frame into `frame`, waiting at most `timeout_ms` milliseconds (negative waits forever)
+///
+/// # Safety
+/// `handle` must come from `crosscan_open()` and `frame` must point to writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crosscan_read(
+    handle: *mut CrosscanHandle,
+    frame: *mut CrosscanFrame,
+    timeout_ms: i32,
+) -> CrosscanResult {
+    // SAFETY: validity of non-NULL pointers is guaranteed by the caller
+    let (Some(handle), Some(frame)) = (unsafe { handle.as_mut() }, unsafe { frame.as_mut() })
+    else {
+        set_last_error("handle or frame is NULL");
+        return CrosscanResult::NullPointer;
+    };
+
+    let can = &mut handle.can;
+    let result = handle.runtime.block_on(async {
+        if timeout_ms < 0 {
+            can.read_frame().await
+        } else {
+            tokio::time::timeout(Duration::from_millis(timeout_ms as u64), can.read_frame())
+                .await
+                .unwrap_or_else(|_| Err(ErrorKind::TimedOut.into()))
+        }
+    });
+    match result {
+        Ok(received) => {
+            *frame = CrosscanFrame::from(&received);
+            CrosscanResult::Ok
+        }
+        Err(e) => result_from_io(e),
+    }
+}
+
+/// Writes `frame` to the interface
+///
+/// # Safety
+/// `handle` must come from `crosscan_open()` and `frame` must point to a valid frame.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crosscan_write(
+    handle: *mut CrosscanHandle,
+    frame: *const CrosscanFrame,
+) -> CrosscanResult {
+    // SAFETY: validity of non-NULL pointers is guaranteed by the caller
+    let (Some(handle), Some(frame)) = (unsafe { handle.as_mut() }, unsafe { frame.as_ref() })
+    else {
+        set_last_error("handle or frame is NULL");
+        return CrosscanResult::NullPointer;
+    };
+
+    let frame = match CanFrame::try_from(frame) {
+        Ok(frame) => frame,
+        Err(e) => {
+            set_last_error(e);
+            return CrosscanResult::InvalidFrame;
+        }
+    };
+    match handle.runtime.block_on(handle.can.write_frame(frame)) {
+        Ok(()) => CrosscanResult::Ok,
+        Err(e) => result_from_io(e),
+    }
+}
+
+/// Closes the interface and frees the handle. NULL is ignored.
+///
+/// # Safety
+/// `handle` must come from `crosscan_open()` and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crosscan_close(handle: *mut CrosscanHandle) {
+    if !handle.is_null() {
+        // SAFETY: the handle was created by Box::into_raw in crosscan_open
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Returns a description of the last error on this thread
+///
+/// The string stays valid until the next crosscan call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn crosscan_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
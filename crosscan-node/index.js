@@ -0,0 +1,16 @@
+// Loads the native binding and makes CanBus an async iterator over received frames:
+//
+//   for await (const frame of await CanBus.open('can0')) { ... }
+const { CanBus } = require('./crosscan.node')
+
+CanBus.prototype[Symbol.asyncIterator] = async function* () {
+  for (;;) {
+    const frame = await this.read()
+    if (frame === null) {
+      return
+    }
+    yield frame
+  }
+}
+
+module.exports = { CanBus }
@@ -0,0 +1,203 @@
+///
+/// crosscan-node
+///
+/// N-API bindings for the crosscan platform backends. Each CanBus owns a task that reads and
+/// writes the interface, so pending reads never block writes.
+///
+use crosscan::{CanInterface, FramesDropped, can::CanFrame};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(target_os = "linux")]
+type PlatformCan = crosscan::lin_can::LinuxCan;
+#[cfg(target_os = "windows")]
+type PlatformCan = crosscan::win_can::WindowsCan;
+
+// Number of received frames buffered before the interface task drops frames
+const RX_QUEUE_LEN: usize = 1024;
+const TX_QUEUE_LEN: usize = 64;
+
+type WriteRequest = (CanFrame, oneshot::Sender<std::io::Result<()>>);
+
+fn to_napi_err(error: std::io::Error) -> Error {
+    Error::from_reason(error.to_string())
+}
+
+/// A classic CAN frame
+#[napi(object)]
+pub struct JsCanFrame {
+    pub id: u32,
+    pub data: Vec<u8>,
+    pub extended: Option<bool>,
+    pub rtr: Option<bool>,
+    pub error: Option<bool>,
+}
+
+impl From<CanFrame> for JsCanFrame {
+    fn from(frame: CanFrame) -> Self {
+        Self {
+            id: frame.id(),
+            data: frame.data().to_vec(),
+            extended: Some(frame.is_extended()),
+            rtr: Some(frame.is_rtr()),
+            error: Some(frame.is_error()),
+        }
+    }
+}
+
+impl TryFrom<JsCanFrame> for CanFrame {
+    type Error = Error;
+
+    fn try_from(frame: JsCanFrame) -> Result<Self> {
+        let extended = frame.extended.unwrap_or(false);
+        let frame = if frame.error.unwrap_or(false) {
+            CanFrame::new_error(frame.id)
+        } else if frame.rtr.unwrap_or(false) {
+            CanFrame::new_remote(frame.id, frame.data.len(), extended)
+        } else if extended {
+            CanFrame::new_eff(frame.id, &frame.data)
+        } else {
+            CanFrame::new(frame.id, &frame.data)
+        };
+        frame.map_err(|e| Error::new(Status::InvalidArg, e))
+    }
+}
+
+/// Acceptance filter: a frame passes if `frame.id & mask == id & mask`
+#[napi(object)]
+#[derive(Clone, Copy)]
+pub struct JsCanFilter {
+    pub id: u32,
+    pub mask: u32,
+    pub extended: Option<bool>,
+}
+
+impl JsCanFilter {
+    fn matches(&self, frame: &CanFrame) -> bool {
+        frame.id() & self.mask == self.id & self.mask
+            && self.extended.is_none_or(|e| e == frame.is_extended())
+    }
+}
+
+/// An open CAN interface
+///
+/// If reads fall behind by more than 1024 frames, further frames are dropped and the next read
+/// rejects with the number of frames lost.
+#[napi]
+pub struct CanBus {
+    frames: Arc<tokio::sync::Mutex<mpsc::Receiver<std::io::Result<CanFrame>>>>,
+    writes: mpsc::Sender<WriteRequest>,
+    filters: Arc<Mutex<Vec<JsCanFilter>>>,
+    task: tokio::task::AbortHandle,
+}
+
+#[napi]
+impl CanBus {
+    /// Opens the CAN interface `interface`
+    #[napi(factory)]
+    pub async fn open(interface: String) -> Result<CanBus> {
+        let can = PlatformCan::open(&interface).await.map_err(to_napi_err)?;
+        let (frames_tx, frames_rx) = mpsc::channel(RX_QUEUE_LEN);
+        let (writes_tx, writes_rx) = mpsc::channel(TX_QUEUE_LEN);
+        let task = tokio::spawn(run_interface(can, frames_tx, writes_rx)).abort_handle();
+        Ok(CanBus {
+            frames: Arc::new(tokio::sync::Mutex::new(frames_rx)),
+            writes: writes_tx,
+            filters: Arc::new(Mutex::new(Vec::new())),
+            task,
+        })
+    }
+
+    /// Resolves with the next frame passing the filters, or null once the bus is closed
+    #[napi]
+    pub async fn read(&self) -> Result<Option<JsCanFrame>> {
+        let mut frames = self.frames.lock().await;
+        loop {
+            let Some(result) = frames.recv().await else {
+                return Ok(None);
+            };
+            let frame = result.map_err(to_napi_err)?;
+            let filters = self.filters.lock().unwrap();
+            if filters.is_empty() || filters.iter().any(|f| f.matches(&frame)) {
+                return Ok(Some(frame.into()));
+            }
+        }
+    }
+
+    /// Writes a frame, resolving once the interface has accepted it
+    #[napi]
+    pub async fn write(&self, frame: JsCanFrame) -> Result<()> {
+        let frame = CanFrame::try_from(frame)?;
+        let (done_tx, done_rx) = oneshot::channel();
+        let closed = || Error::from_reason("CanBus is closed");
+        self.writes
+            .send((frame, done_tx))
+            .await
+            .map_err(|_| closed())?;
+        done_rx.await.map_err(|_| closed())?.map_err(to_napi_err)
+    }
+
+    /// Replaces the acceptance filters. An empty list accepts all frames.
+    #[napi]
+    pub fn set_filters(&self, filters: Vec<JsCanFilter>) {
+        *self.filters.lock().unwrap() = filters;
+    }
+
+    /// Closes the interface. Pending and later reads resolve with null.
+    #[napi]
+    pub fn close(&self) {
+        self.task.abort();
+    }
+}
+
+async fn run_interface(
+    mut can: PlatformCan,
+    frames: mpsc::Sender<std::io::Result<CanFrame>>,
+    mut writes: mpsc::Receiver<WriteRequest>,
+) {
+    // Frames dropped because JavaScript fell behind, reported before the next frame
+    let mut dropped = 0u64;
+    loop {
+        tokio::select! {
+            result = can.read_frame() => {
+                let frame = match result {
+                    Ok(frame) => frame,
+                    Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                        Some(FramesDropped(count)) => {
+                            dropped += count;
+                            continue;
+                        }
+                        // Any other error ends the stream
+                        None => {
+                            let _ = frames.try_send(Err(e));
+                            return;
+                        }
+                    },
+                };
+                if dropped > 0 {
+                    match frames.try_send(Err(std::io::Error::other(FramesDropped(dropped)))) {
+                        Ok(()) => dropped = 0,
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            dropped += 1;
+                            continue;
+                        }
+                        Err(mpsc::error::TrySendError::Closed(_)) => return,
+                    }
+                }
+                match frames.try_send(Ok(frame)) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => dropped += 1,
+                    Err(mpsc::error::TrySendError::Closed(_)) => return,
+                }
+            }
+            request = writes.recv() => {
+                let Some((frame, done)) = request else {
+                    return;
+                };
+                let _ = done.send(can.write_frame(frame).await);
+            }
+        }
+    }
+}
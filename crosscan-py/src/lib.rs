@@ -0,0 +1,353 @@
+///
+/// crosscan-py
+///
+/// Python bindings for the crosscan platform backends. Calls block the calling thread with the
+/// GIL released, so other Python threads keep running while waiting for frames. Each Bus owns a
+/// thread that reads and writes the interface, so a pending recv never blocks send.
+///
+use crosscan::{CanInterface, FramesDropped, can};
+use pyo3::exceptions::{PyIOError, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use std::io::ErrorKind;
+use std::sync::{Mutex, mpsc as std_mpsc};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(target_os = "linux")]
+type PlatformCan = crosscan::lin_can::LinuxCan;
+#[cfg(target_os = "windows")]
+type PlatformCan = crosscan::win_can::WindowsCan;
+
+// Number of received frames buffered before the interface thread drops frames
+const RX_QUEUE_LEN: usize = 1024;
+const TX_QUEUE_LEN: usize = 64;
+
+pyo3::create_exception!(crosscan, FramesDroppedError, PyIOError);
+
+fn to_py_err(error: std::io::Error) -> PyErr {
+    if let Some(dropped) = error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<FramesDropped>())
+    {
+        return FramesDroppedError::new_err((dropped.0, dropped.to_string()));
+    }
+    match error.kind() {
+        ErrorKind::TimedOut => PyTimeoutError::new_err(error.to_string()),
+        ErrorKind::InvalidInput => PyValueError::new_err(error.to_string()),
+        _ => PyIOError::new_err(error.to_string()),
+    }
+}
+
+/// A classic CAN frame
+#[pyclass(name = "CanFrame", frozen, eq, from_py_object)]
+#[derive(Clone, PartialEq)]
+struct PyCanFrame(can::CanFrame);
+
+#[pymethods]
+impl PyCanFrame {
+    #[new]
+    #[pyo3(signature = (id, data = Vec::new(), is_extended = false))]
+    fn new(id: u32, data: Vec<u8>, is_extended: bool) -> PyResult<Self> {
+        let frame = if is_extended {
+            can::CanFrame::new_eff(id, &data)
+        } else {
+            can::CanFrame::new(id, &data)
+        };
+        frame.map(Self).map_err(PyValueError::new_err)
+    }
+
+    /// Creates a remote transmission request frame
+    #[staticmethod]
+    #[pyo3(signature = (id, dlc, is_extended = false))]
+    fn remote(id: u32, dlc: usize, is_extended: bool) -> PyResult<Self> {
+        can::CanFrame::new_remote(id, dlc, is_extended)
+            .map(Self)
+            .map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn id(&self) -> u32 {
+        self.0.id()
+    }
+
+    #[getter]
+    fn data(&self) -> Vec<u8> {
+        self.0.data().to_vec()
+    }
+
+    #[getter]
+    fn dlc(&self) -> usize {
+        self.0.dlc()
+    }
+
+    #[getter]
+    fn is_extended(&self) -> bool {
+        self.0.is_extended()
+    }
+
+    #[getter]
+    fn is_rtr(&self) -> bool {
+        self.0.is_rtr()
+    }
+
+    #[getter]
+    fn is_error(&self) -> bool {
+        self.0.is_error()
+    }
+
+    #[getter]
+    fn timestamp(&self) -> Option<u64> {
+        self.0.timestamp()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CanFrame(id=0x{:X}, data=bytes.fromhex('{}'), is_extended={})",
+            self.0.id(),
+            self.0
+                .data()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+            if self.0.is_extended() {
+                "True"
+            } else {
+                "False"
+            }
+        )
+    }
+}
+
+/// Acceptance filter: a frame passes if `frame.id & mask == id & mask`
+#[derive(Clone, Copy)]
+struct Filter {
+    id: u32,
+    mask: u32,
+    is_extended: Option<bool>,
+}
+
+impl Filter {
+    fn matches(&self, frame: &can::CanFrame) -> bool {
+        frame.id() & self.mask == self.id & self.mask
+            && self.is_extended.is_none_or(|e| e == frame.is_extended())
+    }
+}
+
+fn closed() -> std::io::Error {
+    std::io::Error::new(ErrorKind::NotConnected, "Bus has been shut down")
+}
+
+enum Request {
+    Write(can::CanFrame, oneshot::Sender<std::io::Result<()>>),
+    GetBitrate(oneshot::Sender<std::io::Result<Option<u32>>>),
+}
+
+/// An open CAN interface
+#[pyclass(name = "Bus")]
+struct PyBus {
+    frames: Mutex<std_mpsc::Receiver<std::io::Result<can::CanFrame>>>,
+    requests: Mutex<Option<mpsc::Sender<Request>>>,
+    filters: Vec<Filter>,
+}
+
+impl PyBus {
+    fn request<R>(
+        &self,
+        py: Python<'_>,
+        request: impl FnOnce(oneshot::Sender<std::io::Result<R>>) -> Request + Send,
+    ) -> PyResult<R>
+    where
+        R: Send,
+    {
+        py.detach(|| {
+            let requests = self.requests.lock().unwrap().clone().ok_or_else(closed)?;
+            let (done, result) = oneshot::channel();
+            requests
+                .blocking_send(request(done))
+                .map_err(|_| closed())?;
+            result.blocking_recv().map_err(|_| closed())?
+        })
+        .map_err(to_py_err)
+    }
+}
+
+#[pymethods]
+impl PyBus {
+    #[new]
+    fn new(py: Python<'_>, interface: String) -> PyResult<Self> {
+        let (frames_tx, frames) = std_mpsc::sync_channel(RX_QUEUE_LEN);
+        let (requests, requests_rx) = mpsc::channel(TX_QUEUE_LEN);
+        py.detach(|| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let can = runtime.block_on(PlatformCan::open(&interface))?;
+            std::thread::Builder::new()
+                .name(format!("crosscan-py-{}", interface))
+                .spawn(move || runtime.block_on(run_interface(can, frames_tx, requests_rx)))?;
+            Ok::<_, std::io::Error>(())
+        })
+        .map_err(to_py_err)?;
+        Ok(Self {
+            frames: Mutex::new(frames),
+            requests: Mutex::new(Some(requests)),
+            filters: Vec::new(),
+        })
+    }
+
+    /// Sets acceptance filters as dicts with `can_id`, `can_mask` and optional `extended` keys,
+    /// like python-can. None or an empty list accepts all frames.
+    #[pyo3(signature = (filters = None))]
+    fn set_filters(
+        &mut self,
+        filters: Option<Vec<Bound<'_, pyo3::types::PyDict>>>,
+    ) -> PyResult<()> {
+        let mut parsed = Vec::new();
+        for filter in filters.unwrap_or_default() {
+            let get = |key: &str| -> PyResult<Option<Bound<'_, PyAny>>> { filter.get_item(key) };
+            let id = get("can_id")?
+                .ok_or_else(|| PyValueError::new_err("Filter requires can_id"))?
+                .extract()?;
+            let mask = get("can_mask")?
+                .ok_or_else(|| PyValueError::new_err("Filter requires can_mask"))?
+                .extract()?;
+            let is_extended = get("extended")?.map(|e| e.extract()).transpose()?;
+            parsed.push(Filter {
+                id,
+                mask,
+                is_extended,
+            });
+        }
+        self.filters = parsed;
+        Ok(())
+    }
+
+    /// Receives the next frame passing the filters. Returns None if `timeout` seconds pass first
+    #[pyo3(signature = (timeout = None))]
+    fn recv(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Option<PyCanFrame>> {
+        let timeout = timeout
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let filters = &self.filters;
+        let frames = &self.frames;
+        py.detach(|| {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let frames = frames.lock().unwrap();
+            loop {
+                let frame = match deadline {
+                    Some(deadline) => {
+                        match frames
+                            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                        {
+                            Ok(result) => result?,
+                            Err(std_mpsc::RecvTimeoutError::Timeout) => return Ok(None),
+                            Err(std_mpsc::RecvTimeoutError::Disconnected) => return Err(closed()),
+                        }
+                    }
+                    None => frames.recv().map_err(|_| closed())??,
+                };
+                if filters.is_empty() || filters.iter().any(|f| f.matches(&frame)) {
+                    return Ok(Some(frame));
+                }
+            }
+        })
+        .map(|frame| frame.map(PyCanFrame))
+        .map_err(to_py_err)
+    }
+
+    /// Sends a frame
+    fn send(&self, py: Python<'_>, frame: PyCanFrame) -> PyResult<()> {
+        self.request(py, |done| Request::Write(frame.0, done))
+    }
+
+    /// Returns the configured bitrate, or None if the interface doesn't report one
+    fn bitrate(&self, py: Python<'_>) -> PyResult<Option<u32>> {
+        self.request(py, Request::GetBitrate)
+    }
+
+    /// Closes the interface. Later calls raise OSError, recv once the frames already received
+    /// have been read.
+    fn shutdown(&self, py: Python<'_>) {
+        // The interface thread exits once the last sender is gone
+        py.detach(|| self.requests.lock().unwrap().take());
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<Bound<'_, PyAny>>,
+        _exc_value: Option<Bound<'_, PyAny>>,
+        _traceback: Option<Bound<'_, PyAny>>,
+    ) {
+        self.shutdown(py);
+    }
+}
+
+async fn run_interface(
+    mut can: PlatformCan,
+    frames: std_mpsc::SyncSender<std::io::Result<can::CanFrame>>,
+    mut requests: mpsc::Receiver<Request>,
+) {
+    // Frames dropped because Python fell behind, reported before the next frame
+    let mut dropped = 0u64;
+    loop {
+        tokio::select! {
+            result = can.read_frame() => {
+                let frame = match result {
+                    Ok(frame) => frame,
+                    Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                        Some(FramesDropped(count)) => {
+                            dropped += count;
+                            continue;
+                        }
+                        // Any other error ends the stream
+                        None => {
+                            let _ = frames.try_send(Err(e));
+                            return;
+                        }
+                    },
+                };
+                if dropped > 0 {
+                    match frames.try_send(Err(std::io::Error::other(FramesDropped(dropped)))) {
+                        Ok(()) => dropped = 0,
+                        Err(std_mpsc::TrySendError::Full(_)) => {
+                            dropped += 1;
+                            continue;
+                        }
+                        Err(std_mpsc::TrySendError::Disconnected(_)) => return,
+                    }
+                }
+                match frames.try_send(Ok(frame)) {
+                    Ok(()) => {}
+                    Err(std_mpsc::TrySendError::Full(_)) => dropped += 1,
+                    Err(std_mpsc::TrySendError::Disconnected(_)) => return,
+                }
+            }
+            request = requests.recv() => match request {
+                Some(Request::Write(frame, done)) => {
+                    let _ = done.send(can.write_frame(frame).await);
+                }
+                Some(Request::GetBitrate(done)) => {
+                    let _ = done.send(can.get_bitrate().await);
+                }
+                None => return,
+            },
+        }
+    }
+}
+
+#[pymodule(name = "crosscan")]
+fn crosscan_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyCanFrame>()?;
+    m.add_class::<PyBus>()?;
+    m.add(
+        "FramesDroppedError",
+        m.py().get_type::<FramesDroppedError>(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,9 @@
+///
+/// uniffi-bindgen.rs
+///
+/// Generates the Kotlin and Swift bindings, e.g.
+/// `cargo run -p crosscan-uniffi --features cli --bin uniffi-bindgen -- generate --library <lib> --language kotlin --out-dir out`
+///
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,242 @@
+///
+/// crosscan-uniffi
+///
+/// Kotlin/Swift bindings generated with uniffi. The frame model and SocketCAN record codec are
+/// available on every target, so mobile apps talking to Bluetooth/TCP bridges share them with
+/// desktop code. CanBus is only available where crosscan has a platform backend.
+///
+use crosscan::can;
+
+uniffi::setup_scaffolding!();
+
+/// A classic CAN frame
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct CanFrame {
+    pub id: u32,
+    pub data: Vec<u8>,
+    pub is_extended: bool,
+    pub is_rtr: bool,
+    pub is_error: bool,
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Debug, uniffi::Error)]
+pub enum CanError {
+    InvalidFrame { message: String },
+    Io { message: String },
+    FramesDropped { count: u64 },
+}
+
+impl std::fmt::Display for CanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanError::InvalidFrame { message } => write!(f, "Invalid frame: {}", message),
+            CanError::Io { message } => write!(f, "{}", message),
+            CanError::FramesDropped { count } => {
+                write!(f, "{} frames were dropped before they could be read", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CanError {}
+
+impl From<std::io::Error> for CanError {
+    fn from(error: std::io::Error) -> Self {
+        if let Some(dropped) = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<crosscan::FramesDropped>())
+        {
+            return CanError::FramesDropped { count: dropped.0 };
+        }
+        CanError::Io {
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<can::CanFrame> for CanFrame {
+    fn from(frame: can::CanFrame) -> Self {
+        Self {
+            id: frame.id(),
+            data: frame.data().to_vec(),
+            is_extended: frame.is_extended(),
+            is_rtr: frame.is_rtr(),
+            is_error: frame.is_error(),
+            timestamp: frame.timestamp(),
+        }
+    }
+}
+
+impl TryFrom<CanFrame> for can::CanFrame {
+    type Error = CanError;
+
+    fn try_from(frame: CanFrame) -> Result<Self, CanError> {
+        let result = if frame.is_error {
+            can::CanFrame::new_error(frame.id)
+        } else if frame.is_rtr {
+            can::CanFrame::new_remote(frame.id, frame.data.len(), frame.is_extended)
+        } else if frame.is_extended {
+            can::CanFrame::new_eff(frame.id, &frame.data)
+        } else {
+            can::CanFrame::new(frame.id, &frame.data)
+        };
+        let mut converted = result.map_err(|e| CanError::InvalidFrame {
+            message: e.to_string(),
+        })?;
+        converted.set_timestamp(frame.timestamp);
+        Ok(converted)
+    }
+}
+
+/// Checks ID and data length of a frame
+#[uniffi::export]
+pub fn validate_frame(frame: CanFrame) -> Result<(), CanError> {
+    can::CanFrame::try_from(frame).map(|_| ())
+}
+
+/// Encodes a frame as a 16 byte SocketCAN record (struct can_frame with a big-endian ID)
+#[uniffi::export]
+pub fn encode_socketcan(frame: CanFrame) -> Result<Vec<u8>, CanError> {
+    let frame = can::CanFrame::try_from(frame)?;
+    Ok(crosscan::pcap::encode_socketcan(&frame).to_vec())
+}
+
+/// Decodes a SocketCAN record produced by `encode_socketcan` or a SocketCAN bridge
+#[uniffi::export]
+pub fn decode_socketcan(record: Vec<u8>) -> Result<CanFrame, CanError> {
+    Ok(crosscan::pcap::decode_socketcan(&record)?.into())
+}
+
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+mod bus {
+    use super::{CanError, CanFrame};
+    use crosscan::{CanInterface, FramesDropped};
+    use std::sync::{Mutex, mpsc as std_mpsc};
+    use std::time::Duration;
+    use tokio::sync::{mpsc, oneshot};
+
+    #[cfg(target_os = "linux")]
+    type PlatformCan = crosscan::lin_can::LinuxCan;
+    #[cfg(target_os = "windows")]
+    type PlatformCan = crosscan::win_can::WindowsCan;
+
+    // Number of received frames buffered before the interface thread drops frames
+    const RX_QUEUE_LEN: usize = 1024;
+    const TX_QUEUE_LEN: usize = 64;
+
+    type WriteRequest = (
+        crosscan::can::CanFrame,
+        oneshot::Sender<std::io::Result<()>>,
+    );
+
+    fn closed() -> CanError {
+        CanError::Io {
+            message: "Interface thread stopped".to_string(),
+        }
+    }
+
+    /// An open CAN interface. Calls block the calling thread.
+    ///
+    /// The interface is read and written on a thread of its own, so a pending read does not block
+    /// writes. If reads fall behind by more than 1024 frames, further frames are dropped and the
+    /// next read fails with `FramesDropped`.
+    #[derive(uniffi::Object)]
+    pub struct CanBus {
+        frames: Mutex<std_mpsc::Receiver<std::io::Result<crosscan::can::CanFrame>>>,
+        writes: mpsc::Sender<WriteRequest>,
+    }
+
+    #[uniffi::export]
+    impl CanBus {
+        #[uniffi::constructor]
+        pub fn open(interface: String) -> Result<Self, CanError> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let can = runtime.block_on(PlatformCan::open(&interface))?;
+            let (frames_tx, frames) = std_mpsc::sync_channel(RX_QUEUE_LEN);
+            let (writes, writes_rx) = mpsc::channel(TX_QUEUE_LEN);
+            std::thread::Builder::new()
+                .name(format!("crosscan-uniffi-{}", interface))
+                .spawn(move || runtime.block_on(run_interface(can, frames_tx, writes_rx)))?;
+            Ok(Self {
+                frames: Mutex::new(frames),
+                writes,
+            })
+        }
+
+        /// Reads the next frame. Returns None if `timeout_ms` passes first
+        pub fn read(&self, timeout_ms: Option<u64>) -> Result<Option<CanFrame>, CanError> {
+            let frames = self.frames.lock().unwrap();
+            let result = match timeout_ms {
+                Some(ms) => match frames.recv_timeout(Duration::from_millis(ms)) {
+                    Ok(result) => result,
+                    Err(std_mpsc::RecvTimeoutError::Timeout) => return Ok(None),
+                    Err(std_mpsc::RecvTimeoutError::Disconnected) => return Err(closed()),
+                },
+                None => frames.recv().map_err(|_| closed())?,
+            };
+            Ok(Some(result?.into()))
+        }
+
+        pub fn write(&self, frame: CanFrame) -> Result<(), CanError> {
+            let frame = crosscan::can::CanFrame::try_from(frame)?;
+            let (done, result) = oneshot::channel();
+            self.writes
+                .blocking_send((frame, done))
+                .map_err(|_| closed())?;
+            Ok(result.blocking_recv().map_err(|_| closed())??)
+        }
+    }
+
+    async fn run_interface(
+        mut can: PlatformCan,
+        frames: std_mpsc::SyncSender<std::io::Result<crosscan::can::CanFrame>>,
+        mut writes: mpsc::Receiver<WriteRequest>,
+    ) {
+        // Frames dropped because the app fell behind, reported before the next frame
+        let mut dropped = 0u64;
+        loop {
+            tokio::select! {
+                result = can.read_frame() => {
+                    let frame = match result {
+                        Ok(frame) => frame,
+                        Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                            Some(FramesDropped(count)) => {
+                                dropped += count;
+                                continue;
+                            }
+                            // Any other error ends the stream
+                            None => {
+                                let _ = frames.try_send(Err(e));
+                                return;
+                            }
+                        },
+                    };
+                    if dropped > 0 {
+                        match frames.try_send(Err(std::io::Error::other(FramesDropped(dropped)))) {
+                            Ok(()) => dropped = 0,
+                            Err(std_mpsc::TrySendError::Full(_)) => {
+                                dropped += 1;
+                                continue;
+                            }
+                            Err(std_mpsc::TrySendError::Disconnected(_)) => return,
+                        }
+                    }
+                    match frames.try_send(Ok(frame)) {
+                        Ok(()) => {}
+                        Err(std_mpsc::TrySendError::Full(_)) => dropped += 1,
+                        Err(std_mpsc::TrySendError::Disconnected(_)) => return,
+                    }
+                }
+                request = writes.recv() => {
+                    let Some((frame, done)) = request else {
+                        return;
+                    };
+                    let _ = done.send(can.write_frame(frame).await);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+///
+/// ros2_bridge.rs
+///
+/// socketcan_bridge-style ROS 2 node: publishes received frames on /received_messages and writes
+/// frames from /sent_messages to the bus.
+///
+/// cargo run --example ros2_bridge --features ros2 -- can0
+///
+use crosscan::{CanInterface, ros::RosCanFrame};
+use futures::StreamExt;
+use ros2_client::{
+    Context, DEFAULT_PUBLISHER_QOS, DEFAULT_SUBSCRIPTION_QOS, MessageTypeName, Name, NodeName,
+    NodeOptions,
+};
+use std::time::SystemTime;
+
+#[cfg(target_os = "linux")]
+type PlatformCan = crosscan::lin_can::LinuxCan;
+#[cfg(target_os = "windows")]
+type PlatformCan = crosscan::win_can::WindowsCan;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let interface = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "can0".to_string());
+    let mut can = PlatformCan::open(&interface).await?;
+
+    let context = Context::new()?;
+    let mut node = context.new_node(NodeName::new("/", "crosscan_bridge")?, NodeOptions::new())?;
+    tokio::spawn(node.spinner()?.spin());
+
+    let frame_type = MessageTypeName::new("can_msgs", "Frame");
+    let received_topic = node.create_topic(
+        &Name::new("/", "received_messages")?,
+        frame_type.clone(),
+        &DEFAULT_PUBLISHER_QOS,
+    )?;
+    let sent_topic = node.create_topic(
+        &Name::new("/", "sent_messages")?,
+        frame_type,
+        &DEFAULT_SUBSCRIPTION_QOS,
+    )?;
+    let publisher = node.create_publisher::<RosCanFrame>(&received_topic, None)?;
+    let subscription = node.create_subscription::<RosCanFrame>(&sent_topic, None)?;
+    let mut sent = Box::pin(subscription.async_stream());
+
+    loop {
+        tokio::select! {
+            frame = can.read_frame() => {
+                let message = RosCanFrame::from_frame(&frame?, SystemTime::now(), &interface);
+                if let Err(e) = publisher.async_publish(message).await {
+                    eprintln!("Publishing failed: {:?}", e);
+                }
+            }
+            Some(message) = sent.next() => match message {
+                Ok((message, _)) => match (&message).try_into() {
+                    Ok(frame) => can.write_frame(frame).await?,
+                    Err(e) => eprintln!("Ignoring invalid frame: {}", e),
+                },
+                Err(e) => eprintln!("Receive failed: {:?}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+#![no_main]
+///
+/// linux_raw.rs
+///
+/// Decodes arbitrary bytes as a Linux `struct can_frame`, and round trips arbitrary valid frames.
+///
+use crosscan::can::{CanFrame, Strictness};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (&[u8], CanFrame)| {
+    let (raw, mut frame) = input;
+    for strictness in [Strictness::Strict, Strictness::Preserve] {
+        if let Ok(decoded) = CanFrame::from_linux_raw_with(raw, strictness) {
+            let again = CanFrame::from_linux_raw_with(&decoded.to_linux_raw(), strictness);
+            assert_eq!(again.unwrap(), decoded);
+        }
+    }
+
+    // The raw layout has no timestamp
+    frame.set_timestamp(None);
+    assert_eq!(
+        CanFrame::from_linux_raw(&frame.to_linux_raw()).unwrap(),
+        frame
+    );
+});
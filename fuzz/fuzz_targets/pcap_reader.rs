@@ -0,0 +1,21 @@
+#![no_main]
+///
+/// pcap_reader.rs
+///
+/// Reads arbitrary bytes as a pcap or pcapng capture until the first error.
+///
+use crosscan::{can::Strictness, pcap::PcapReader};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for strictness in [Strictness::Strict, Strictness::Preserve] {
+        let Ok(reader) = PcapReader::new(data) else {
+            return;
+        };
+        for frame in reader.with_strictness(strictness) {
+            if frame.is_err() {
+                break;
+            }
+        }
+    }
+});
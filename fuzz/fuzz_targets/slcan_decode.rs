@@ -0,0 +1,17 @@
+#![no_main]
+///
+/// slcan_decode.rs
+///
+/// Decodes arbitrary text as SLCAN. Whatever decodes must encode back to an equivalent frame.
+///
+use crosscan::{can::Strictness, slcan};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    for strictness in [Strictness::Strict, Strictness::Preserve] {
+        if let Ok(frame) = slcan::decode_with(text, strictness) {
+            let encoded = slcan::encode(&frame).unwrap();
+            assert_eq!(slcan::decode_with(&encoded, strictness).unwrap(), frame);
+        }
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+///
+/// wire_body.rs
+///
+/// Decodes arbitrary bytes as a canserver frame body. Whatever decodes must survive a round trip.
+///
+use crosscan::{can::CanFrame, wire};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = wire::decode_body(data) {
+        let encoded = wire::encode_frame(&frame).unwrap();
+        let decoded: CanFrame = wire::decode_body(&encoded[wire::FRAME_HEADER_LEN..]).unwrap();
+        assert_eq!(decoded, frame);
+    }
+});
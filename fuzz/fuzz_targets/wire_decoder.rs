@@ -0,0 +1,19 @@
+#![no_main]
+///
+/// wire_decoder.rs
+///
+/// Feeds the canserver stream decoder arbitrarily split input. It must never panic and must not
+/// buffer more than one frame once it asks for more data.
+///
+use crosscan::wire::{FRAME_HEADER_LEN, FrameDecoder, MAX_FRAME_LEN};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|chunks: Vec<Vec<u8>>| {
+    let mut decoder: FrameDecoder = FrameDecoder::new();
+    for chunk in chunks {
+        decoder.push(&chunk);
+        // Every error consumes input, so this always ends
+        while !matches!(decoder.decode(), Ok(None)) {}
+        assert!(decoder.buffered() <= FRAME_HEADER_LEN + MAX_FRAME_LEN);
+    }
+});
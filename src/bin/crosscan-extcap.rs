@@ -0,0 +1,144 @@
+///
+/// crosscan-extcap.rs
+///
+/// Wireshark extcap plugin capturing from any crosscan backend.
+///
+/// Copy or link the binary into Wireshark's extcap directory. Interfaces are discovered from
+/// /sys/class/net on Linux and from the comma separated CROSSCAN_EXTCAP_INTERFACES variable.
+///
+use crosscan::{
+    CanInterface, FramesDropped,
+    pcap::{LINKTYPE_CAN_SOCKETCAN, PcapWriter},
+};
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Write};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(target_os = "linux")]
+type PlatformCan = crosscan::lin_can::LinuxCan;
+#[cfg(target_os = "windows")]
+type PlatformCan = crosscan::win_can::WindowsCan;
+
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1).cloned())
+}
+
+fn interfaces() -> Vec<String> {
+    let mut interfaces: Vec<String> = std::env::var("CROSSCAN_EXTCAP_INTERFACES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|i| i.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .collect();
+
+    #[cfg(target_os = "linux")]
+    interfaces.extend(crosscan::lin_can::can_interfaces());
+
+    interfaces.sort();
+    interfaces.dedup();
+    interfaces
+}
+
+// Captures until Wireshark closes the fifo. Frames lost on the way are reported as an error once
+// the capture stops, as pcap files cannot record them.
+async fn capture(interface: &str, fifo: &str) -> std::io::Result<()> {
+    let output: Box<dyn Write> = if fifo == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(fifo)?)
+    };
+    let mut pcap = PcapWriter::new(output)?;
+    pcap.flush()?;
+
+    let mut can = PlatformCan::open(interface).await?;
+    let mut dropped = 0;
+    let result = loop {
+        let frames = match can.read_frames().await {
+            Ok(frames) => frames,
+            Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                Some(FramesDropped(count)) => {
+                    dropped += count;
+                    continue;
+                }
+                None => break Err(e),
+            },
+        };
+        // Frames are stamped with their receive time, or the time their batch was read
+        let read_at = SystemTime::now();
+        let written = frames
+            .iter()
+            .try_for_each(|frame| {
+                let time = frame
+                    .timestamp()
+                    .map_or(read_at, |ns| UNIX_EPOCH + Duration::from_nanos(ns));
+                pcap.write_frame(frame, time)
+            })
+            .and_then(|()| pcap.flush());
+        if let Err(e) = written {
+            break Err(e);
+        }
+    };
+
+    match result {
+        // Wireshark closes the fifo when the capture is stopped
+        Err(e) if e.kind() == ErrorKind::BrokenPipe && dropped == 0 => Ok(()),
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Err(IoError::other(FramesDropped(dropped))),
+        result => result,
+    }
+}
+
+async fn run(args: &[String]) -> std::io::Result<()> {
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+
+    if has("--extcap-interfaces") {
+        println!(
+            "extcap {{version={}}}{{display=crosscan}}",
+            env!("CARGO_PKG_VERSION")
+        );
+        for interface in interfaces() {
+            println!("interface {{value={0}}}{{display=crosscan {0}}}", interface);
+        }
+    } else if has("--extcap-dlts") {
+        println!(
+            "dlt {{number={}}}{{name=CAN_SOCKETCAN}}{{display=SocketCAN}}",
+            LINKTYPE_CAN_SOCKETCAN
+        );
+    } else if has("--extcap-config") {
+        // No configuration options
+    } else if has("--capture") {
+        let (Some(interface), Some(fifo)) = (
+            arg_value(args, "--extcap-interface"),
+            arg_value(args, "--fifo"),
+        ) else {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "--capture requires --extcap-interface and --fifo",
+            ));
+        };
+        capture(&interface, &fifo).await.map_err(|e| {
+            IoError::new(e.kind(), format!("Capture on {} failed: {}", interface, e))
+        })?;
+    } else {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Usage: crosscan-extcap --extcap-interfaces | --extcap-dlts | --capture",
+        ));
+    }
+    Ok(())
+}
+
+// Wireshark shows what an extcap writes to stderr when it exits with a failure
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,363 @@
+///
+/// crosscan-monitor.rs
+///
+/// Live terminal view of the IDs on a bus, like cansniffer, for any crosscan backend.
+///
+/// Usage: crosscan-monitor <interface>. Keys: `s` cycles the sort column, `r` reverses the order,
+/// `/` edits the filter, `c` clears the table and `q` quits. The filter is a list of hex ID parts;
+/// an ID is shown if it contains any of them and none of those prefixed with `-`.
+///
+use crosscan::{
+    CanInterface, FramesDropped,
+    can::{CanFrame, FrameKey},
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+type PlatformCan = crosscan::lin_can::LinuxCan;
+#[cfg(target_os = "windows")]
+type PlatformCan = crosscan::win_can::WindowsCan;
+
+// Period over which the frame rate of an ID is measured
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+// How long a changed payload byte stays highlighted
+const HIGHLIGHT: Duration = Duration::from_secs(1);
+const REFRESH: Duration = Duration::from_millis(100);
+
+struct IdStats {
+    frame: CanFrame,
+    count: u64,
+    last_seen: Instant,
+    // When each payload byte last changed
+    changed: [Option<Instant>; 8],
+    window_start: Instant,
+    window_count: u64,
+    rate_hz: f64,
+}
+
+#[derive(Default)]
+struct Monitor {
+    ids: HashMap<FrameKey, IdStats>,
+    error_frames: u64,
+    dropped: u64,
+    read_error: Option<String>,
+}
+
+impl Monitor {
+    fn observe(&mut self, frame: &CanFrame, now: Instant) {
+        if frame.is_error() {
+            self.error_frames += 1;
+            return;
+        }
+        let Some(stats) = self.ids.get_mut(&FrameKey::id(frame)) else {
+            self.ids.insert(
+                FrameKey::id(frame),
+                IdStats {
+                    frame: frame.clone(),
+                    count: 1,
+                    last_seen: now,
+                    changed: [None; 8],
+                    window_start: now,
+                    window_count: 1,
+                    rate_hz: 0.0,
+                },
+            );
+            return;
+        };
+
+        let (old, new) = (stats.frame.data(), frame.data());
+        for (i, changed) in stats.changed.iter_mut().enumerate() {
+            if old.get(i) != new.get(i) {
+                *changed = Some(now);
+            }
+        }
+        stats.frame = frame.clone();
+        stats.count += 1;
+        stats.last_seen = now;
+        stats.window_count += 1;
+        let elapsed = now.duration_since(stats.window_start);
+        if elapsed >= RATE_WINDOW {
+            stats.rate_hz = stats.window_count as f64 / elapsed.as_secs_f64();
+            stats.window_start = now;
+            stats.window_count = 0;
+        }
+    }
+}
+
+impl IdStats {
+    // The measured rate, or 0 once the ID has been silent for a full window
+    fn rate_hz(&self, now: Instant) -> f64 {
+        if now.duration_since(self.last_seen) > RATE_WINDOW {
+            0.0
+        } else {
+            self.rate_hz
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Id,
+    Rate,
+    Count,
+    Age,
+}
+
+impl SortBy {
+    fn next(self) -> Self {
+        match self {
+            SortBy::Id => SortBy::Rate,
+            SortBy::Rate => SortBy::Count,
+            SortBy::Count => SortBy::Age,
+            SortBy::Age => SortBy::Id,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortBy::Id => "ID",
+            SortBy::Rate => "rate",
+            SortBy::Count => "count",
+            SortBy::Age => "age",
+        }
+    }
+}
+
+struct View {
+    interface: String,
+    sort_by: SortBy,
+    reverse: bool,
+    filter: String,
+    editing_filter: bool,
+}
+
+impl View {
+    fn matches(&self, id: &str) -> bool {
+        let mut included = None;
+        for term in self.filter.split_whitespace() {
+            let term = term.to_uppercase();
+            match term.strip_prefix('-') {
+                Some(excluded) => {
+                    if id.contains(excluded) {
+                        return false;
+                    }
+                }
+                None => included = Some(included.unwrap_or(false) || id.contains(&term)),
+            }
+        }
+        included.unwrap_or(true)
+    }
+
+    fn draw(&self, frame: &mut ratatui::Frame, monitor: &Monitor) {
+        let now = Instant::now();
+        let id_text = |stats: &IdStats| {
+            if stats.frame.is_extended() {
+                format!("{:08X}", stats.frame.id())
+            } else {
+                format!("{:03X}", stats.frame.id())
+            }
+        };
+
+        let mut ids: Vec<(String, &IdStats)> = monitor
+            .ids
+            .values()
+            .map(|stats| (id_text(stats), stats))
+            .filter(|(id, _)| self.matches(id))
+            .collect();
+        ids.sort_by(|(_, a), (_, b)| match self.sort_by {
+            SortBy::Id => {
+                (a.frame.is_extended(), a.frame.id()).cmp(&(b.frame.is_extended(), b.frame.id()))
+            }
+            SortBy::Rate => b.rate_hz(now).total_cmp(&a.rate_hz(now)),
+            SortBy::Count => b.count.cmp(&a.count),
+            SortBy::Age => a.last_seen.cmp(&b.last_seen).reverse(),
+        });
+        if self.reverse {
+            ids.reverse();
+        }
+
+        let rows = ids.iter().map(|(id, stats)| {
+            let payload = if stats.frame.is_rtr() {
+                Line::from("remote request")
+            } else {
+                Line::from(
+                    stats
+                        .frame
+                        .data()
+                        .iter()
+                        .zip(stats.changed)
+                        .map(|(byte, changed)| {
+                            let style =
+                                if changed.is_some_and(|t| now.duration_since(t) < HIGHLIGHT) {
+                                    Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                                } else {
+                                    Style::new()
+                                };
+                            Span::styled(format!("{:02X} ", byte), style)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            };
+            Row::new([
+                Cell::from(id.clone()),
+                Cell::from(stats.frame.dlc().to_string()),
+                Cell::from(payload),
+                Cell::from(format!("{:.1}", stats.rate_hz(now))),
+                Cell::from(stats.count.to_string()),
+                Cell::from(format!(
+                    "{:.1}",
+                    now.duration_since(stats.last_seen).as_secs_f64()
+                )),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(9),
+                Constraint::Length(4),
+                Constraint::Length(25),
+                Constraint::Length(9),
+                Constraint::Length(10),
+                Constraint::Length(8),
+            ],
+        )
+        .header(
+            Row::new(["ID", "DLC", "Data", "Rate/s", "Count", "Age/s"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(format!(" crosscan-monitor {} ", self.interface)));
+
+        let total_rate: f64 = monitor.ids.values().map(|stats| stats.rate_hz(now)).sum();
+        let mut status = format!(
+            "{}/{} IDs  {:.0} frames/s  {} error frames  {} dropped  sort: {}{}  ",
+            ids.len(),
+            monitor.ids.len(),
+            total_rate,
+            monitor.error_frames,
+            monitor.dropped,
+            self.sort_by.name(),
+            if self.reverse { " (reversed)" } else { "" },
+        );
+        if self.editing_filter {
+            status.push_str(&format!(
+                "filter: {}_  (Enter to apply, Esc to clear)",
+                self.filter
+            ));
+        } else if !self.filter.is_empty() {
+            status.push_str(&format!("filter: {}", self.filter));
+        } else {
+            status.push_str("q quit  s sort  r reverse  / filter  c clear");
+        }
+        let status = match &monitor.read_error {
+            Some(e) => Line::styled(format!("Read failed: {}", e), Style::new().fg(Color::Red)),
+            None => Line::from(status),
+        };
+
+        let [table_area, status_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        frame.render_widget(table, table_area);
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+}
+
+async fn read_loop(mut can: PlatformCan, monitor: Arc<Mutex<Monitor>>) {
+    loop {
+        match can.read_frames().await {
+            Ok(frames) => {
+                let now = Instant::now();
+                let mut monitor = monitor.lock().unwrap();
+                for frame in &frames {
+                    monitor.observe(frame, now);
+                }
+            }
+            Err(e) => {
+                let mut monitor = monitor.lock().unwrap();
+                if let Some(dropped) = e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                    monitor.dropped += dropped.0;
+                    continue;
+                }
+                monitor.read_error = Some(e.to_string());
+                return;
+            }
+        }
+    }
+}
+
+fn run(terminal: &mut ratatui::DefaultTerminal, mut view: View, monitor: &Mutex<Monitor>) {
+    loop {
+        if terminal
+            .draw(|frame| view.draw(frame, &monitor.lock().unwrap()))
+            .is_err()
+        {
+            return;
+        }
+        if !event::poll(REFRESH).unwrap_or(false) {
+            continue;
+        }
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if view.editing_filter {
+            match key.code {
+                KeyCode::Enter => view.editing_filter = false,
+                KeyCode::Esc => {
+                    view.filter.clear();
+                    view.editing_filter = false;
+                }
+                KeyCode::Backspace => {
+                    view.filter.pop();
+                }
+                KeyCode::Char(c) => view.filter.push(c),
+                _ => {}
+            }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return,
+            KeyCode::Char('s') => view.sort_by = view.sort_by.next(),
+            KeyCode::Char('r') => view.reverse = !view.reverse,
+            KeyCode::Char('/') => view.editing_filter = true,
+            KeyCode::Char('c') => *monitor.lock().unwrap() = Monitor::default(),
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let Some(interface) = std::env::args().nth(1) else {
+        eprintln!("Usage: crosscan-monitor <interface>");
+        std::process::exit(1);
+    };
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the Tokio runtime");
+    let can = match runtime.block_on(PlatformCan::open(&interface)) {
+        Ok(can) => can,
+        Err(e) => {
+            eprintln!("Opening {} failed: {}", interface, e);
+            std::process::exit(1);
+        }
+    };
+    let monitor = Arc::new(Mutex::new(Monitor::default()));
+    runtime.spawn(read_loop(can, monitor.clone()));
+
+    let view = View {
+        interface,
+        sort_by: SortBy::Id,
+        reverse: false,
+        filter: String::new(),
+        editing_filter: false,
+    };
+    let mut terminal = ratatui::init();
+    run(&mut terminal, view, &monitor);
+    ratatui::restore();
+}
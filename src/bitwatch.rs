@@ -0,0 +1,208 @@
+///
+/// bitwatch.rs
+///
+/// Tracks payload bit changes per ID and correlates them with user-marked events.
+///
+use crate::can::CanFrame;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+/// A single payload bit of an ID. `bit` 0 is the least significant bit of the byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BitRef {
+    pub id: u32,
+    pub extended: bool,
+    pub byte: u8,
+    pub bit: u8,
+}
+
+/// How strongly a bit is correlated with the marks of a label
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BitCorrelation {
+    pub bit: BitRef,
+    /// Number of marks followed by a change of the bit within the window
+    pub hits: u32,
+    /// Number of marks with the label
+    pub marks: u32,
+    /// Number of times the bit changed over the whole observation
+    pub total_changes: u64,
+    /// `hits / marks`, discounted by how likely the bit is to change in any window anyway
+    pub score: f64,
+}
+
+struct IdBits {
+    last: Vec<u8>,
+    changes: [[u64; 8]; 8],
+}
+
+struct OpenMark {
+    label: String,
+    time: SystemTime,
+    bits: HashSet<BitRef>,
+}
+
+#[derive(Default)]
+struct LabelStats {
+    marks: u32,
+    hits: HashMap<BitRef, u32>,
+}
+
+/// Tracks which payload bits change and which of them follow user-marked events
+///
+/// Feed every received frame into `observe()` and call `mark()` when the event of interest
+/// happens (a button press, a door opening). Bits that change within `window` after the marks of a
+/// label are ranked by `correlate()`. Bits that change all the time, like counters and checksums,
+/// score low because they would also change in a window without a mark.
+pub struct BitWatch {
+    window: Duration,
+    ids: HashMap<(bool, u32), IdBits>,
+    first_seen: Option<SystemTime>,
+    last_seen: Option<SystemTime>,
+    open_marks: Vec<OpenMark>,
+    labels: HashMap<String, LabelStats>,
+}
+
+impl BitWatch {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            ids: HashMap::new(),
+            first_seen: None,
+            last_seen: None,
+            open_marks: Vec::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Records that the event `label` happened at `time`
+    pub fn mark(&mut self, label: &str, time: SystemTime) {
+        self.open_marks.push(OpenMark {
+            label: label.to_string(),
+            time,
+            bits: HashSet::new(),
+        });
+    }
+
+    /// Adds a frame received at `time`. Error and remote frames are ignored.
+    pub fn observe(&mut self, frame: &CanFrame, time: SystemTime) {
+        if frame.is_error() || frame.is_rtr() {
+            return;
+        }
+        self.first_seen.get_or_insert(time);
+        self.last_seen = Some(time);
+        self.close_marks(time);
+
+        let key = (frame.is_extended(), frame.id());
+        let data = frame.data();
+        let Some(state) = self.ids.get_mut(&key) else {
+            self.ids.insert(
+                key,
+                IdBits {
+                    last: data.to_vec(),
+                    changes: [[0; 8]; 8],
+                },
+            );
+            return;
+        };
+
+        for (byte, (old, new)) in state.last.iter().zip(data).enumerate().take(8) {
+            let diff = old ^ new;
+            for bit in (0..8).filter(|bit| diff & (1 << bit) != 0) {
+                state.changes[byte][bit] += 1;
+                let bit_ref = BitRef {
+                    id: frame.id(),
+                    extended: frame.is_extended(),
+                    byte: byte as u8,
+                    bit: bit as u8,
+                };
+                for mark in self.open_marks.iter_mut().filter(|m| m.time <= time) {
+                    mark.bits.insert(bit_ref);
+                }
+            }
+        }
+        state.last = data.to_vec();
+    }
+
+    /// Returns how often each bit of an ID changed, indexed by byte and bit
+    pub fn change_counts(&self, id: u32, extended: bool) -> Option<[[u64; 8]; 8]> {
+        self.ids.get(&(extended, id)).map(|state| state.changes)
+    }
+
+    /// Returns the labels that have been marked
+    pub fn labels(&self) -> Vec<&str> {
+        let mut labels: HashSet<&str> = self.labels.keys().map(String::as_str).collect();
+        labels.extend(self.open_marks.iter().map(|m| m.label.as_str()));
+        let mut labels: Vec<_> = labels.into_iter().collect();
+        labels.sort_unstable();
+        labels
+    }
+
+    /// Ranks the bits that changed after the marks of `label`, best match first
+    ///
+    /// Marks whose window has not ended yet are included with the changes seen so far.
+    pub fn correlate(&self, label: &str) -> Vec<BitCorrelation> {
+        let mut marks = 0;
+        let mut hits: HashMap<BitRef, u32> = HashMap::new();
+        if let Some(stats) = self.labels.get(label) {
+            marks += stats.marks;
+            hits.extend(stats.hits.iter().map(|(bit, n)| (*bit, *n)));
+        }
+        for mark in self.open_marks.iter().filter(|m| m.label == label) {
+            marks += 1;
+            for bit in &mark.bits {
+                *hits.entry(*bit).or_default() += 1;
+            }
+        }
+        if marks == 0 {
+            return Vec::new();
+        }
+
+        let span = match (self.first_seen, self.last_seen) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or_default(),
+            _ => Duration::ZERO,
+        };
+
+        let mut correlations: Vec<_> = hits
+            .into_iter()
+            .map(|(bit, hits)| {
+                let total_changes =
+                    self.ids[&(bit.extended, bit.id)].changes[bit.byte as usize][bit.bit as usize];
+                // Probability that the bit changes within a window by chance
+                let background = if span.is_zero() {
+                    0.0
+                } else {
+                    (total_changes as f64 * self.window.as_secs_f64() / span.as_secs_f64()).min(1.0)
+                };
+                BitCorrelation {
+                    bit,
+                    hits,
+                    marks,
+                    total_changes,
+                    score: hits as f64 / marks as f64 * (1.0 - background),
+                }
+            })
+            .collect();
+        correlations.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.bit.cmp(&b.bit)));
+        correlations
+    }
+
+    // Folds the marks whose window ended before `now` into the label statistics
+    fn close_marks(&mut self, now: SystemTime) {
+        let window = self.window;
+        if !self.open_marks.iter().any(|m| m.time + window < now) {
+            return;
+        }
+        let (closed, open): (Vec<_>, Vec<_>) = std::mem::take(&mut self.open_marks)
+            .into_iter()
+            .partition(|m| m.time + window < now);
+        self.open_marks = open;
+
+        for mark in closed {
+            let stats = self.labels.entry(mark.label).or_default();
+            stats.marks += 1;
+            for bit in mark.bits {
+                *stats.hits.entry(bit).or_default() += 1;
+            }
+        }
+    }
+}
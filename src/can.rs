@@ -3,9 +3,45 @@
 ///
 /// Provides an abstracted CanFrame data struct.
 ///
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// Size of Linux `struct can_frame`
+pub const LINUX_CAN_FRAME_LEN: usize = 16;
+
+// can_id flags and masks of Linux `struct can_frame`
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_SFF_MASK: u32 = 0x7FF;
+const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+
+/// How strictly frames received from a bus or decoded from a log are validated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Strictness {
+    /// Reject frames that are not valid classic CAN frames
+    #[default]
+    Strict,
+    /// Keep technically invalid frames that still have an unambiguous meaning, so that gateways
+    /// can forward them unchanged. A DLC of 9 to 15 is kept in `len8_dlc()` with 8 data bytes.
+    Preserve,
+}
+
+/// A classic CAN frame
+///
+/// Human-readable serde formats (JSON, TOML, ...) use the following stable schema; binary formats
+/// such as bincode keep the compact field layout used on the Windows canserver pipe.
+///
+/// ```json
+/// {"id": "1A3", "extended": false, "rtr": false, "error": false, "dlc": 3, "data": "0102FF", "timestamp": null}
+/// ```
+///
+/// - `id`: identifier as hexadecimal string without prefix
+/// - `extended`, `rtr`, `error`: frame flags, optional on input (default false)
+/// - `dlc`: data length code, optional on input for data frames (defaults to the data length)
+/// - `data`: payload as hexadecimal string, empty for remote and error frames. Either case is
+///   accepted on input.
+/// - `timestamp`: receive timestamp or null, optional on input
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CanFrame {
     id: u32,
     data: [u8; 8],
@@ -14,49 +50,50 @@ pub struct CanFrame {
     is_rtr: bool,
     is_error: bool,
     timestamp: Option<u64>,
+    // DLC of 9 to 15 sent or received for 8 data bytes, 0 if unused
+    len8_dlc: u8,
 }
 
 impl CanFrame {
     /// Create a new Standard ID CAN data frame
-    pub fn new(id: u32, data: &[u8]) -> Result<Self, &'static str> {
-        Self::validate_id(id, false)?;
-        Self::validate_data(data)?;
-        let mut buf = [0u8; 8];
-        buf[..data.len()].copy_from_slice(data);
-        Ok(Self {
-            id,
-            data: buf,
-            dlc: data.len(),
-            is_extended: false,
-            is_rtr: false,
-            is_error: false,
-            timestamp: None,
-        })
+    pub const fn new(id: u32, data: &[u8]) -> Result<Self, &'static str> {
+        Self::new_data(id, data, false)
     }
 
     /// Create a new Extended ID CAN data frame
-    pub fn new_eff(id: u32, data: &[u8]) -> Result<Self, &'static str> {
-        Self::validate_id(id, true)?;
-        Self::validate_data(data)?;
+    pub const fn new_eff(id: u32, data: &[u8]) -> Result<Self, &'static str> {
+        Self::new_data(id, data, true)
+    }
+
+    const fn new_data(id: u32, data: &[u8], is_extended: bool) -> Result<Self, &'static str> {
+        if let Err(e) = Self::validate_id(id, is_extended) {
+            return Err(e);
+        }
+        if let Err(e) = Self::validate_data(data) {
+            return Err(e);
+        }
         let mut buf = [0u8; 8];
-        buf[..data.len()].copy_from_slice(data);
+        buf.split_at_mut(data.len()).0.copy_from_slice(data);
         Ok(Self {
             id,
             data: buf,
             dlc: data.len(),
-            is_extended: true,
+            is_extended,
             is_rtr: false,
             is_error: false,
             timestamp: None,
+            len8_dlc: 0,
         })
     }
 
     /// Create a new CAN remote frame
-    pub fn new_remote(id: u32, dlc: usize, is_extended: bool) -> Result<Self, &'static str> {
+    pub const fn new_remote(id: u32, dlc: usize, is_extended: bool) -> Result<Self, &'static str> {
         if dlc > 8 {
             return Err("RTR frame DLC must be <= 8");
         }
-        Self::validate_id(id, is_extended)?;
+        if let Err(e) = Self::validate_id(id, is_extended) {
+            return Err(e);
+        }
         Ok(Self {
             id,
             data: [0u8; 8],
@@ -65,11 +102,12 @@ impl CanFrame {
             is_rtr: true,
             is_error: false,
             timestamp: None,
+            len8_dlc: 0,
         })
     }
 
     /// Create a new CAN error frame
-    pub fn new_error(id: u32) -> Result<Self, &'static str> {
+    pub const fn new_error(id: u32) -> Result<Self, &'static str> {
         if id > 0x1FFFFFFF {
             return Err("CAN error frame ID must be <= 29 bits");
         }
@@ -81,18 +119,73 @@ impl CanFrame {
             is_rtr: false,
             is_error: true,
             timestamp: None,
+            len8_dlc: 0,
         })
     }
 
+    /// Like `new()`, for tables of frames built at compile time
+    ///
+    /// Panics if the ID or data is invalid, which fails the build when evaluated in a `const` or
+    /// `static` item, e.g. `static COMMANDS: [CanFrame; 2] = [CanFrame::standard(...), ...]`.
+    pub const fn standard(id: u32, data: &[u8]) -> Self {
+        match Self::new(id, data) {
+            Ok(frame) => frame,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `new_eff()`, for tables of frames built at compile time. Panics like `standard()`.
+    pub const fn extended(id: u32, data: &[u8]) -> Self {
+        match Self::new_eff(id, data) {
+            Ok(frame) => frame,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `new_remote()`, for tables of frames built at compile time. Panics like `standard()`.
+    pub const fn remote(id: u32, dlc: usize, is_extended: bool) -> Self {
+        match Self::new_remote(id, dlc, is_extended) {
+            Ok(frame) => frame,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
     pub fn set_timestamp(&mut self, ts: Option<u64>) {
         self.timestamp = ts;
     }
 
-    pub fn timestamp(&self) -> Option<u64> {
+    pub const fn timestamp(&self) -> Option<u64> {
         self.timestamp
     }
 
-    fn validate_id(id: u32, extended: bool) -> Result<(), &'static str> {
+    /// Returns the DLC of 9 to 15 the frame was sent with, if any. All of them mean 8 data bytes.
+    ///
+    /// Received frames only carry it with `Strictness::Preserve`.
+    pub fn len8_dlc(&self) -> Option<u8> {
+        (self.len8_dlc > 8).then_some(self.len8_dlc)
+    }
+
+    /// Sets a DLC of 9 to 15 to send instead of 8, or clears it with None
+    ///
+    /// Only valid for data and remote frames with a DLC of 8. Linux only puts it on the bus if the
+    /// interface has the `cc-len8-dlc` control mode enabled.
+    pub fn set_len8_dlc(&mut self, len8_dlc: Option<u8>) -> Result<(), &'static str> {
+        match len8_dlc {
+            None => self.len8_dlc = 0,
+            Some(dlc) => {
+                if !(9..=15).contains(&dlc) {
+                    return Err("len8_dlc must be between 9 and 15");
+                }
+                if self.is_error || self.dlc != 8 {
+                    return Err("len8_dlc requires a data or remote frame with a DLC of 8");
+                }
+                self.len8_dlc = dlc;
+            }
+        }
+        Ok(())
+    }
+
+    const fn validate_id(id: u32, extended: bool) -> Result<(), &'static str> {
         if extended {
             if id > 0x1FFFFFFF {
                 return Err("Extended ID must be <= 29 bits (0x1FFFFFFF)");
@@ -105,7 +198,7 @@ impl CanFrame {
         Ok(())
     }
 
-    fn validate_data(data: &[u8]) -> Result<(), &'static str> {
+    const fn validate_data(data: &[u8]) -> Result<(), &'static str> {
         if data.len() > 8 {
             Err("CAN data must be <= 8 bytes")
         } else {
@@ -113,24 +206,729 @@ impl CanFrame {
         }
     }
 
-    pub fn id(&self) -> u32 {
+    /// Encodes the frame in the memory layout of Linux `struct can_frame` (host byte order)
+    pub fn to_linux_raw(&self) -> [u8; LINUX_CAN_FRAME_LEN] {
+        let mut id = self.id;
+        if self.is_extended {
+            id |= CAN_EFF_FLAG;
+        }
+        if self.is_rtr {
+            id |= CAN_RTR_FLAG;
+        }
+        if self.is_error {
+            id |= CAN_ERR_FLAG;
+        }
+
+        let mut raw = [0u8; LINUX_CAN_FRAME_LEN];
+        raw[..4].copy_from_slice(&id.to_ne_bytes());
+        raw[4] = self.dlc as u8;
+        raw[7] = self.len8_dlc;
+        if !self.is_rtr {
+            raw[8..].copy_from_slice(&self.data);
+        }
+        raw
+    }
+
+    /// Decodes a frame from the memory layout of Linux `struct can_frame` (host byte order)
+    ///
+    /// CAN FD frames (`struct canfd_frame`) are not supported because CanFrame is classic CAN only.
+    pub fn from_linux_raw(raw: &[u8]) -> Result<Self, &'static str> {
+        Self::from_linux_raw_with(raw, Strictness::Strict)
+    }
+
+    /// Like `from_linux_raw()`, with `Strictness::Preserve` keeping a length of 9 to 15 or the
+    /// `len8_dlc` field as `len8_dlc()`
+    pub fn from_linux_raw_with(raw: &[u8], strictness: Strictness) -> Result<Self, &'static str> {
+        let raw: &[u8; LINUX_CAN_FRAME_LEN] = raw
+            .try_into()
+            .map_err(|_| "struct can_frame must be 16 bytes")?;
+        let id = u32::from_ne_bytes(raw[..4].try_into().unwrap());
+        let (len, len8_dlc) = match (strictness, raw[4]) {
+            (Strictness::Strict, len) if len > 8 => return Err("CAN data must be <= 8 bytes"),
+            (Strictness::Preserve, len @ 9..=15) => (8, Some(len)),
+            (Strictness::Preserve, 8) if (9..=15).contains(&raw[7]) => (8, Some(raw[7])),
+            (_, len) if len > 8 => return Err("CAN DLC must be <= 15"),
+            (_, len) => (len as usize, None),
+        };
+
+        let mut frame = if id & CAN_ERR_FLAG != 0 {
+            Self::new_error(id & CAN_EFF_MASK)
+        } else if id & CAN_RTR_FLAG != 0 {
+            Self::new_remote(id & CAN_EFF_MASK, len, id & CAN_EFF_FLAG != 0)
+        } else if id & CAN_EFF_FLAG != 0 {
+            Self::new_eff(id & CAN_EFF_MASK, &raw[8..8 + len])
+        } else {
+            Self::new(id & CAN_SFF_MASK, &raw[8..8 + len])
+        }?;
+        if !frame.is_error {
+            frame.set_len8_dlc(len8_dlc)?;
+        }
+        Ok(frame)
+    }
+
+    pub const fn id(&self) -> u32 {
         self.id
     }
     pub fn data(&self) -> &[u8] {
         &self.data[..self.dlc]
     }
-    pub fn dlc(&self) -> usize {
+    pub const fn dlc(&self) -> usize {
         self.dlc
     }
-    pub fn is_extended(&self) -> bool {
+    pub const fn is_extended(&self) -> bool {
         self.is_extended
     }
-    pub fn is_rtr(&self) -> bool {
+    /// The ID in hex, 3 digits for standard and 8 for extended IDs like `candump`
+    pub fn id_hex(&self) -> String {
+        if self.is_extended {
+            format!("{:08X}", self.id)
+        } else {
+            format!("{:03X}", self.id)
+        }
+    }
+    pub const fn is_rtr(&self) -> bool {
         self.is_rtr
     }
-    pub fn is_error(&self) -> bool {
+    pub const fn is_error(&self) -> bool {
         self.is_error
     }
+
+    /// Reads the `len` bit field starting at bit `start` of the payload
+    ///
+    /// Bits are numbered in little-endian (Intel) order like a DBC signal with `@1`: bit `start` is
+    /// bit `start % 8` of byte `start / 8` and becomes the least significant bit of the result.
+    /// Returns None if `len` is 0 or above 64, or the field does not fit in the payload.
+    pub fn get_bits(&self, start: usize, len: usize) -> Option<u64> {
+        self.check_bits(start, len).ok()?;
+        Some((self.payload_word() >> start) & bit_mask(len))
+    }
+
+    /// Writes `value` into the `len` bit field starting at bit `start` of the payload, numbered
+    /// like `get_bits()`. The other bits are left unchanged.
+    pub fn set_bits(&mut self, start: usize, len: usize, value: u64) -> Result<(), &'static str> {
+        self.check_bits(start, len)?;
+        if value & !bit_mask(len) != 0 {
+            return Err("Value does not fit in the bit field");
+        }
+        let word = self.payload_word() & !(bit_mask(len) << start) | value << start;
+        let dlc = self.dlc;
+        self.payload_mut()?
+            .copy_from_slice(&word.to_le_bytes()[..dlc]);
+        Ok(())
+    }
+
+    fn check_bits(&self, start: usize, len: usize) -> Result<(), &'static str> {
+        if len == 0 || len > 64 {
+            return Err("Bit field length must be between 1 and 64");
+        }
+        match start.checked_add(len) {
+            Some(end) if end <= self.dlc * 8 => Ok(()),
+            _ => Err("Bit field does not fit in the payload"),
+        }
+    }
+
+    // The payload as a little-endian integer, zero padded to 8 bytes
+    fn payload_word(&self) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes[..self.dlc].copy_from_slice(self.data());
+        u64::from_le_bytes(bytes)
+    }
+
+    fn payload_mut(&mut self) -> Result<&mut [u8], &'static str> {
+        if self.is_rtr || self.is_error {
+            return Err("Remote and error frames have no payload");
+        }
+        Ok(&mut self.data[..self.dlc])
+    }
+}
+
+fn bit_mask(len: usize) -> u64 {
+    u64::MAX >> (64 - len)
+}
+
+// Generates a getter and a setter for a value stored at a byte offset of the payload
+macro_rules! payload_accessors {
+    ($($ty:ty: $get:ident, $set:ident, $from:ident, $to:ident, $order:literal;)*) => {
+        impl CanFrame {
+            $(
+                #[doc = concat!("Reads the ", $order, " `", stringify!($ty), "` at byte `offset` of the payload")]
+                ///
+                /// Returns None if it does not fit in the payload.
+                pub fn $get(&self, offset: usize) -> Option<$ty> {
+                    let bytes = self.data().get(offset..)?.get(..size_of::<$ty>())?;
+                    Some(<$ty>::$from(bytes.try_into().unwrap()))
+                }
+
+                #[doc = concat!("Writes `value` as ", $order, " `", stringify!($ty), "` at byte `offset` of the payload")]
+                ///
+                /// The payload length is not changed, the value must fit within it.
+                pub fn $set(&mut self, offset: usize, value: $ty) -> Result<(), &'static str> {
+                    let bytes = value.$to();
+                    self.payload_mut()?
+                        .get_mut(offset..)
+                        .and_then(|payload| payload.get_mut(..bytes.len()))
+                        .ok_or("Value does not fit in the payload")?
+                        .copy_from_slice(&bytes);
+                    Ok(())
+                }
+            )*
+        }
+    };
+}
+
+payload_accessors! {
+    u16: get_u16_le, set_u16_le, from_le_bytes, to_le_bytes, "little-endian";
+    u16: get_u16_be, set_u16_be, from_be_bytes, to_be_bytes, "big-endian";
+    i16: get_i16_le, set_i16_le, from_le_bytes, to_le_bytes, "little-endian";
+    i16: get_i16_be, set_i16_be, from_be_bytes, to_be_bytes, "big-endian";
+    u32: get_u32_le, set_u32_le, from_le_bytes, to_le_bytes, "little-endian";
+    u32: get_u32_be, set_u32_be, from_be_bytes, to_be_bytes, "big-endian";
+    f32: get_f32_le, set_f32_le, from_le_bytes, to_le_bytes, "little-endian";
+    f32: get_f32_be, set_f32_be, from_be_bytes, to_be_bytes, "big-endian";
+}
+
+/// Generates valid frames of every kind, with IDs at the limits of their range and empty and
+/// full payloads more often than uniform sampling would
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CanFrame {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let extended = bool::arbitrary(u)?;
+        let max_id = if extended { CAN_EFF_MASK } else { CAN_SFF_MASK };
+        let id = match u.int_in_range(0..=3)? {
+            0 => 0,
+            1 => max_id,
+            _ => u.int_in_range(0..=max_id)?,
+        };
+        let len = match u.int_in_range(0..=3)? {
+            0 => 0,
+            1 => 8,
+            _ => u.int_in_range(0..=8)?,
+        };
+
+        let mut frame = match u.int_in_range(0..=9)? {
+            0 => Self::new_error(u.int_in_range(0..=CAN_EFF_MASK)?),
+            1 => Self::new_remote(id, len, extended),
+            _ => {
+                let mut data = [0u8; 8];
+                u.fill_buffer(&mut data[..len])?;
+                if extended {
+                    Self::new_eff(id, &data[..len])
+                } else {
+                    Self::new(id, &data[..len])
+                }
+            }
+        }
+        .map_err(|_| arbitrary::Error::IncorrectFormat)?;
+        frame.timestamp = Option::<u64>::arbitrary(u)?;
+        Ok(frame)
+    }
+}
+
+// Field layout of the binary encoding, unchanged from the original derive
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "CanFrame")]
+struct BinaryCanFrame {
+    id: u32,
+    data: [u8; 8],
+    dlc: usize,
+    is_extended: bool,
+    is_rtr: bool,
+    is_error: bool,
+    timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "CanFrame")]
+struct JsonCanFrame {
+    id: String,
+    #[serde(default)]
+    extended: bool,
+    #[serde(default)]
+    rtr: bool,
+    #[serde(default)]
+    error: bool,
+    #[serde(default)]
+    dlc: Option<usize>,
+    #[serde(default)]
+    data: String,
+    #[serde(default)]
+    timestamp: Option<u64>,
+}
+
+impl Serialize for CanFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let data = if self.is_rtr || self.is_error {
+                String::new()
+            } else {
+                self.data().iter().map(|b| format!("{:02X}", b)).collect()
+            };
+            JsonCanFrame {
+                id: format!("{:X}", self.id),
+                extended: self.is_extended,
+                rtr: self.is_rtr,
+                error: self.is_error,
+                dlc: Some(self.dlc),
+                data,
+                timestamp: self.timestamp,
+            }
+            .serialize(serializer)
+        } else {
+            BinaryCanFrame {
+                id: self.id,
+                data: self.data,
+                dlc: self.dlc,
+                is_extended: self.is_extended,
+                is_rtr: self.is_rtr,
+                is_error: self.is_error,
+                timestamp: self.timestamp,
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CanFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        if !deserializer.is_human_readable() {
+            let frame = BinaryCanFrame::deserialize(deserializer)?;
+            let max_id = if frame.is_extended || frame.is_error {
+                CAN_EFF_MASK
+            } else {
+                CAN_SFF_MASK
+            };
+            if frame.id > max_id {
+                return Err(D::Error::custom(format!(
+                    "CAN ID {:X} is out of range",
+                    frame.id
+                )));
+            }
+            if frame.dlc > 8 {
+                return Err(D::Error::custom(format!(
+                    "CAN dlc {} is out of range",
+                    frame.dlc
+                )));
+            }
+            // The unused bytes are zero in every encoded data frame
+            if !frame.is_rtr && !frame.is_error && frame.data[frame.dlc..].iter().any(|&b| b != 0) {
+                return Err(D::Error::custom("CAN dlc does not match the data length"));
+            }
+            return Ok(Self {
+                id: frame.id,
+                data: frame.data,
+                dlc: frame.dlc,
+                is_extended: frame.is_extended,
+                is_rtr: frame.is_rtr,
+                is_error: frame.is_error,
+                timestamp: frame.timestamp,
+                len8_dlc: 0,
+            });
+        }
+
+        let json = JsonCanFrame::deserialize(deserializer)?;
+        // from_str_radix alone would also accept a leading `+`
+        let is_hex = |digits: &str| digits.bytes().all(|b| b.is_ascii_hexdigit());
+        let id = Some(&json.id)
+            .filter(|id| is_hex(id))
+            .and_then(|id| u32::from_str_radix(id, 16).ok())
+            .ok_or_else(|| D::Error::custom(format!("Invalid CAN ID \"{}\"", json.id)))?;
+        if !is_hex(&json.data) || json.data.len() % 2 != 0 {
+            return Err(D::Error::custom(format!(
+                "Invalid CAN data \"{}\"",
+                json.data
+            )));
+        }
+        let data = (0..json.data.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&json.data[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| D::Error::custom(format!("Invalid CAN data \"{}\"", json.data)))?;
+
+        let mut frame = if json.error {
+            Self::new_error(id)
+        } else if json.rtr {
+            Self::new_remote(id, json.dlc.unwrap_or(0), json.extended)
+        } else {
+            if json.dlc.is_some_and(|dlc| dlc != data.len()) {
+                return Err(D::Error::custom("CAN dlc does not match the data length"));
+            }
+            if json.extended {
+                Self::new_eff(id, &data)
+            } else {
+                Self::new(id, &data)
+            }
+        }
+        .map_err(D::Error::custom)?;
+        frame.timestamp = json.timestamp;
+        Ok(frame)
+    }
+}
+
+/// A hashable view of some fields of a CanFrame, for HashMap and HashSet keys
+///
+/// CanFrame equality includes the receive timestamp, so two receptions of the same frame never
+/// compare equal. A key compares only the fields picked by its constructor. Keys built by different
+/// constructors never compare equal, even for the same frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FrameKey {
+    view: KeyView,
+    id: u32,
+    is_extended: bool,
+    is_rtr: bool,
+    is_error: bool,
+    dlc: u8,
+    len8_dlc: u8,
+    data: [u8; 8],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum KeyView {
+    Id,
+    IdPayload,
+    IgnoreTimestamp,
+}
+
+impl FrameKey {
+    /// Key of the ID and whether it is extended, e.g. for the last value of each ID
+    pub fn id(frame: &CanFrame) -> Self {
+        Self::from_id(frame.id, frame.is_extended)
+    }
+
+    /// Same as `id()` of a frame with this ID, e.g. to look up an ID without a frame at hand
+    pub fn from_id(id: u32, is_extended: bool) -> Self {
+        Self {
+            view: KeyView::Id,
+            id,
+            is_extended,
+            is_rtr: false,
+            is_error: false,
+            dlc: 0,
+            len8_dlc: 0,
+            data: [0u8; 8],
+        }
+    }
+
+    /// Key of the ID, the frame type and the data bytes, e.g. to find which payloads an ID is
+    /// sent with
+    pub fn id_payload(frame: &CanFrame) -> Self {
+        let mut data = [0u8; 8];
+        data[..frame.data().len()].copy_from_slice(frame.data());
+        Self {
+            view: KeyView::IdPayload,
+            is_rtr: frame.is_rtr,
+            is_error: frame.is_error,
+            dlc: frame.dlc as u8,
+            data,
+            ..Self::id(frame)
+        }
+    }
+
+    /// Key of every field except the timestamp, e.g. to drop duplicate frames
+    ///
+    /// Unlike `id_payload()`, frames sent with different `len8_dlc()` values are told apart.
+    pub fn ignore_timestamp(frame: &CanFrame) -> Self {
+        Self {
+            view: KeyView::IgnoreTimestamp,
+            len8_dlc: frame.len8_dlc,
+            ..Self::id_payload(frame)
+        }
+    }
+
+    pub fn can_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn is_extended(&self) -> bool {
+        self.is_extended
+    }
+}
+
+/// Transmit and receive error counters (TEC/REC) of a CAN controller
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanErrorCounters {
+    pub tx_errors: u16,
+    pub rx_errors: u16,
+}
+
+/// Error state of a CAN controller
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BusState {
+    /// Both error counters are below 96
+    #[default]
+    ErrorActive,
+    /// An error counter reached 96
+    ErrorWarning,
+    /// An error counter reached 128, the controller no longer sends active error flags
+    ErrorPassive,
+    /// The transmit error counter exceeded 255, the controller has left the bus
+    BusOff,
+    /// The controller is stopped or sleeping
+    Stopped,
+}
+
+/// Maximum transfer unit of an interface limited to classic CAN frames
+pub const CAN_MTU: u32 = 16;
+/// Maximum transfer unit of a CAN FD capable interface
+pub const CANFD_MTU: u32 = 72;
+
+/// Details of a CAN adapter, for display by tooling
+///
+/// Details the interface does not report are None or empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterfaceInfo {
+    /// Driver or adapter name, e.g. "gs_usb", "peak_usb" or "vcan"
+    pub driver: Option<String>,
+    /// Frequency of the controller clock in Hz
+    pub clock_hz: Option<u32>,
+    /// Bitrates the adapter is limited to. Empty if it supports any bitrate its bit timing allows
+    pub bitrates: Vec<u32>,
+    /// Data phase bitrates a CAN FD adapter is limited to
+    pub data_bitrates: Vec<u32>,
+    /// Highest bitrate the adapter supports
+    pub max_bitrate: Option<u32>,
+    /// Maximum transfer unit, `CAN_MTU` or `CANFD_MTU`
+    pub mtu: Option<u32>,
+    /// Termination resistances in ohms the adapter can switch between. Empty if its termination
+    /// is not switchable
+    pub terminations: Vec<u16>,
+}
+
+impl InterfaceInfo {
+    /// Returns true if the interface is configured for CAN FD frames
+    pub fn is_fd(&self) -> bool {
+        self.mtu == Some(CANFD_MTU)
+    }
+
+    /// Returns true if the bus termination can be switched, e.g. with `set_termination()`
+    pub fn has_termination(&self) -> bool {
+        !self.terminations.is_empty()
+    }
+}
+
+/// Maximum payload length of a CAN XL frame
+pub const CANXL_MAX_DATA_LEN: usize = 2048;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "XlFrameFields")]
+pub struct CanXlFrame {
+    priority: u16,
+    sdu_type: u8,
+    acceptance_field: u32,
+    is_sec: bool,
+    data: Vec<u8>,
+    timestamp: Option<u64>,
+}
+
+// Unvalidated fields of a deserialized CanXlFrame, checked by `new()` in the TryFrom impl
+#[derive(Deserialize)]
+#[serde(rename = "CanXlFrame")]
+struct XlFrameFields {
+    priority: u16,
+    sdu_type: u8,
+    acceptance_field: u32,
+    is_sec: bool,
+    data: Vec<u8>,
+    timestamp: Option<u64>,
+}
+
+impl TryFrom<XlFrameFields> for CanXlFrame {
+    type Error = &'static str;
+
+    fn try_from(fields: XlFrameFields) -> Result<Self, Self::Error> {
+        let mut frame = Self::new(
+            fields.priority,
+            fields.sdu_type,
+            fields.acceptance_field,
+            &fields.data,
+        )?;
+        frame.is_sec = fields.is_sec;
+        frame.timestamp = fields.timestamp;
+        Ok(frame)
+    }
+}
+
+impl CanXlFrame {
+    /// Create a new CAN XL data frame
+    ///
+    /// The priority is the 11-bit arbitration ID, the SDU type describes the payload content and the
+    /// acceptance field is the 32-bit value used for receiver side filtering.
+    pub fn new(
+        priority: u16,
+        sdu_type: u8,
+        acceptance_field: u32,
+        data: &[u8],
+    ) -> Result<Self, &'static str> {
+        if priority > 0x7FF {
+            return Err("CAN XL priority must be <= 11 bits (0x7FF)");
+        }
+        if data.is_empty() || data.len() > CANXL_MAX_DATA_LEN {
+            return Err("CAN XL data must be between 1 and 2048 bytes");
+        }
+        Ok(Self {
+            priority,
+            sdu_type,
+            acceptance_field,
+            is_sec: false,
+            data: data.to_vec(),
+            timestamp: None,
+        })
+    }
+
+    /// Mark the frame as carrying CAN XL security (CADsec) content
+    pub fn set_sec(&mut self, is_sec: bool) {
+        self.is_sec = is_sec;
+    }
+
+    pub fn set_timestamp(&mut self, ts: Option<u64>) {
+        self.timestamp = ts;
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+    pub fn sdu_type(&self) -> u8 {
+        self.sdu_type
+    }
+    pub fn acceptance_field(&self) -> u32 {
+        self.acceptance_field
+    }
+    pub fn is_sec(&self) -> bool {
+        self.is_sec
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Formats the frame like can-utils: `123#DEADBEEF` in compact form, as accepted by `cansend`, or
+/// `123   [4]  DE AD BE EF` like `candump` with the alternate flag (`{:#}`)
+///
+/// Extended IDs have 8 hex digits, remote frames end in `R` and their DLC, error frames carry the
+/// error flag in their ID, and a `len8_dlc()` is appended as `_` and a hex digit.
+impl std::fmt::Display for CanFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let id = if self.is_error {
+            self.id | CAN_ERR_FLAG
+        } else {
+            self.id
+        };
+        if self.is_extended || self.is_error {
+            write!(f, "{:08X}", id)?;
+        } else {
+            write!(f, "{:03X}", id)?;
+        }
+
+        if f.alternate() {
+            write!(f, "   [{}]  ", self.dlc)?;
+            if self.is_rtr {
+                return write!(f, "remote request");
+            }
+            if self.is_error {
+                return write!(f, "ERRORFRAME");
+            }
+            for (i, byte) in self.data().iter().enumerate() {
+                let sep = if i == 0 { "" } else { " " };
+                write!(f, "{}{:02X}", sep, byte)?;
+            }
+            return Ok(());
+        }
+
+        write!(f, "#")?;
+        if self.is_rtr {
+            write!(f, "R")?;
+            if self.dlc > 0 {
+                write!(f, "{:X}", self.dlc)?;
+            }
+        } else {
+            for byte in self.data() {
+                write!(f, "{:02X}", byte)?;
+            }
+        }
+        if self.len8_dlc > 8 {
+            write!(f, "_{:X}", self.len8_dlc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the frame like can-utils: `<prio>#<flags>:<sdt>:<af>#<data>` with hex fields, where the
+/// flags are 0x80 (XL format) plus 0x01 for security content
+impl std::fmt::Display for CanXlFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:03X}#{:02X}:{:02X}:{:08X}#",
+            self.priority,
+            self.xl_flags(),
+            self.sdu_type,
+            self.acceptance_field
+        )?;
+        for byte in &self.data {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl CanXlFrame {
+    // CANXL_XLF and CANXL_SEC of linux/can.h
+    fn xl_flags(&self) -> u8 {
+        0x80 | u8::from(self.is_sec)
+    }
+}
+
+/// Logs the frame in the compact form of its `Display` impl
+#[cfg(feature = "defmt")]
+impl defmt::Format for CanFrame {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let id = if self.is_error {
+            self.id | CAN_ERR_FLAG
+        } else {
+            self.id
+        };
+        match self.is_extended || self.is_error {
+            true => defmt::write!(f, "{=u32:08X}#", id),
+            false => defmt::write!(f, "{=u32:03X}#", id),
+        }
+        if self.is_rtr {
+            defmt::write!(f, "R");
+            if self.dlc > 0 {
+                defmt::write!(f, "{=usize:X}", self.dlc);
+            }
+        } else {
+            for byte in self.data() {
+                defmt::write!(f, "{=u8:02X}", byte);
+            }
+        }
+        if self.len8_dlc > 8 {
+            defmt::write!(f, "_{=u8:X}", self.len8_dlc);
+        }
+    }
+}
+
+/// Logs the frame in the form of its `Display` impl
+#[cfg(feature = "defmt")]
+impl defmt::Format for CanXlFrame {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "{=u16:03X}#{=u8:02X}:{=u8:02X}:{=u32:08X}#",
+            self.priority,
+            self.xl_flags(),
+            self.sdu_type,
+            self.acceptance_field
+        );
+        for byte in &self.data {
+            defmt::write!(f, "{=u8:02X}", byte);
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -150,48 +948,95 @@ impl From<socketcan::CanFrame> for CanFrame {
             return CanFrame::new_error(id_raw).unwrap();
         }
         if sc.is_extended() {
-            return CanFrame::new_eff(id_raw, sc.data()).unwrap();
+            CanFrame::new_eff(id_raw, sc.data()).unwrap()
         } else {
-            return CanFrame::new(id_raw, sc.data()).unwrap();
+            CanFrame::new(id_raw, sc.data()).unwrap()
         }
     }
 }
 
 #[cfg(target_os = "linux")]
-impl Into<socketcan::CanFrame> for CanFrame {
-    fn into(self) -> socketcan::CanFrame {
-        use socketcan::{self, EmbeddedFrame};
+impl From<CanFrame> for socketcan::CanFrame {
+    fn from(frame: CanFrame) -> Self {
+        use socketcan::{self, EmbeddedFrame, frame::AsPtr};
 
-        let sc_id = if self.is_extended() {
-            match socketcan::ExtendedId::new(self.id()) {
+        let sc_id = if frame.is_extended() {
+            match socketcan::ExtendedId::new(frame.id()) {
                 Some(ext_id) => Ok(socketcan::Id::Extended(ext_id)),
-                None => Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Invalid CAN ID for extended can frame: {:?}", self.id()),
-                )),
+                None => Err(std::io::Error::other(format!(
+                    "Invalid CAN ID for extended can frame: {:?}",
+                    frame.id()
+                ))),
             }
         } else {
-            match socketcan::StandardId::new(self.id() as u16) {
+            match socketcan::StandardId::new(frame.id() as u16) {
                 Some(std_id) => Ok(socketcan::Id::Standard(std_id)),
-                None => Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Invalid CAN ID for standard can frame: {:?}", self.id()),
-                )),
+                None => Err(std::io::Error::other(format!(
+                    "Invalid CAN ID for standard can frame: {:?}",
+                    frame.id()
+                ))),
             }
         }
         .unwrap();
 
-        if self.is_error() {
+        if frame.is_error() {
             return socketcan::CanFrame::Error(
-                socketcan::CanErrorFrame::new_error(self.id(), self.data()).unwrap(),
+                socketcan::CanErrorFrame::new_error(frame.id(), frame.data()).unwrap(),
             );
         }
-        if self.is_rtr() {
-            return socketcan::CanFrame::Remote(
-                socketcan::CanRemoteFrame::new(sc_id, self.data()).unwrap(),
-            );
+        let sc_frame = if frame.is_rtr() {
+            socketcan::CanFrame::Remote(
+                socketcan::CanRemoteFrame::new(sc_id, frame.data()).unwrap(),
+            )
+        } else {
+            socketcan::CanFrame::Data(socketcan::CanDataFrame::new(sc_id, frame.data()).unwrap())
+        };
+        if frame.len8_dlc == 0 {
+            return sc_frame;
         }
+        // SAFETY: as_ptr points at the can_frame owned by `sc_frame`, which is Copy
+        let mut raw = unsafe { *sc_frame.as_ptr() };
+        raw.len8_dlc = frame.len8_dlc;
+        socketcan::CanFrame::from(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(frame: &BinaryCanFrame) -> Vec<u8> {
+        bincode::serde::encode_to_vec(frame, bincode::config::standard()).unwrap()
+    }
+
+    fn decode(body: &[u8]) -> Result<CanFrame, bincode::error::DecodeError> {
+        bincode::serde::decode_from_slice(body, bincode::config::standard()).map(|(f, _)| f)
+    }
+
+    fn binary(dlc: usize, data: [u8; 8]) -> BinaryCanFrame {
+        BinaryCanFrame {
+            id: 0x123,
+            data,
+            dlc,
+            is_extended: false,
+            is_rtr: false,
+            is_error: false,
+            timestamp: Some(7),
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut frame = CanFrame::standard(0x123, &[1, 2, 3]);
+        frame.set_timestamp(Some(7));
+        let body = bincode::serde::encode_to_vec(&frame, bincode::config::standard()).unwrap();
+        assert_eq!(body, encode(&binary(3, [1, 2, 3, 0, 0, 0, 0, 0])));
+        assert_eq!(decode(&body).unwrap(), frame);
+    }
 
-        socketcan::CanFrame::Data(socketcan::CanDataFrame::new(sc_id, self.data()).unwrap())
+    #[test]
+    fn binary_rejects_invalid_dlc() {
+        assert!(decode(&encode(&binary(9, [0; 8]))).is_err());
+        assert!(decode(&encode(&binary(2, [1, 2, 3, 0, 0, 0, 0, 0]))).is_err());
     }
 }
@@ -0,0 +1,1090 @@
+///
+/// canopen.rs
+///
+/// CANopen helpers built directly on CanInterface.
+///
+use crate::{CanInterface, can::CanFrame};
+use std::io::{Error as IoError, ErrorKind};
+use std::time::Duration;
+
+/// COB-ID used by the LSS master for requests
+pub const LSS_MASTER_COB_ID: u32 = 0x7E5;
+/// COB-ID used by LSS slaves for responses
+pub const LSS_SLAVE_COB_ID: u32 = 0x7E4;
+
+const DEFAULT_LSS_TIMEOUT: Duration = Duration::from_millis(100);
+
+// LSS command specifiers (CiA 305)
+const CS_SWITCH_GLOBAL: u8 = 0x04;
+const CS_CONFIGURE_NODE_ID: u8 = 0x11;
+const CS_CONFIGURE_BIT_TIMING: u8 = 0x13;
+const CS_ACTIVATE_BIT_TIMING: u8 = 0x15;
+const CS_STORE_CONFIGURATION: u8 = 0x17;
+const CS_SWITCH_SELECTIVE_VENDOR: u8 = 0x40;
+const CS_SWITCH_SELECTIVE_RESPONSE: u8 = 0x44;
+const CS_IDENTIFY_SLAVE: u8 = 0x4F;
+const CS_FASTSCAN: u8 = 0x51;
+const CS_INQUIRE_VENDOR: u8 = 0x5A;
+const CS_INQUIRE_NODE_ID: u8 = 0x5E;
+
+// Fastscan bit-check value that resets all unconfigured slaves and asks them to answer
+const FASTSCAN_RESET: u8 = 0x80;
+
+/// Bitrates of the CiA 301 bit timing table, indexed by table entry
+const BIT_TIMING_TABLE: [Option<u32>; 9] = [
+    Some(1_000_000),
+    Some(800_000),
+    Some(500_000),
+    Some(250_000),
+    Some(125_000),
+    None,
+    Some(50_000),
+    Some(20_000),
+    Some(10_000),
+];
+
+/// The 128-bit LSS address (identity object 0x1018) of a CANopen device
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LssAddress {
+    pub vendor_id: u32,
+    pub product_code: u32,
+    pub revision_number: u32,
+    pub serial_number: u32,
+}
+
+impl LssAddress {
+    fn parts(&self) -> [u32; 4] {
+        [
+            self.vendor_id,
+            self.product_code,
+            self.revision_number,
+            self.serial_number,
+        ]
+    }
+
+    fn from_parts(parts: [u32; 4]) -> Self {
+        Self {
+            vendor_id: parts[0],
+            product_code: parts[1],
+            revision_number: parts[2],
+            serial_number: parts[3],
+        }
+    }
+}
+
+/// LSS master for configuring node-ID and bitrate of CANopen slaves (CiA 305)
+pub struct LssMaster<'a, T: CanInterface> {
+    can: &'a mut T,
+    timeout: Duration,
+}
+
+impl<'a, T: CanInterface> LssMaster<'a, T> {
+    pub fn new(can: &'a mut T) -> Self {
+        Self {
+            can,
+            timeout: DEFAULT_LSS_TIMEOUT,
+        }
+    }
+
+    /// Sets how long to wait for a slave response (default 100ms)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Switches all slaves into configuration (`true`) or waiting (`false`) state
+    pub async fn switch_state_global(&mut self, configuration: bool) -> std::io::Result<()> {
+        self.send(CS_SWITCH_GLOBAL, &[configuration as u8]).await
+    }
+
+    /// Switches the single slave with `address` into configuration state
+    pub async fn switch_state_selective(&mut self, address: LssAddress) -> std::io::Result<()> {
+        for (i, part) in address.parts().into_iter().enumerate() {
+            self.send(CS_SWITCH_SELECTIVE_VENDOR + i as u8, &part.to_le_bytes())
+                .await?;
+        }
+        self.receive(CS_SWITCH_SELECTIVE_RESPONSE).await?;
+        Ok(())
+    }
+
+    /// Assigns a node-ID (1-127, or 255 for unconfigured) to the slave in configuration state
+    pub async fn configure_node_id(&mut self, node_id: u8) -> std::io::Result<()> {
+        if !(1..=127).contains(&node_id) && node_id != 0xFF {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "CANopen node-ID must be between 1 and 127, or 255",
+            ));
+        }
+        self.send(CS_CONFIGURE_NODE_ID, &[node_id]).await?;
+        let response = self.receive(CS_CONFIGURE_NODE_ID).await?;
+        check_error_code("node-ID", &response)
+    }
+
+    /// Configures the bitrate of the slave in configuration state from the CiA 301 bit timing table
+    ///
+    /// The new bitrate only takes effect after `activate_bit_timing()`.
+    pub async fn configure_bit_timing(&mut self, bitrate: u32) -> std::io::Result<()> {
+        let index = BIT_TIMING_TABLE
+            .iter()
+            .position(|b| *b == Some(bitrate))
+            .ok_or_else(|| {
+                IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!("Bitrate {} is not in the CiA 301 bit timing table", bitrate),
+                )
+            })?;
+        self.send(CS_CONFIGURE_BIT_TIMING, &[0, index as u8])
+            .await?;
+        let response = self.receive(CS_CONFIGURE_BIT_TIMING).await?;
+        check_error_code("bit timing", &response)
+    }
+
+    /// Makes all slaves in configuration state switch to the configured bitrate
+    ///
+    /// Slaves stop transmitting for `switch_delay`, switch, and wait `switch_delay` again.
+    pub async fn activate_bit_timing(&mut self, switch_delay: Duration) -> std::io::Result<()> {
+        let delay_ms = u16::try_from(switch_delay.as_millis()).map_err(|_| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                "Switch delay must fit in 16 bits of ms",
+            )
+        })?;
+        self.send(CS_ACTIVATE_BIT_TIMING, &delay_ms.to_le_bytes())
+            .await
+    }
+
+    /// Stores the configured node-ID and bitrate in the slave's non-volatile memory
+    pub async fn store_configuration(&mut self) -> std::io::Result<()> {
+        self.send(CS_STORE_CONFIGURATION, &[]).await?;
+        let response = self.receive(CS_STORE_CONFIGURATION).await?;
+        check_error_code("store configuration", &response)
+    }
+
+    /// Reads the LSS address of the slave in configuration state
+    pub async fn inquire_address(&mut self) -> std::io::Result<LssAddress> {
+        let mut parts = [0u32; 4];
+        for (i, part) in parts.iter_mut().enumerate() {
+            let cs = CS_INQUIRE_VENDOR + i as u8;
+            self.send(cs, &[]).await?;
+            let response = self.receive(cs).await?;
+            *part = u32::from_le_bytes(response[1..5].try_into().unwrap());
+        }
+        Ok(LssAddress::from_parts(parts))
+    }
+
+    /// Reads the node-ID of the slave in configuration state (255 if unconfigured)
+    pub async fn inquire_node_id(&mut self) -> std::io::Result<u8> {
+        self.send(CS_INQUIRE_NODE_ID, &[]).await?;
+        Ok(self.receive(CS_INQUIRE_NODE_ID).await?[1])
+    }
+
+    /// Finds one unconfigured slave with the LSS Fastscan procedure
+    ///
+    /// On success the found slave is left in configuration state, ready for `configure_node_id()`.
+    /// Returns None if no unconfigured slave answered. Every missing response costs one timeout,
+    /// so a scan takes up to 128 timeouts.
+    pub async fn fastscan(&mut self) -> std::io::Result<Option<LssAddress>> {
+        if !self.fastscan_step(0, FASTSCAN_RESET, 0, 0).await? {
+            return Ok(None);
+        }
+
+        let mut parts = [0u32; 4];
+        for sub in 0..4u8 {
+            let mut id = 0u32;
+            for bit in (0..32u8).rev() {
+                // Slaves answer if their bits 31..=bit equal id, so silence means this bit is set
+                if !self.fastscan_step(id, bit, sub, sub).await? {
+                    id |= 1 << bit;
+                }
+            }
+
+            let next = (sub + 1) % 4;
+            if !self.fastscan_step(id, 0, sub, next).await? {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "LSS slave stopped responding during fastscan",
+                ));
+            }
+            parts[sub as usize] = id;
+        }
+        Ok(Some(LssAddress::from_parts(parts)))
+    }
+
+    // Sends one fastscan request and returns whether any slave answered
+    async fn fastscan_step(
+        &mut self,
+        id: u32,
+        bit_checked: u8,
+        sub: u8,
+        next: u8,
+    ) -> std::io::Result<bool> {
+        let mut payload = [0u8; 7];
+        payload[..4].copy_from_slice(&id.to_le_bytes());
+        payload[4] = bit_checked;
+        payload[5] = sub;
+        payload[6] = next;
+        self.send(CS_FASTSCAN, &payload).await?;
+
+        match self.receive(CS_IDENTIFY_SLAVE).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::TimedOut => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn send(&mut self, cs: u8, payload: &[u8]) -> std::io::Result<()> {
+        let mut data = [0u8; 8];
+        data[0] = cs;
+        data[1..1 + payload.len()].copy_from_slice(payload);
+        let frame = CanFrame::new(LSS_MASTER_COB_ID, &data)
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        self.can.write_frame(frame).await
+    }
+
+    // Waits for a slave response with command specifier `cs`, ignoring all other traffic
+    async fn receive(&mut self, cs: u8) -> std::io::Result<[u8; 8]> {
+        let wait = async {
+            loop {
+                let frame = self.can.read_frame().await?;
+                if frame.id() == LSS_SLAVE_COB_ID
+                    && !frame.is_extended()
+                    && frame.dlc() == 8
+                    && frame.data()[0] == cs
+                {
+                    return Ok(frame.data().try_into().unwrap());
+                }
+            }
+        };
+        tokio::time::timeout(self.timeout, wait)
+            .await
+            .map_err(|_| IoError::new(ErrorKind::TimedOut, "No response from LSS slave"))?
+    }
+}
+
+fn check_error_code(service: &str, response: &[u8; 8]) -> std::io::Result<()> {
+    match response[1] {
+        0 => Ok(()),
+        0xFF => Err(IoError::other(format!(
+            "LSS slave rejected {} with implementation specific error {}",
+            service, response[2]
+        ))),
+        code => Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!("LSS slave rejected {} with error code {}", service, code),
+        )),
+    }
+}
+
+/// CANopen basic data types (CiA 301 object dictionary indices 0x0001-0x001B)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Boolean,
+    Integer8,
+    Integer16,
+    Integer32,
+    Integer64,
+    Unsigned8,
+    Unsigned16,
+    Unsigned32,
+    Unsigned64,
+    Real32,
+    Real64,
+    VisibleString,
+    OctetString,
+    UnicodeString,
+    Domain,
+    Other(u16),
+}
+
+impl DataType {
+    /// Size of the encoded value in bytes. None for variable length types.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            DataType::Boolean | DataType::Integer8 | DataType::Unsigned8 => Some(1),
+            DataType::Integer16 | DataType::Unsigned16 => Some(2),
+            DataType::Integer32 | DataType::Unsigned32 | DataType::Real32 => Some(4),
+            DataType::Integer64 | DataType::Unsigned64 | DataType::Real64 => Some(8),
+            _ => None,
+        }
+    }
+}
+
+impl From<u16> for DataType {
+    fn from(code: u16) -> Self {
+        match code {
+            0x01 => DataType::Boolean,
+            0x02 => DataType::Integer8,
+            0x03 => DataType::Integer16,
+            0x04 => DataType::Integer32,
+            0x05 => DataType::Unsigned8,
+            0x06 => DataType::Unsigned16,
+            0x07 => DataType::Unsigned32,
+            0x08 => DataType::Real32,
+            0x09 => DataType::VisibleString,
+            0x0A => DataType::OctetString,
+            0x0B => DataType::UnicodeString,
+            0x0F => DataType::Domain,
+            0x11 => DataType::Real64,
+            0x15 => DataType::Integer64,
+            0x1B => DataType::Unsigned64,
+            other => DataType::Other(other),
+        }
+    }
+}
+
+/// Access rights of an object dictionary entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccessType {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+    Const,
+}
+
+impl AccessType {
+    pub fn is_readable(&self) -> bool {
+        !matches!(self, AccessType::WriteOnly)
+    }
+    pub fn is_writable(&self) -> bool {
+        matches!(self, AccessType::WriteOnly | AccessType::ReadWrite)
+    }
+}
+
+/// A single (sub-)object of an object dictionary
+#[derive(Clone, Debug, PartialEq)]
+pub struct OdEntry {
+    pub index: u16,
+    pub subindex: u8,
+    /// ParameterName of the entry. For sub-objects this is the sub-object's own name.
+    pub name: String,
+    /// ParameterName of the parent object for sub-objects of arrays and records
+    pub parent_name: Option<String>,
+    pub data_type: DataType,
+    pub access: AccessType,
+    pub pdo_mappable: bool,
+    /// DefaultValue as written in the file, possibly containing `$NODEID`
+    pub default_value: Option<String>,
+    /// ParameterValue from a DCF
+    pub parameter_value: Option<String>,
+}
+
+impl OdEntry {
+    /// Evaluates the configured value (ParameterValue, else DefaultValue) as an integer
+    ///
+    /// Handles decimal, `0x` hexadecimal and `0` octal notation as well as `$NODEID+...` expressions.
+    pub fn integer_value(&self, node_id: u8) -> Option<i64> {
+        let value = self
+            .parameter_value
+            .as_deref()
+            .or(self.default_value.as_deref())?;
+        parse_integer(value, node_id)
+    }
+}
+
+/// Object dictionary loaded from an EDS or DCF file (CiA 306)
+#[derive(Clone, Debug, Default)]
+pub struct ObjectDictionary {
+    entries: std::collections::BTreeMap<(u16, u8), OdEntry>,
+    /// NodeID from the DeviceComissioning section of a DCF
+    pub node_id: Option<u8>,
+}
+
+impl ObjectDictionary {
+    /// Loads an EDS or DCF file
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the contents of an EDS or DCF file
+    pub fn parse(contents: &str) -> std::io::Result<Self> {
+        let sections = parse_ini(contents);
+        let mut od = ObjectDictionary {
+            node_id: sections
+                .get("devicecomissioning")
+                .and_then(|s| s.get("nodeid"))
+                .and_then(|v| parse_integer(v, 0))
+                .and_then(|v| u8::try_from(v).ok()),
+            ..Default::default()
+        };
+
+        for (section, keys) in &sections {
+            let Some((index, subindex)) = parse_object_section(section) else {
+                continue;
+            };
+            let parent_name = subindex.and_then(|_| {
+                sections
+                    .get(&format!("{:04x}", index))
+                    .and_then(|parent| parent.get("parametername").cloned())
+            });
+
+            // Only variables (ObjectType 7, the default) and domains (2) hold values. Arrays and
+            // records (8/9) only describe their sub-objects, and NULL (0), DEFTYPE (5) and
+            // DEFSTRUCT (6) objects define data types, including their sub-objects
+            let object_type = |keys: &std::collections::HashMap<String, String>| {
+                keys.get("objecttype")
+                    .and_then(|v| parse_integer(v, 0))
+                    .unwrap_or(0x7)
+            };
+            let parent_type = match subindex {
+                Some(_) => sections.get(&format!("{:04x}", index)).map(object_type),
+                None => None,
+            };
+            if matches!(parent_type, Some(0x0 | 0x5 | 0x6))
+                || subindex.is_none() && !matches!(object_type(keys), 0x2 | 0x7)
+            {
+                continue;
+            }
+
+            let data_type = keys
+                .get("datatype")
+                .and_then(|v| parse_integer(v, 0))
+                .and_then(|v| u16::try_from(v).ok())
+                // A domain's DataType is optional and defaults to DOMAIN
+                .or((subindex.is_none() && object_type(keys) == 0x2).then_some(0x0F))
+                .ok_or_else(|| {
+                    IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("Object [{}] has no valid DataType", section),
+                    )
+                })?;
+            let access = match keys.get("accesstype").map(|a| a.to_lowercase()).as_deref() {
+                Some("ro") => AccessType::ReadOnly,
+                Some("wo") => AccessType::WriteOnly,
+                Some("rw") | Some("rwr") | Some("rww") => AccessType::ReadWrite,
+                Some("const") => AccessType::Const,
+                _ => {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("Object [{}] has no valid AccessType", section),
+                    ));
+                }
+            };
+
+            let entry = OdEntry {
+                index,
+                subindex: subindex.unwrap_or(0),
+                name: keys.get("parametername").cloned().unwrap_or_default(),
+                parent_name,
+                data_type: DataType::from(data_type),
+                access,
+                pdo_mappable: keys.get("pdomapping").is_some_and(|v| v == "1"),
+                default_value: keys.get("defaultvalue").cloned().filter(|v| !v.is_empty()),
+                parameter_value: keys
+                    .get("parametervalue")
+                    .cloned()
+                    .filter(|v| !v.is_empty()),
+            };
+            od.entries.insert((entry.index, entry.subindex), entry);
+        }
+
+        Ok(od)
+    }
+
+    /// Returns the entry at `index`/`subindex`
+    pub fn get(&self, index: u16, subindex: u8) -> Option<&OdEntry> {
+        self.entries.get(&(index, subindex))
+    }
+
+    /// Finds an entry by name, ignoring case, spaces and punctuation
+    ///
+    /// Sub-objects can be addressed as `"Parent.Child"`, e.g. `"IdentityObject.VendorId"`.
+    /// `"ProducerHeartbeatTime"` matches an entry named "Producer heartbeat time".
+    pub fn find(&self, name: &str) -> Option<&OdEntry> {
+        let (parent, child) = match name.split_once('.') {
+            Some((parent, child)) => (Some(normalize_name(parent)), normalize_name(child)),
+            None => (None, normalize_name(name)),
+        };
+        self.entries.values().find(|entry| {
+            normalize_name(&entry.name) == child
+                && parent.as_ref().is_none_or(|parent| {
+                    entry
+                        .parent_name
+                        .as_deref()
+                        .is_some_and(|p| normalize_name(p) == *parent)
+                })
+        })
+    }
+
+    /// Iterates over all entries ordered by index and subindex
+    pub fn entries(&self) -> impl Iterator<Item = &OdEntry> {
+        self.entries.values()
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// Parses an INI file into lowercase section names mapping lowercase keys to values
+fn parse_ini(
+    contents: &str,
+) -> std::collections::HashMap<String, std::collections::HashMap<String, String>> {
+    let mut sections = std::collections::HashMap::new();
+    let mut current = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim().to_lowercase();
+            sections
+                .entry(name.clone())
+                .or_insert_with(Default::default);
+            current = Some(name);
+        } else if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            sections.get_mut(section).map(
+                |keys: &mut std::collections::HashMap<String, String>| {
+                    keys.insert(key.trim().to_lowercase(), value.trim().to_string())
+                },
+            );
+        }
+    }
+    sections
+}
+
+// Parses "1018" or "1018sub1" into an index and optional subindex
+fn parse_object_section(section: &str) -> Option<(u16, Option<u8>)> {
+    let (index, subindex) = match section.split_once("sub") {
+        Some((index, sub)) => (index, Some(u8::from_str_radix(sub, 16).ok()?)),
+        None => (section, None),
+    };
+    if index.len() != 4 {
+        return None;
+    }
+    Some((u16::from_str_radix(index, 16).ok()?, subindex))
+}
+
+// Parses an EDS integer value, including $NODEID based expressions
+fn parse_integer(value: &str, node_id: u8) -> Option<i64> {
+    let value = value.trim();
+    let upper = value.to_uppercase();
+    if let Some(pos) = upper.find("$NODEID") {
+        let rest = format!("{}{}", &upper[..pos], &upper[pos + "$NODEID".len()..]);
+        let rest = rest
+            .trim()
+            .trim_start_matches('+')
+            .trim_end_matches('+')
+            .trim();
+        let offset = if rest.is_empty() {
+            0
+        } else {
+            parse_integer(rest, node_id)?
+        };
+        return Some(node_id as i64 + offset);
+    }
+
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let magnitude = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Default COB-ID of the SYNC object
+pub const SYNC_COB_ID: u32 = 0x80;
+
+/// Cyclic SYNC producer (CiA 301 object 0x1005/0x1006/0x1019)
+pub struct SyncProducer {
+    period: Duration,
+    cob_id: u32,
+    counter_overflow: u8,
+    counter: u8,
+}
+
+impl SyncProducer {
+    /// Creates a producer sending a SYNC without counter every `period`
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            cob_id: SYNC_COB_ID,
+            counter_overflow: 0,
+            counter: 0,
+        }
+    }
+
+    /// Sends the SYNC on `cob_id` instead of 0x80
+    pub fn with_cob_id(mut self, cob_id: u32) -> Self {
+        self.cob_id = cob_id;
+        self
+    }
+
+    /// Adds a counter byte to each SYNC that runs from 1 to `overflow` (2-240) and wraps around
+    pub fn with_counter(mut self, overflow: u8) -> std::io::Result<Self> {
+        if !(2..=240).contains(&overflow) {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "SYNC counter overflow value must be between 2 and 240",
+            ));
+        }
+        self.counter_overflow = overflow;
+        self.counter = 0;
+        Ok(self)
+    }
+
+    /// Returns the next SYNC frame and advances the counter
+    pub fn next_frame(&mut self) -> std::io::Result<CanFrame> {
+        let frame = if self.counter_overflow == 0 {
+            CanFrame::new(self.cob_id, &[])
+        } else {
+            self.counter = self.counter % self.counter_overflow + 1;
+            CanFrame::new(self.cob_id, &[self.counter])
+        };
+        frame.map_err(|e| IoError::new(ErrorKind::InvalidInput, e))
+    }
+
+    /// Sends SYNC frames every period until an I/O error occurs
+    ///
+    /// Late ticks are skipped rather than sent in a burst, so consumers never see SYNCs closer
+    /// together than the period.
+    pub async fn run<T: CanInterface>(&mut self, can: &mut T) -> std::io::Result<()> {
+        let mut interval = tokio::time::interval(self.period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let frame = self.next_frame()?;
+            can.write_frame(frame).await?;
+        }
+    }
+}
+
+/// Direction of a PDO as seen from the configured node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PdoKind {
+    /// RPDO, received by the node
+    Receive,
+    /// TPDO, transmitted by the node
+    Transmit,
+}
+
+/// An object mapped into a PDO
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PdoMappingEntry {
+    pub index: u16,
+    pub subindex: u8,
+    pub bits: u8,
+}
+
+/// A single object dictionary write needed to apply a PDO configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SdoWrite {
+    pub index: u16,
+    pub subindex: u8,
+    pub value: u32,
+    /// Size of the value in bytes (1 or 4)
+    pub size: u8,
+}
+
+/// Communication and mapping parameters of one RPDO or TPDO
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PdoConfig {
+    pub kind: PdoKind,
+    /// PDO number, starting at 1
+    pub number: u16,
+    pub cob_id: u32,
+    /// 0 = acyclic synchronous, 1-240 = every n-th SYNC, 254/255 = event driven
+    pub transmission_type: u8,
+    pub entries: Vec<PdoMappingEntry>,
+}
+
+impl PdoConfig {
+    /// Creates an event driven PDO with no mapped objects
+    pub fn new(kind: PdoKind, number: u16, cob_id: u32) -> Self {
+        Self {
+            kind,
+            number,
+            cob_id,
+            transmission_type: 0xFF,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the predefined connection set COB-ID of PDO 1-4 of `node_id`
+    pub fn default_cob_id(kind: PdoKind, number: u16, node_id: u8) -> Option<u32> {
+        if !(1..=4).contains(&number) {
+            return None;
+        }
+        let base = match kind {
+            PdoKind::Transmit => 0x180,
+            PdoKind::Receive => 0x200,
+        };
+        Some(base + 0x100 * (number as u32 - 1) + node_id as u32)
+    }
+
+    /// Transmits or accepts the PDO on every `n`-th SYNC (1-240)
+    pub fn synchronous(mut self, n: u8) -> Self {
+        self.transmission_type = n;
+        self
+    }
+
+    pub fn with_transmission_type(mut self, transmission_type: u8) -> Self {
+        self.transmission_type = transmission_type;
+        self
+    }
+
+    /// Appends an object to the mapping
+    ///
+    /// `bits` must be 1 to 64; `pack()`, `unpack()` and `sdo_writes()` reject other lengths.
+    pub fn map(mut self, index: u16, subindex: u8, bits: u8) -> Self {
+        self.entries.push(PdoMappingEntry {
+            index,
+            subindex,
+            bits,
+        });
+        self
+    }
+
+    /// Appends an object dictionary entry to the mapping, using the size of its data type
+    pub fn map_entry(self, entry: &OdEntry) -> std::io::Result<Self> {
+        let size = entry.data_type.size().ok_or_else(|| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("{} has no fixed size and can not be mapped", entry.name),
+            )
+        })?;
+        Ok(self.map(entry.index, entry.subindex, size as u8 * 8))
+    }
+
+    /// Total number of mapped bits
+    pub fn mapped_bits(&self) -> usize {
+        self.entries.iter().map(|e| e.bits as usize).sum()
+    }
+
+    /// Returns the writes that apply this configuration, in the order required by CiA 301
+    ///
+    /// The PDO is invalidated, its mapping cleared and rewritten, and then re-enabled.
+    pub fn sdo_writes(&self) -> std::io::Result<Vec<SdoWrite>> {
+        self.check()?;
+        let (comm, mapping) = match self.kind {
+            PdoKind::Receive => (0x1400, 0x1600),
+            PdoKind::Transmit => (0x1800, 0x1A00),
+        };
+        let comm = comm + self.number - 1;
+        let mapping = mapping + self.number - 1;
+        let write = |index, subindex, value, size| SdoWrite {
+            index,
+            subindex,
+            value,
+            size,
+        };
+
+        let mut writes = vec![
+            write(comm, 1, self.cob_id | 0x8000_0000, 4),
+            write(comm, 2, self.transmission_type as u32, 1),
+            write(mapping, 0, 0, 1),
+        ];
+        for (i, entry) in self.entries.iter().enumerate() {
+            let value =
+                (entry.index as u32) << 16 | (entry.subindex as u32) << 8 | entry.bits as u32;
+            writes.push(write(mapping, i as u8 + 1, value, 4));
+        }
+        writes.push(write(mapping, 0, self.entries.len() as u32, 1));
+        writes.push(write(comm, 1, self.cob_id, 4));
+        Ok(writes)
+    }
+
+    /// Packs one value per mapped object into a PDO frame
+    ///
+    /// Values are truncated to the mapped bit length, so signed values can be passed as `v as u64`.
+    pub fn pack(&self, values: &[u64]) -> std::io::Result<CanFrame> {
+        self.check()?;
+        if values.len() != self.entries.len() {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "PDO maps {} objects but {} values were given",
+                    self.entries.len(),
+                    values.len()
+                ),
+            ));
+        }
+        let mut payload = 0u64;
+        let mut offset = 0;
+        for (entry, value) in self.entries.iter().zip(values) {
+            payload |= (value & bit_mask(entry.bits)) << offset;
+            offset += entry.bits as usize;
+        }
+        let len = self.mapped_bits().div_ceil(8);
+        CanFrame::new(self.cob_id, &payload.to_le_bytes()[..len])
+            .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))
+    }
+
+    /// Unpacks the mapped objects of a received PDO frame
+    pub fn unpack(&self, frame: &CanFrame) -> std::io::Result<Vec<u64>> {
+        self.check()?;
+        if frame.dlc() * 8 < self.mapped_bits() {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "PDO frame has {} bytes but the mapping needs {} bits",
+                    frame.dlc(),
+                    self.mapped_bits()
+                ),
+            ));
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..frame.dlc()].copy_from_slice(frame.data());
+        let payload = u64::from_le_bytes(bytes);
+        let mut offset = 0;
+        Ok(self
+            .entries
+            .iter()
+            .map(|entry| {
+                let value = (payload >> offset) & bit_mask(entry.bits);
+                offset += entry.bits as usize;
+                value
+            })
+            .collect())
+    }
+
+    fn check(&self) -> std::io::Result<()> {
+        if !(1..=512).contains(&self.number) {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "PDO number must be between 1 and 512",
+            ));
+        }
+        if let Some(entry) = self.entries.iter().find(|e| e.bits == 0 || e.bits > 64) {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Object {:04X}sub{:X} is mapped with {} bits, must be 1 to 64",
+                    entry.index, entry.subindex, entry.bits
+                ),
+            ));
+        }
+        if self.entries.len() > 64 || self.mapped_bits() > 64 {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "PDO mapping exceeds 64 bits",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn bit_mask(bits: u8) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pdo() -> PdoConfig {
+        PdoConfig::new(PdoKind::Transmit, 1, 0x181)
+    }
+
+    #[test]
+    fn pack_unpack_round_trip() {
+        let config = pdo().map(0x6000, 1, 8).map(0x6401, 1, 16).map(0x2000, 0, 1);
+        let frame = config.pack(&[0xAB, 0x1234, 1]).unwrap();
+        assert_eq!(frame.id(), 0x181);
+        assert_eq!(frame.data(), &[0xAB, 0x34, 0x12, 0x01]);
+        assert_eq!(config.unpack(&frame).unwrap(), vec![0xAB, 0x1234, 1]);
+    }
+
+    #[test]
+    fn pack_truncates_to_mapped_bits() {
+        let config = pdo().map(0x6000, 1, 4).map(0x6000, 2, 4);
+        let frame = config.pack(&[0x1F, -1i64 as u64]).unwrap();
+        assert_eq!(frame.data(), &[0xFF]);
+        assert_eq!(config.unpack(&frame).unwrap(), vec![0xF, 0xF]);
+    }
+
+    #[test]
+    fn full_64_bit_mapping() {
+        let config = pdo().map(0x6000, 1, 64);
+        let frame = config.pack(&[u64::MAX - 1]).unwrap();
+        assert_eq!(frame.dlc(), 8);
+        assert_eq!(config.unpack(&frame).unwrap(), vec![u64::MAX - 1]);
+    }
+
+    #[test]
+    fn rejects_invalid_bit_lengths() {
+        for config in [
+            pdo().map(0x6000, 1, 0),
+            pdo().map(0x6000, 1, 64).map(0x6000, 2, 0),
+            pdo().map(0x6000, 1, 65),
+            pdo().map(0x6000, 1, 32).map(0x6000, 2, 40),
+        ] {
+            let frame = CanFrame::new(0x181, &[0; 8]).unwrap();
+            assert!(config.pack(&vec![0; config.entries.len()]).is_err());
+            assert!(config.unpack(&frame).is_err());
+            assert!(config.sdo_writes().is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_value_count() {
+        let config = pdo().map(0x6000, 1, 8).map(0x6000, 2, 8);
+        assert!(config.pack(&[1]).is_err());
+        assert!(config.pack(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn unpack_rejects_short_frame() {
+        let config = pdo().map(0x6000, 1, 16).map(0x6000, 2, 8);
+        let frame = CanFrame::new(0x181, &[1, 2]).unwrap();
+        assert!(config.unpack(&frame).is_err());
+    }
+
+    #[test]
+    fn empty_mapping() {
+        let frame = pdo().pack(&[]).unwrap();
+        assert_eq!(frame.dlc(), 0);
+        assert_eq!(pdo().unpack(&frame).unwrap(), Vec::<u64>::new());
+    }
+
+    const EDS: &str = "
+[DeviceComissioning]
+NodeID=0x05
+
+[1000]
+ParameterName=Device type
+ObjectType=0x7
+DataType=0x0007
+AccessType=ro
+DefaultValue=0x00000191
+PDOMapping=0
+
+[1018]
+ParameterName=Identity Object
+ObjectType=0x9
+SubNumber=2
+
+[1018sub0]
+ParameterName=Highest sub-index supported
+DataType=0x0005
+AccessType=const
+DefaultValue=1
+
+[1018sub1]
+ParameterName=Vendor-ID
+DataType=0x0007
+AccessType=ro
+DefaultValue=0x1234
+
+[1014]
+ParameterName=COB-ID EMCY
+DataType=0x0007
+AccessType=rw
+DefaultValue=$NODEID+0x80
+ParameterValue=
+
+[0007]
+ParameterName=UNSIGNED32
+ObjectType=0x5
+DataType=0x0007
+AccessType=ro
+
+[0040]
+ParameterName=Custom struct
+ObjectType=0x6
+SubNumber=1
+
+[0040sub1]
+ParameterName=Field
+DataType=0x0007
+AccessType=ro
+
+[1F50]
+ParameterName=Program data
+ObjectType=0x2
+AccessType=rw
+
+[6000sub1]
+ParameterName=Input 1
+DataType=0x0005
+AccessType=ro
+PDOMapping=1
+";
+
+    #[test]
+    fn parses_objects_and_sub_objects() {
+        let od = ObjectDictionary::parse(EDS).unwrap();
+        assert_eq!(od.node_id, Some(5));
+
+        let device_type = od.get(0x1000, 0).unwrap();
+        assert_eq!(device_type.data_type, DataType::Unsigned32);
+        assert_eq!(device_type.access, AccessType::ReadOnly);
+        assert_eq!(device_type.integer_value(5), Some(0x191));
+
+        let vendor = od.find("IdentityObject.VendorId").unwrap();
+        assert_eq!((vendor.index, vendor.subindex), (0x1018, 1));
+        assert_eq!(vendor.parent_name.as_deref(), Some("Identity Object"));
+        assert!(od.get(0x1018, 0).is_some());
+
+        let input = od.get(0x6000, 1).unwrap();
+        assert!(input.pdo_mappable);
+        assert_eq!(input.parent_name, None);
+    }
+
+    #[test]
+    fn skips_records_and_type_definitions() {
+        let od = ObjectDictionary::parse(EDS).unwrap();
+        assert!(od.get(0x1018, 0xFF).is_none());
+        assert!(od.get(0x0007, 0).is_none());
+        assert!(od.get(0x0040, 0).is_none());
+        assert!(od.get(0x0040, 1).is_none());
+        assert_eq!(od.entries().count(), 6);
+    }
+
+    #[test]
+    fn domain_defaults_to_domain_type() {
+        let od = ObjectDictionary::parse(EDS).unwrap();
+        let domain = od.get(0x1F50, 0).unwrap();
+        assert_eq!(domain.data_type, DataType::Domain);
+        assert!(domain.access.is_writable());
+    }
+
+    #[test]
+    fn evaluates_node_id_expressions() {
+        let od = ObjectDictionary::parse(EDS).unwrap();
+        let emcy = od.get(0x1014, 0).unwrap();
+        assert_eq!(emcy.parameter_value, None);
+        assert_eq!(emcy.integer_value(5), Some(0x85));
+        assert_eq!(parse_integer("$nodeid + 0x10", 2), Some(0x12));
+        assert_eq!(parse_integer("0x100+$NODEID", 2), Some(0x102));
+        assert_eq!(parse_integer("$NODEID", 7), Some(7));
+    }
+
+    #[test]
+    fn parses_integer_notations() {
+        assert_eq!(parse_integer("42", 0), Some(42));
+        assert_eq!(parse_integer("-42", 0), Some(-42));
+        assert_eq!(parse_integer("0x2A", 0), Some(42));
+        assert_eq!(parse_integer("0X2a", 0), Some(42));
+        assert_eq!(parse_integer("052", 0), Some(42));
+        assert_eq!(parse_integer("0", 0), Some(0));
+        assert_eq!(parse_integer("abc", 0), None);
+        assert_eq!(parse_integer("ß$NODEID", 0), None);
+        assert_eq!(parse_integer("$NODEIDé", 0), None);
+    }
+
+    #[test]
+    fn rejects_objects_without_data_type() {
+        let eds = "[2000]\nParameterName=Broken\nObjectType=0x7\nAccessType=rw\n";
+        assert_eq!(
+            ObjectDictionary::parse(eds).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,206 @@
+///
+/// capture.rs
+///
+/// Long-running capture of an interface to a pcapng stream on a dedicated thread.
+///
+use crate::{CanInterface, FramesDropped, pcap::PcapngWriter};
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// Default size of the write buffer between the reader and the output
+pub const DEFAULT_WRITE_BUFFER: usize = 1024 * 1024;
+
+/// Frame counters of a running or finished capture
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// Frames written to the output
+    pub frames: u64,
+    /// Frames the interface reported as dropped (see `FramesDropped`)
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    frames: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// A capture running on its own thread
+///
+/// For gap-free capture at full bus load, the capture thread owns a single-threaded runtime so
+/// it is never starved by application tasks. It reads frames in batches (`read_frames`) and writes
+/// them through a large buffer, so the output only sees a few big writes. On Linux, also raise the
+/// socket receive buffer in `setup` (`LinuxCan::set_receive_buffer`) so the kernel can absorb
+/// stalls of the output. Losses reported by the interface are counted in `CaptureStats::dropped`.
+///
+/// Frames are written with their receive timestamp, read as nanoseconds since the Unix epoch like
+/// those of `LinuxCan`. Frames without a timestamp get the time their batch was read.
+///
+/// `benches/capture.rs` checks that two fully loaded 1 Mbit/s buses are captured without loss and
+/// fails if any frame is lost.
+pub struct Capture {
+    stop: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<std::io::Result<()>>>,
+    counters: Arc<Counters>,
+}
+
+impl Capture {
+    /// Opens `channel` on a new thread and starts writing its frames to `writer` as pcapng
+    ///
+    /// `setup` runs on the capture thread right after the interface was opened. Returns once the
+    /// interface is open, or with the error of `open` or `setup`.
+    pub fn start<T, W, F>(channel: &str, writer: W, setup: F) -> std::io::Result<Self>
+    where
+        T: CanInterface + 'static,
+        W: Write + Send + 'static,
+        F: FnOnce(&mut T) -> std::io::Result<()> + Send + 'static,
+    {
+        Self::start_with_buffer(channel, writer, DEFAULT_WRITE_BUFFER, setup)
+    }
+
+    /// Like `start()`, with a write buffer of `buffer_len` bytes
+    pub fn start_with_buffer<T, W, F>(
+        channel: &str,
+        writer: W,
+        buffer_len: usize,
+        setup: F,
+    ) -> std::io::Result<Self>
+    where
+        T: CanInterface + 'static,
+        W: Write + Send + 'static,
+        F: FnOnce(&mut T) -> std::io::Result<()> + Send + 'static,
+    {
+        let channel = channel.to_string();
+        let counters = Arc::new(Counters::default());
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        let thread_counters = counters.clone();
+        let thread = std::thread::Builder::new()
+            .name(format!("crosscan-capture-{}", channel))
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                rt.block_on(async move {
+                    let opened = async {
+                        let mut can = T::open(&channel).await?;
+                        setup(&mut can)?;
+                        let output = BufWriter::with_capacity(buffer_len, writer);
+                        Ok((can, PcapngWriter::new(output)?))
+                    }
+                    .await;
+                    let (can, output) = match opened {
+                        Ok(opened) => {
+                            let _ = ready_tx.send(Ok(()));
+                            opened
+                        }
+                        Err(e) => {
+                            let _ = ready_tx.send(Err(e));
+                            return Ok(());
+                        }
+                    };
+                    run(can, output, stop_rx, &thread_counters).await
+                })
+            })?;
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                stop: Some(stop_tx),
+                thread: Some(thread),
+                counters,
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            // The thread failed before opening the interface, e.g. building the runtime
+            Err(_) => Err(thread
+                .join()
+                .map_err(|_| std::io::Error::other("Capture thread panicked"))?
+                .err()
+                .unwrap_or_else(|| std::io::Error::other("Capture thread exited"))),
+        }
+    }
+
+    /// Returns the counters so far
+    pub fn stats(&self) -> CaptureStats {
+        CaptureStats {
+            frames: self.counters.frames.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns true once the capture stopped on its own because of an error
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    /// Stops the capture, flushes the output and returns the final counters
+    ///
+    /// Returns the error that ended the capture early, if any.
+    pub fn stop(mut self) -> std::io::Result<CaptureStats> {
+        self.shutdown()?;
+        Ok(self.stats())
+    }
+
+    fn shutdown(&mut self) -> std::io::Result<()> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| std::io::Error::other("Capture thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+async fn run<T: CanInterface, W: Write>(
+    mut can: T,
+    mut output: PcapngWriter<W>,
+    mut stop: oneshot::Receiver<()>,
+    counters: &Counters,
+) -> std::io::Result<()> {
+    loop {
+        let frames = tokio::select! {
+            _ = &mut stop => break,
+            frames = can.read_frames() => frames,
+        };
+        match frames {
+            Ok(frames) => {
+                let read_at = SystemTime::now();
+                for frame in &frames {
+                    let time = frame
+                        .timestamp()
+                        .map_or(read_at, |ns| UNIX_EPOCH + Duration::from_nanos(ns));
+                    output.write_frame(frame, time)?;
+                }
+                counters
+                    .frames
+                    .fetch_add(frames.len() as u64, Ordering::Relaxed);
+            }
+            Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                Some(FramesDropped(count)) => {
+                    counters.dropped.fetch_add(*count, Ordering::Relaxed);
+                }
+                None => {
+                    output.flush()?;
+                    return Err(e);
+                }
+            },
+        }
+    }
+    output.flush()
+}
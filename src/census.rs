@@ -0,0 +1,166 @@
+///
+/// census.rs
+///
+/// Bus census: per-ID rates and payload statistics of a capture or live traffic.
+///
+use crate::can::CanFrame;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+/// Statistics of one CAN ID
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdCensus {
+    pub id: u32,
+    pub extended: bool,
+    /// Number of data and remote frames seen
+    pub count: u64,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    /// Average frames per second between the first and last frame. 0 with fewer than 2 frames
+    pub rate_hz: f64,
+    pub min_dlc: usize,
+    pub max_dlc: usize,
+    /// Per byte, the bits that changed at least once between consecutive frames
+    pub changing_bits: [u8; 8],
+    /// Per byte, the Shannon entropy of the observed values in bits (0 to 8)
+    pub byte_entropy: [f64; 8],
+}
+
+struct IdState {
+    count: u64,
+    first_seen: SystemTime,
+    last_seen: SystemTime,
+    min_dlc: usize,
+    max_dlc: usize,
+    last_data: Option<Vec<u8>>,
+    changing_bits: [u8; 8],
+    // Histogram of the values of each byte position
+    histograms: Box<[[u32; 256]; 8]>,
+}
+
+/// Accumulates a census of the IDs on a bus
+///
+/// Feed frames with `observe()`, or build one from a capture with `from_frames()`, then call
+/// `report()`. Error frames are ignored. Each ID keeps 8 KiB of histograms for the entropy.
+#[derive(Default)]
+pub struct Census {
+    ids: BTreeMap<(bool, u32), IdState>,
+}
+
+impl Census {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a census from timestamped frames, e.g. a `PcapReader`
+    pub fn from_frames<I>(frames: I) -> std::io::Result<Self>
+    where
+        I: IntoIterator<Item = std::io::Result<(SystemTime, CanFrame)>>,
+    {
+        let mut census = Self::new();
+        for frame in frames {
+            let (time, frame) = frame?;
+            census.observe(&frame, time);
+        }
+        Ok(census)
+    }
+
+    /// Adds a frame received at `time`
+    pub fn observe(&mut self, frame: &CanFrame, time: SystemTime) {
+        if frame.is_error() {
+            return;
+        }
+        let state = self
+            .ids
+            .entry((frame.is_extended(), frame.id()))
+            .or_insert_with(|| IdState {
+                count: 0,
+                first_seen: time,
+                last_seen: time,
+                min_dlc: frame.dlc(),
+                max_dlc: frame.dlc(),
+                last_data: None,
+                changing_bits: [0; 8],
+                histograms: Box::new([[0; 256]; 8]),
+            });
+
+        state.count += 1;
+        state.first_seen = state.first_seen.min(time);
+        state.last_seen = state.last_seen.max(time);
+        state.min_dlc = state.min_dlc.min(frame.dlc());
+        state.max_dlc = state.max_dlc.max(frame.dlc());
+        if frame.is_rtr() {
+            return;
+        }
+
+        let data = frame.data();
+        for (i, byte) in data.iter().enumerate().take(8) {
+            state.histograms[i][*byte as usize] += 1;
+        }
+        if let Some(last) = &state.last_data {
+            for (i, (old, new)) in last.iter().zip(data).enumerate().take(8) {
+                state.changing_bits[i] |= old ^ new;
+            }
+        }
+        state.last_data = Some(data.to_vec());
+    }
+
+    /// Forgets everything seen so far, e.g. to start the next window of a live census
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+
+    /// Returns the number of distinct IDs seen
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the statistics of every ID, standard IDs first, each in ascending order
+    pub fn report(&self) -> Vec<IdCensus> {
+        self.ids
+            .iter()
+            .map(|(&(extended, id), state)| {
+                let span = state
+                    .last_seen
+                    .duration_since(state.first_seen)
+                    .unwrap_or(Duration::ZERO);
+                let rate_hz = if state.count > 1 && !span.is_zero() {
+                    (state.count - 1) as f64 / span.as_secs_f64()
+                } else {
+                    0.0
+                };
+                IdCensus {
+                    id,
+                    extended,
+                    count: state.count,
+                    first_seen: state.first_seen,
+                    last_seen: state.last_seen,
+                    rate_hz,
+                    min_dlc: state.min_dlc,
+                    max_dlc: state.max_dlc,
+                    changing_bits: state.changing_bits,
+                    byte_entropy: std::array::from_fn(|i| entropy(&state.histograms[i])),
+                }
+            })
+            .collect()
+    }
+}
+
+fn entropy(histogram: &[u32; 256]) -> f64 {
+    let total: u64 = histogram.iter().map(|&n| n as u64).sum();
+    if total == 0 {
+        return 0.0;
+    }
+    histogram
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / total as f64;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
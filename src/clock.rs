@@ -0,0 +1,128 @@
+///
+/// clock.rs
+///
+/// Correlation of frame timestamps with UTC.
+///
+use crate::can::CanFrame;
+use std::sync::{
+    Arc,
+    atomic::{AtomicI64, Ordering},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Provides the offset between the clock frame timestamps are taken from and UTC
+///
+/// Implement this for PTP, GPS PPS or any other reference to export captures in UTC.
+pub trait ClockOffsetSource: Send + Sync {
+    /// Returns UTC minus the timestamp clock, in nanoseconds, at the current time
+    fn offset(&self) -> std::io::Result<i64>;
+}
+
+/// Offset that is updated from outside, e.g. by a PPS handler or a PTP servo
+#[derive(Clone, Debug, Default)]
+pub struct SharedOffset(Arc<AtomicI64>);
+
+impl SharedOffset {
+    pub fn new(offset_ns: i64) -> Self {
+        Self(Arc::new(AtomicI64::new(offset_ns)))
+    }
+
+    pub fn set(&self, offset_ns: i64) {
+        self.0.store(offset_ns, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl ClockOffsetSource for SharedOffset {
+    fn offset(&self) -> std::io::Result<i64> {
+        Ok(self.get())
+    }
+}
+
+/// Timestamps taken from the system's realtime clock, such as those of `LinuxCan`
+///
+/// The offset is 0, so the result is as accurate as the system clock is disciplined (e.g. by
+/// chrony, or by ptp4l and phc2sys from a PTP grandmaster).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl ClockOffsetSource for SystemClock {
+    fn offset(&self) -> std::io::Result<i64> {
+        Ok(0)
+    }
+}
+
+/// Converts frame timestamps to UTC using a registered offset source
+pub struct UtcConverter {
+    source: Box<dyn ClockOffsetSource>,
+    resolution: Duration,
+}
+
+impl UtcConverter {
+    /// Creates a converter for timestamps counted in nanoseconds
+    pub fn new<S: ClockOffsetSource + 'static>(source: S) -> Self {
+        Self {
+            source: Box::new(source),
+            resolution: Duration::from_nanos(1),
+        }
+    }
+
+    /// Sets the length of one timestamp tick (default 1ns)
+    pub fn with_resolution(mut self, resolution: Duration) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Returns the UTC time of a raw timestamp
+    pub fn to_utc(&self, timestamp: u64) -> std::io::Result<SystemTime> {
+        let offset = self.source.offset()?;
+        let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let nanos = i128::try_from(self.resolution.as_nanos())
+            .ok()
+            .and_then(|resolution| (timestamp as i128).checked_mul(resolution))
+            .and_then(|nanos| nanos.checked_add(offset as i128));
+        let nanos = match nanos {
+            Some(nanos) if nanos < 0 => {
+                return Err(invalid("Timestamp lies before the UNIX epoch"));
+            }
+            Some(nanos) => u64::try_from(nanos).ok(),
+            None => None,
+        }
+        .ok_or_else(|| invalid("Timestamp is out of range"))?;
+        Ok(UNIX_EPOCH + Duration::from_nanos(nanos))
+    }
+
+    /// Returns the UTC receive time of a frame. Returns None if the frame has no timestamp
+    pub fn frame_utc(&self, frame: &CanFrame) -> std::io::Result<Option<SystemTime>> {
+        frame.timestamp().map(|ts| self.to_utc(ts)).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_utc_reports_why_a_timestamp_cannot_be_converted() {
+        let converter = UtcConverter::new(SharedOffset::new(-1_000));
+        assert_eq!(
+            converter.to_utc(1_500).unwrap(),
+            UNIX_EPOCH + Duration::from_nanos(500)
+        );
+
+        let before_epoch = converter.to_utc(500).unwrap_err();
+        assert_eq!(
+            before_epoch.to_string(),
+            "Timestamp lies before the UNIX epoch"
+        );
+
+        let too_late = UtcConverter::new(SystemClock)
+            .with_resolution(Duration::from_secs(1))
+            .to_utc(u64::MAX)
+            .unwrap_err();
+        assert_eq!(too_late.to_string(), "Timestamp is out of range");
+    }
+}
@@ -0,0 +1,313 @@
+///
+/// dedicated.rs
+///
+/// Runs an interface's I/O on its own OS thread and runtime, optionally pinned to a CPU core.
+///
+use crate::{
+    CanInterface, FramesDropped,
+    can::{CanErrorCounters, CanFrame, InterfaceInfo},
+};
+use std::collections::VecDeque;
+use std::io::{Error as IoError, ErrorKind};
+use std::marker::PhantomData;
+use tokio::sync::{mpsc, oneshot};
+
+/// Default number of received batches buffered between the I/O thread and the reader
+pub const DEFAULT_QUEUE_LEN: usize = 1024;
+
+/// Settings of the thread started by `DedicatedCan::open_with()`
+#[derive(Clone, Debug)]
+pub struct ThreadOptions {
+    name: Option<String>,
+    core: Option<usize>,
+    queue_len: usize,
+}
+
+impl Default for ThreadOptions {
+    fn default() -> Self {
+        Self {
+            name: None,
+            core: None,
+            queue_len: DEFAULT_QUEUE_LEN,
+        }
+    }
+}
+
+impl ThreadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the thread. Defaults to `crosscan-io-{interface}`
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Pins the thread to the CPU core with index `core`. Only supported on Linux
+    pub fn with_core(mut self, core: usize) -> Self {
+        self.core = Some(core);
+        self
+    }
+
+    /// Buffers up to `batches` received batches until the reader catches up. Further frames
+    /// are dropped and reported as `FramesDropped`
+    pub fn with_queue_len(mut self, batches: usize) -> Self {
+        self.queue_len = batches;
+        self
+    }
+}
+
+enum Request {
+    Write(CanFrame, oneshot::Sender<std::io::Result<()>>),
+    WriteMany(Vec<CanFrame>, oneshot::Sender<std::io::Result<()>>),
+    GetBitrate(oneshot::Sender<std::io::Result<Option<u32>>>),
+    GetErrorCounters(oneshot::Sender<std::io::Result<Option<CanErrorCounters>>>),
+    GetInfo(oneshot::Sender<std::io::Result<InterfaceInfo>>),
+    TxPending(oneshot::Sender<std::io::Result<usize>>),
+    Flush(oneshot::Sender<std::io::Result<()>>),
+}
+
+/// An interface whose I/O runs on a dedicated thread with its own single-threaded runtime
+///
+/// For jitter-sensitive control loops that should not share the application's tokio worker pool,
+/// the backend `T` is opened and driven on a thread of its own, which can be pinned to a core that
+/// is kept free of other work. `DedicatedCan` itself is a `CanInterface` that talks to that thread
+/// over channels, so it can be used anywhere the backend could.
+///
+/// If the reader falls behind by more than the queue length, frames are dropped on the I/O
+/// thread and reported as `FramesDropped`. The thread exits once the `DedicatedCan` is dropped.
+pub struct DedicatedCan<T> {
+    frames: mpsc::Receiver<std::io::Result<Vec<CanFrame>>>,
+    requests: mpsc::Sender<Request>,
+    buffered: VecDeque<CanFrame>,
+    _backend: PhantomData<fn() -> T>,
+}
+
+impl<T: CanInterface + 'static> DedicatedCan<T> {
+    /// Opens `interface` with `T` on a new thread configured by `options`
+    ///
+    /// Returns once the interface is open, or with the error of opening it or of pinning the
+    /// thread.
+    pub async fn open_with(interface: &str, options: ThreadOptions) -> std::io::Result<Self> {
+        let (frames_tx, frames) = mpsc::channel(options.queue_len.max(1));
+        let (requests, requests_rx) = mpsc::channel(options.queue_len.max(1));
+        let (ready_tx, ready_rx) = oneshot::channel();
+
+        let interface = interface.to_string();
+        let name = options
+            .name
+            .unwrap_or_else(|| format!("crosscan-io-{}", interface));
+        let core = options.core;
+        std::thread::Builder::new().name(name).spawn(move || {
+            let opened = (|| {
+                if let Some(core) = core {
+                    pin_to_core(core)?;
+                }
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+            })();
+            let rt = match opened {
+                Ok(rt) => rt,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            rt.block_on(async move {
+                match T::open(&interface).await {
+                    Ok(can) => {
+                        let _ = ready_tx.send(Ok(()));
+                        run(can, frames_tx, requests_rx).await;
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                    }
+                }
+            });
+        })?;
+
+        ready_rx
+            .await
+            .map_err(|_| IoError::other("Interface thread panicked"))??;
+        Ok(Self {
+            frames,
+            requests,
+            buffered: VecDeque::new(),
+            _backend: PhantomData,
+        })
+    }
+
+    async fn request<R>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<std::io::Result<R>>) -> Request,
+    ) -> std::io::Result<R> {
+        let (done, result) = oneshot::channel();
+        self.requests
+            .send(request(done))
+            .await
+            .map_err(|_| thread_stopped())?;
+        result.await.map_err(|_| thread_stopped())?
+    }
+
+    async fn receive(&mut self) -> std::io::Result<()> {
+        match self.frames.recv().await {
+            Some(Ok(frames)) => {
+                self.buffered.extend(frames);
+                Ok(())
+            }
+            Some(Err(e)) => Err(e),
+            None => Err(thread_stopped()),
+        }
+    }
+}
+
+impl<T: CanInterface + 'static> CanInterface for DedicatedCan<T> {
+    /// Opens `interface` with `T` on a new, unpinned thread
+    async fn open(interface: &str) -> std::io::Result<Self> {
+        Self::open_with(interface, ThreadOptions::default()).await
+    }
+
+    async fn read_frame(&mut self) -> std::io::Result<CanFrame> {
+        loop {
+            if let Some(frame) = self.buffered.pop_front() {
+                return Ok(frame);
+            }
+            self.receive().await?;
+        }
+    }
+
+    async fn read_frames(&mut self) -> std::io::Result<Vec<CanFrame>> {
+        if self.buffered.is_empty() {
+            self.receive().await?;
+        }
+        Ok(self.buffered.drain(..).collect())
+    }
+
+    async fn write_frame(&mut self, frame: CanFrame) -> std::io::Result<()> {
+        self.request(|done| Request::Write(frame, done)).await
+    }
+
+    async fn write_frames(&mut self, frames: &[CanFrame]) -> std::io::Result<()> {
+        let frames = frames.to_vec();
+        self.request(|done| Request::WriteMany(frames, done)).await
+    }
+
+    async fn get_bitrate(&mut self) -> std::io::Result<Option<u32>> {
+        self.request(Request::GetBitrate).await
+    }
+
+    async fn get_error_counters(&mut self) -> std::io::Result<Option<CanErrorCounters>> {
+        self.request(Request::GetErrorCounters).await
+    }
+
+    async fn get_info(&mut self) -> std::io::Result<InterfaceInfo> {
+        self.request(Request::GetInfo).await
+    }
+
+    async fn tx_pending(&mut self) -> std::io::Result<usize> {
+        self.request(Request::TxPending).await
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        self.request(Request::Flush).await
+    }
+}
+
+fn thread_stopped() -> IoError {
+    IoError::new(ErrorKind::BrokenPipe, "Interface thread stopped")
+}
+
+async fn run<T: CanInterface>(
+    mut can: T,
+    frames: mpsc::Sender<std::io::Result<Vec<CanFrame>>>,
+    mut requests: mpsc::Receiver<Request>,
+) {
+    // Frames dropped because the reader fell behind, reported before the next batch
+    let mut dropped = 0u64;
+    loop {
+        tokio::select! {
+            result = can.read_frames() => match result {
+                Ok(batch) => {
+                    if dropped > 0 {
+                        match frames.try_send(Err(IoError::other(FramesDropped(dropped)))) {
+                            Ok(()) => dropped = 0,
+                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                dropped += batch.len() as u64;
+                                continue;
+                            }
+                            Err(mpsc::error::TrySendError::Closed(_)) => return,
+                        }
+                    }
+                    match frames.try_send(Ok(batch)) {
+                        Ok(()) => {}
+                        Err(mpsc::error::TrySendError::Full(Ok(batch))) => {
+                            dropped += batch.len() as u64;
+                        }
+                        Err(_) => return,
+                    }
+                }
+                Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                    Some(FramesDropped(count)) => dropped += count,
+                    None => {
+                        let _ = frames.send(Err(e)).await;
+                        return;
+                    }
+                },
+            },
+            request = requests.recv() => match request {
+                Some(Request::Write(frame, done)) => {
+                    let _ = done.send(can.write_frame(frame).await);
+                }
+                Some(Request::WriteMany(batch, done)) => {
+                    let _ = done.send(can.write_frames(&batch).await);
+                }
+                Some(Request::GetBitrate(done)) => {
+                    let _ = done.send(can.get_bitrate().await);
+                }
+                Some(Request::GetErrorCounters(done)) => {
+                    let _ = done.send(can.get_error_counters().await);
+                }
+                Some(Request::GetInfo(done)) => {
+                    let _ = done.send(can.get_info().await);
+                }
+                Some(Request::TxPending(done)) => {
+                    let _ = done.send(can.tx_pending().await);
+                }
+                Some(Request::Flush(done)) => {
+                    let _ = done.send(can.flush().await);
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> std::io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!("CPU core {} is out of range", core),
+        ));
+    }
+    // SAFETY: cpu_set_t is plain data and only accessed through the libc helpers
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        return Err(IoError::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> std::io::Result<()> {
+    Err(IoError::new(
+        ErrorKind::Unsupported,
+        "Pinning threads to a core is only supported on Linux",
+    ))
+}
@@ -0,0 +1,403 @@
+///
+/// export.rs
+///
+/// Exports captured frames and their decoded signals to CSV and Apache Parquet.
+///
+use crate::{can::CanFrame, pipeline::Annotated};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Microseconds since the Unix epoch, negative before it
+pub(crate) fn unix_micros(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_micros() as i64,
+        Err(e) => -(e.duration().as_micros() as i64),
+    }
+}
+
+// Inverse of `unix_micros()`
+#[cfg(feature = "sqlite")]
+pub(crate) fn from_unix_micros(micros: i64) -> SystemTime {
+    let offset = std::time::Duration::from_micros(micros.unsigned_abs());
+    if micros < 0 {
+        UNIX_EPOCH - offset
+    } else {
+        UNIX_EPOCH + offset
+    }
+}
+
+// Looks up the value of each named signal column in `signals`
+fn signal_values<'a>(
+    columns: &'a [String],
+    signals: &'a [(String, f64)],
+) -> impl Iterator<Item = Option<f64>> + 'a {
+    columns.iter().map(|column| {
+        signals
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| *value)
+    })
+}
+
+/// Writes frames as CSV rows
+///
+/// Columns are `time` (seconds since the Unix epoch with microsecond resolution), `channel`,
+/// `id` (decimal), `extended`, `rtr`, `error`, `dlc` and `data` (hex), followed by one column per
+/// signal passed to `with_signals()`. Signals missing from a frame leave their cell empty.
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    signals: Vec<String>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Writes the header row to `writer`
+    pub fn new(writer: W) -> std::io::Result<Self> {
+        Self::with_signals(writer, &[])
+    }
+
+    /// Writes the header row to `writer`, including a column for each signal in `signals`
+    pub fn with_signals(mut writer: W, signals: &[&str]) -> std::io::Result<Self> {
+        let mut header = String::from("time,channel,id,extended,rtr,error,dlc,data");
+        for signal in signals {
+            header.push(',');
+            header.push_str(&csv_field(signal));
+        }
+        writeln!(writer, "{}", header)?;
+        Ok(Self {
+            writer,
+            signals: signals.iter().map(|s| s.to_string()).collect(),
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &CanFrame, time: SystemTime) -> std::io::Result<()> {
+        self.write_row(frame, None, &[], time)
+    }
+
+    /// Writes a frame together with its channel and decoded signals
+    pub fn write_annotated(&mut self, frame: &Annotated, time: SystemTime) -> std::io::Result<()> {
+        self.write_row(&frame.frame, frame.channel.as_deref(), &frame.signals, time)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_row(
+        &mut self,
+        frame: &CanFrame,
+        channel: Option<&str>,
+        signals: &[(String, f64)],
+        time: SystemTime,
+    ) -> std::io::Result<()> {
+        let micros = unix_micros(time);
+        let sign = if micros < 0 { "-" } else { "" };
+        let micros = micros.unsigned_abs();
+        let data: String = frame.data().iter().map(|b| format!("{:02X}", b)).collect();
+
+        let mut row = format!(
+            "{}{}.{:06},{},{},{},{},{},{},{}",
+            sign,
+            micros / 1_000_000,
+            micros % 1_000_000,
+            channel.map(csv_field).unwrap_or_default(),
+            frame.id(),
+            frame.is_extended(),
+            frame.is_rtr(),
+            frame.is_error(),
+            frame.dlc(),
+            data,
+        );
+        for value in signal_values(&self.signals, signals) {
+            row.push(',');
+            if let Some(value) = value {
+                row.push_str(&value.to_string());
+            }
+        }
+        writeln!(self.writer, "{}", row)
+    }
+}
+
+// Quotes a field if it contains a separator, quote or line break (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub use parquet_writer::{DEFAULT_ROW_GROUP_LEN, ParquetWriter};
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use super::{signal_values, unix_micros};
+    use crate::{can::CanFrame, pipeline::Annotated};
+    use parquet::basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+    use parquet::data_type::{
+        BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type,
+    };
+    use parquet::file::{properties::WriterProperties, writer::SerializedFileWriter};
+    use parquet::schema::types::Type;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// Number of rows buffered before a row group is written
+    pub const DEFAULT_ROW_GROUP_LEN: usize = 65_536;
+
+    #[derive(Default)]
+    struct Columns {
+        time: Vec<i64>,
+        channel: Vec<ByteArray>,
+        channel_levels: Vec<i16>,
+        id: Vec<i32>,
+        extended: Vec<bool>,
+        rtr: Vec<bool>,
+        error: Vec<bool>,
+        dlc: Vec<i32>,
+        data: Vec<ByteArray>,
+        // Per signal column: present values and definition levels
+        signals: Vec<(Vec<f64>, Vec<i16>)>,
+    }
+
+    /// Writes frames to an Apache Parquet file
+    ///
+    /// Columns are `time` (timestamp, microseconds, UTC), `channel` (optional string), `id`
+    /// (uint32), `extended`, `rtr`, `error` (booleans), `dlc` (uint8), `data` (binary) and one
+    /// optional double column per signal passed to `with_signals()`. Rows are buffered and written
+    /// in row groups; `close()` must be called to write the file footer.
+    pub struct ParquetWriter<W: Write + Send> {
+        writer: SerializedFileWriter<W>,
+        signals: Vec<String>,
+        columns: Columns,
+        rows: usize,
+        row_group_len: usize,
+    }
+
+    fn to_io_error(e: parquet::errors::ParquetError) -> std::io::Error {
+        match e {
+            parquet::errors::ParquetError::External(e) => std::io::Error::other(e),
+            e => std::io::Error::other(e.to_string()),
+        }
+    }
+
+    fn column(
+        name: &str,
+        physical: PhysicalType,
+        repetition: Repetition,
+        logical: Option<LogicalType>,
+    ) -> parquet::errors::Result<Arc<Type>> {
+        Type::primitive_type_builder(name, physical)
+            .with_repetition(repetition)
+            .with_logical_type(logical)
+            .build()
+            .map(Arc::new)
+    }
+
+    impl<W: Write + Send> ParquetWriter<W> {
+        pub fn new(writer: W) -> std::io::Result<Self> {
+            Self::with_signals(writer, &[])
+        }
+
+        /// Creates a writer with an additional column for each signal in `signals`
+        pub fn with_signals(writer: W, signals: &[&str]) -> std::io::Result<Self> {
+            let unsigned = |bit_width| {
+                Some(LogicalType::Integer {
+                    bit_width,
+                    is_signed: false,
+                })
+            };
+            let mut fields = vec![
+                column(
+                    "time",
+                    PhysicalType::INT64,
+                    Repetition::REQUIRED,
+                    Some(LogicalType::Timestamp {
+                        is_adjusted_to_u_t_c: true,
+                        unit: TimeUnit::MICROS,
+                    }),
+                ),
+                column(
+                    "channel",
+                    PhysicalType::BYTE_ARRAY,
+                    Repetition::OPTIONAL,
+                    Some(LogicalType::String),
+                ),
+                column(
+                    "id",
+                    PhysicalType::INT32,
+                    Repetition::REQUIRED,
+                    unsigned(32),
+                ),
+                column(
+                    "extended",
+                    PhysicalType::BOOLEAN,
+                    Repetition::REQUIRED,
+                    None,
+                ),
+                column("rtr", PhysicalType::BOOLEAN, Repetition::REQUIRED, None),
+                column("error", PhysicalType::BOOLEAN, Repetition::REQUIRED, None),
+                column(
+                    "dlc",
+                    PhysicalType::INT32,
+                    Repetition::REQUIRED,
+                    unsigned(8),
+                ),
+                column("data", PhysicalType::BYTE_ARRAY, Repetition::REQUIRED, None),
+            ];
+            for signal in signals {
+                fields.push(column(
+                    signal,
+                    PhysicalType::DOUBLE,
+                    Repetition::OPTIONAL,
+                    None,
+                ));
+            }
+            let fields = fields
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(to_io_error)?;
+            let schema = Type::group_type_builder("can_frames")
+                .with_fields(fields)
+                .build()
+                .map_err(to_io_error)?;
+
+            let writer = SerializedFileWriter::new(
+                writer,
+                Arc::new(schema),
+                Arc::new(WriterProperties::builder().build()),
+            )
+            .map_err(to_io_error)?;
+
+            Ok(Self {
+                writer,
+                signals: signals.iter().map(|s| s.to_string()).collect(),
+                columns: Columns {
+                    signals: vec![Default::default(); signals.len()],
+                    ..Default::default()
+                },
+                rows: 0,
+                row_group_len: DEFAULT_ROW_GROUP_LEN,
+            })
+        }
+
+        /// Sets the number of rows per row group (default `DEFAULT_ROW_GROUP_LEN`)
+        pub fn with_row_group_len(mut self, rows: usize) -> Self {
+            self.row_group_len = rows.max(1);
+            self
+        }
+
+        pub fn write_frame(&mut self, frame: &CanFrame, time: SystemTime) -> std::io::Result<()> {
+            self.push_row(frame, None, &[], time)
+        }
+
+        /// Writes a frame together with its channel and decoded signals
+        pub fn write_annotated(
+            &mut self,
+            frame: &Annotated,
+            time: SystemTime,
+        ) -> std::io::Result<()> {
+            self.push_row(&frame.frame, frame.channel.as_deref(), &frame.signals, time)
+        }
+
+        /// Writes the buffered rows as a row group
+        pub fn flush(&mut self) -> std::io::Result<()> {
+            if self.rows == 0 {
+                return Ok(());
+            }
+            let columns = std::mem::take(&mut self.columns);
+            self.columns.signals = vec![Default::default(); self.signals.len()];
+            self.rows = 0;
+            self.write_row_group(columns).map_err(to_io_error)
+        }
+
+        /// Writes the remaining rows and the file footer, and returns the underlying writer
+        pub fn close(mut self) -> std::io::Result<W> {
+            self.flush()?;
+            self.writer.into_inner().map_err(to_io_error)
+        }
+
+        fn push_row(
+            &mut self,
+            frame: &CanFrame,
+            channel: Option<&str>,
+            signals: &[(String, f64)],
+            time: SystemTime,
+        ) -> std::io::Result<()> {
+            let columns = &mut self.columns;
+            columns.time.push(unix_micros(time));
+            match channel {
+                Some(channel) => {
+                    columns.channel.push(ByteArray::from(channel));
+                    columns.channel_levels.push(1);
+                }
+                None => columns.channel_levels.push(0),
+            }
+            columns.id.push(frame.id() as i32);
+            columns.extended.push(frame.is_extended());
+            columns.rtr.push(frame.is_rtr());
+            columns.error.push(frame.is_error());
+            columns.dlc.push(frame.dlc() as i32);
+            columns.data.push(ByteArray::from(frame.data().to_vec()));
+            for ((values, levels), value) in columns
+                .signals
+                .iter_mut()
+                .zip(signal_values(&self.signals, signals))
+            {
+                match value {
+                    Some(value) => {
+                        values.push(value);
+                        levels.push(1);
+                    }
+                    None => levels.push(0),
+                }
+            }
+
+            self.rows += 1;
+            if self.rows >= self.row_group_len {
+                self.flush()?;
+            }
+            Ok(())
+        }
+
+        fn write_row_group(&mut self, columns: Columns) -> parquet::errors::Result<()> {
+            let mut row_group = self.writer.next_row_group()?;
+
+            macro_rules! write_column {
+                ($type:ty, $values:expr, $levels:expr) => {{
+                    let mut column = row_group.next_column()?.ok_or_else(|| {
+                        parquet::errors::ParquetError::General("Missing column".into())
+                    })?;
+                    column
+                        .typed::<$type>()
+                        .write_batch($values, $levels, None)?;
+                    column.close()?;
+                }};
+            }
+
+            write_column!(Int64Type, &columns.time, None);
+            write_column!(
+                ByteArrayType,
+                &columns.channel,
+                Some(&columns.channel_levels)
+            );
+            write_column!(Int32Type, &columns.id, None);
+            write_column!(BoolType, &columns.extended, None);
+            write_column!(BoolType, &columns.rtr, None);
+            write_column!(BoolType, &columns.error, None);
+            write_column!(Int32Type, &columns.dlc, None);
+            write_column!(ByteArrayType, &columns.data, None);
+            for (values, levels) in &columns.signals {
+                write_column!(DoubleType, values, Some(levels));
+            }
+
+            row_group.close()?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,229 @@
+///
+/// grpc.rs
+///
+/// gRPC service giving remote clients streaming access to CAN interfaces (see proto/crosscan.proto).
+///
+use crate::{
+    CanInterface, FramesDropped,
+    can::CanFrame,
+    proto::{self, InterfaceEvent},
+};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+use tonic::{Request, Response, Status};
+
+#[allow(clippy::all)]
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/crosscan.CanBus.rs"));
+}
+pub use generated::can_bus_client::CanBusClient;
+pub use generated::can_bus_server::{CanBus, CanBusServer};
+
+// Events buffered per subscriber before it starts losing frames
+const EVENT_QUEUE_LEN: usize = 1024;
+const REQUEST_QUEUE_LEN: usize = 64;
+
+enum ChannelRequest {
+    Send(CanFrame, oneshot::Sender<std::io::Result<()>>),
+    GetBitrate(oneshot::Sender<std::io::Result<Option<u32>>>),
+}
+
+// What the interface task broadcasts. Plain stack values, so the per-subscriber clone does not
+// allocate; each subscriber converts them to protobuf messages itself.
+#[derive(Clone)]
+enum ChannelEvent {
+    Frame(CanFrame),
+    Dropped(FramesDropped),
+}
+
+struct Channel {
+    // Weak so that subscriptions end once the interface task stops
+    events: broadcast::WeakSender<ChannelEvent>,
+    requests: mpsc::Sender<ChannelRequest>,
+}
+
+/// Serves a set of named CAN interfaces over gRPC
+///
+/// Each interface is owned by a task that broadcasts received frames to all subscribers and
+/// executes Send/GetConfig requests in between reads.
+#[derive(Default)]
+pub struct GrpcGateway {
+    channels: BTreeMap<String, Channel>,
+}
+
+impl GrpcGateway {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `can` available as `name`. Must be called within a tokio runtime.
+    pub fn add_channel<T>(&mut self, name: &str, can: T)
+    where
+        T: CanInterface + Send + 'static,
+    {
+        let (events, _) = broadcast::channel(EVENT_QUEUE_LEN);
+        let (requests_tx, requests_rx) = mpsc::channel(REQUEST_QUEUE_LEN);
+        self.channels.insert(
+            name.to_string(),
+            Channel {
+                events: events.downgrade(),
+                requests: requests_tx,
+            },
+        );
+        tokio::spawn(run_channel(can, events, requests_rx));
+    }
+
+    /// Returns the service for use with tonic's server builder
+    pub fn into_service(self) -> CanBusServer<Self> {
+        CanBusServer::new(self)
+    }
+
+    /// Returns the service, rejecting calls without an `authorization: Bearer <token>` header
+    pub fn into_authenticated_service(
+        self,
+        token: &str,
+    ) -> tonic::service::interceptor::InterceptedService<CanBusServer<Self>, BearerAuth> {
+        CanBusServer::with_interceptor(self, BearerAuth(format!("Bearer {}", token).into()))
+    }
+
+    fn channel(&self, name: &str) -> Result<&Channel, Status> {
+        self.channels
+            .get(name)
+            .ok_or_else(|| Status::not_found(format!("No channel named {}", name)))
+    }
+
+    async fn request<R>(
+        &self,
+        channel: &str,
+        request: impl FnOnce(oneshot::Sender<std::io::Result<R>>) -> ChannelRequest,
+    ) -> Result<R, Status> {
+        let closed = || Status::unavailable(format!("Channel {} is closed", channel));
+        let (tx, rx) = oneshot::channel();
+        self.channel(channel)?
+            .requests
+            .send(request(tx))
+            .await
+            .map_err(|_| closed())?;
+        rx.await
+            .map_err(|_| closed())?
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+/// Checks the `authorization` metadata of every call against a fixed bearer token
+#[derive(Clone)]
+pub struct BearerAuth(Arc<str>);
+
+impl tonic::service::Interceptor for BearerAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        match request.metadata().get("authorization") {
+            Some(value) if value.as_bytes() == self.0.as_bytes() => Ok(request),
+            _ => Err(Status::unauthenticated("Invalid or missing bearer token")),
+        }
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<InterfaceEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl CanBus for GrpcGateway {
+    type SubscribeStream = EventStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let request = request.into_inner();
+        let events = self
+            .channel(&request.channel)?
+            .events
+            .upgrade()
+            .ok_or_else(|| Status::unavailable(format!("Channel {} is closed", request.channel)))?;
+        let filters = request.filters;
+
+        let stream = BroadcastStream::new(events.subscribe()).filter_map(move |event| {
+            let event = match event {
+                Ok(ChannelEvent::Frame(frame)) => {
+                    if !filters.is_empty() && !filters.iter().any(|f| f.matches(&frame)) {
+                        return None;
+                    }
+                    InterfaceEvent::from(&frame)
+                }
+                Ok(ChannelEvent::Dropped(dropped)) => InterfaceEvent::from(dropped),
+                // The subscriber fell behind and missed events
+                Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(count)) => {
+                    InterfaceEvent::from(FramesDropped(count))
+                }
+            };
+            Some(Ok(event))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn send(
+        &self,
+        request: Request<proto::SendRequest>,
+    ) -> Result<Response<proto::SendResponse>, Status> {
+        let request = request.into_inner();
+        let frame = request
+            .frame
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("Missing frame"))?;
+        let frame = CanFrame::try_from(frame).map_err(Status::invalid_argument)?;
+        self.request(&request.channel, |done| ChannelRequest::Send(frame, done))
+            .await?;
+        Ok(Response::new(proto::SendResponse {}))
+    }
+
+    async fn get_config(
+        &self,
+        request: Request<proto::GetConfigRequest>,
+    ) -> Result<Response<proto::InterfaceConfig>, Status> {
+        let channel = request.into_inner().channel;
+        let bitrate = self.request(&channel, ChannelRequest::GetBitrate).await?;
+        Ok(Response::new(proto::InterfaceConfig { channel, bitrate }))
+    }
+
+    async fn list_channels(
+        &self,
+        _request: Request<proto::ListChannelsRequest>,
+    ) -> Result<Response<proto::ListChannelsResponse>, Status> {
+        Ok(Response::new(proto::ListChannelsResponse {
+            channels: self.channels.keys().cloned().collect(),
+        }))
+    }
+}
+
+async fn run_channel<T: CanInterface>(
+    mut can: T,
+    events: broadcast::Sender<ChannelEvent>,
+    mut requests: mpsc::Receiver<ChannelRequest>,
+) {
+    loop {
+        tokio::select! {
+            result = can.read_frame() => {
+                let event = match result {
+                    Ok(frame) => ChannelEvent::Frame(frame),
+                    Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                        Some(dropped) => ChannelEvent::Dropped(*dropped),
+                        None => return,
+                    },
+                };
+                // Having no subscribers is not an error
+                let _ = events.send(event);
+            }
+            request = requests.recv() => match request {
+                Some(ChannelRequest::Send(frame, done)) => {
+                    let _ = done.send(can.write_frame(frame).await);
+                }
+                Some(ChannelRequest::GetBitrate(done)) => {
+                    let _ = done.send(can.get_bitrate().await);
+                }
+                None => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+///
+/// hotplug.rs
+///
+/// Discovery of USB CAN adapters as they are plugged in and out.
+///
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::io::Error as IoError;
+
+/// A USB CAN adapter model recognised by vendor and product ID
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KnownAdapter {
+    pub vendor_id: u16,
+    /// None matches every product of the vendor
+    pub product_id: Option<u16>,
+    pub name: &'static str,
+}
+
+/// Adapters recognised by `AdapterWatcher::new()`
+pub const KNOWN_ADAPTERS: &[KnownAdapter] = &[
+    KnownAdapter {
+        vendor_id: 0x1D50,
+        product_id: Some(0x606F),
+        name: "candleLight (gs_usb)",
+    },
+    KnownAdapter {
+        vendor_id: 0x16D0,
+        product_id: Some(0x117E),
+        name: "CANable (slcan)",
+    },
+    KnownAdapter {
+        vendor_id: 0x0C72,
+        product_id: Some(0x000C),
+        name: "PEAK PCAN-USB",
+    },
+    KnownAdapter {
+        vendor_id: 0x0C72,
+        product_id: Some(0x0011),
+        name: "PEAK PCAN-USB Pro FD",
+    },
+    KnownAdapter {
+        vendor_id: 0x0C72,
+        product_id: Some(0x0012),
+        name: "PEAK PCAN-USB FD",
+    },
+    KnownAdapter {
+        vendor_id: 0x0BFD,
+        product_id: None,
+        name: "Kvaser",
+    },
+];
+
+/// A connected CAN adapter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdapterInfo {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Name of the matching KnownAdapter
+    pub name: &'static str,
+    /// Product string reported by the device
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdapterEvent {
+    Connected(AdapterInfo),
+    Disconnected(AdapterInfo),
+}
+
+fn match_adapter(adapters: &[KnownAdapter], device: &nusb::DeviceInfo) -> Option<AdapterInfo> {
+    adapters
+        .iter()
+        .find(|a| {
+            a.vendor_id == device.vendor_id()
+                && a.product_id.is_none_or(|p| p == device.product_id())
+        })
+        .map(|adapter| AdapterInfo {
+            vendor_id: device.vendor_id(),
+            product_id: device.product_id(),
+            name: adapter.name,
+            product: device.product_string().map(str::to_string),
+            serial_number: device.serial_number().map(str::to_string),
+        })
+}
+
+/// Lists the currently connected adapters from KNOWN_ADAPTERS
+pub async fn list_adapters() -> std::io::Result<Vec<AdapterInfo>> {
+    let devices = nusb::list_devices().await.map_err(IoError::other)?;
+    Ok(devices
+        .filter_map(|device| match_adapter(KNOWN_ADAPTERS, &device))
+        .collect())
+}
+
+/// Reports CAN adapters being connected and disconnected (udev on Linux, SetupAPI on Windows)
+pub struct AdapterWatcher {
+    watch: nusb::hotplug::HotplugWatch,
+    adapters: Vec<KnownAdapter>,
+    present: HashMap<nusb::DeviceId, AdapterInfo>,
+}
+
+impl AdapterWatcher {
+    /// Starts watching for the adapters in KNOWN_ADAPTERS
+    pub async fn new() -> std::io::Result<Self> {
+        Self::with_adapters(KNOWN_ADAPTERS.to_vec()).await
+    }
+
+    /// Starts watching for `adapters`, e.g. KNOWN_ADAPTERS extended with in-house devices
+    pub async fn with_adapters(adapters: Vec<KnownAdapter>) -> std::io::Result<Self> {
+        // Start watching before listing so no device is missed in between
+        let watch = nusb::watch_devices().map_err(IoError::other)?;
+        let present = nusb::list_devices()
+            .await
+            .map_err(IoError::other)?
+            .filter_map(|device| Some((device.id(), match_adapter(&adapters, &device)?)))
+            .collect();
+        Ok(Self {
+            watch,
+            adapters,
+            present,
+        })
+    }
+
+    /// Adapters connected right now
+    pub fn present(&self) -> impl Iterator<Item = &AdapterInfo> {
+        self.present.values()
+    }
+
+    /// Waits for the next adapter to be connected or disconnected
+    ///
+    /// Cancel safe. Events for other USB devices are skipped.
+    pub async fn next_event(&mut self) -> AdapterEvent {
+        loop {
+            let Some(event) = self.watch.next().await else {
+                // The platform watchers never end their stream
+                return std::future::pending().await;
+            };
+            match event {
+                nusb::hotplug::HotplugEvent::Connected(device) => {
+                    if let Some(info) = match_adapter(&self.adapters, &device) {
+                        self.present.insert(device.id(), info.clone());
+                        return AdapterEvent::Connected(info);
+                    }
+                }
+                nusb::hotplug::HotplugEvent::Disconnected(id) => {
+                    if let Some(info) = self.present.remove(&id) {
+                        return AdapterEvent::Disconnected(info);
+                    }
+                }
+            }
+        }
+    }
+}
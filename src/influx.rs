@@ -0,0 +1,414 @@
+///
+/// influx.rs
+///
+/// Pushes decoded signals to InfluxDB or VictoriaMetrics in the line protocol.
+///
+use crate::pipeline::{Annotated, Stage};
+use std::collections::VecDeque;
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+
+/// Maximum number of lines sent in one request
+pub const DEFAULT_BATCH_LEN: usize = 5000;
+/// Time after which buffered lines are sent even if the batch is not full
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of buffered lines above which the oldest ones are dropped
+pub const DEFAULT_MAX_BUFFERED: usize = 100_000;
+/// Upper bound of the wait between retries of a failed request
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+// Time allowed for connecting, sending a batch and reading the response status
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Escapes the characters that end a measurement name
+fn escape_measurement(name: &str) -> String {
+    escape(name, &[',', ' '])
+}
+
+// Escapes the characters that end a tag key, tag value or field key
+fn escape_key(key: &str) -> String {
+    escape(key, &[',', '=', ' '])
+}
+
+fn escape(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// The parts of an http:// URL needed for a request
+#[derive(Clone, Debug)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    // Path and query, e.g. /api/v2/write?bucket=can
+    target: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> std::io::Result<Self> {
+        let invalid =
+            |message: &str| IoError::new(ErrorKind::InvalidInput, format!("{}: {}", message, url));
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("Only http:// URLs are supported"))?;
+        let (authority, target) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse().map_err(|_| invalid("Invalid port in URL"))?,
+            ),
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid("Missing host in URL"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            target: target.to_string(),
+        })
+    }
+}
+
+/// Configures an InfluxDB or VictoriaMetrics line protocol exporter, started with `spawn()`
+///
+/// Every frame with decoded signals becomes one line: the measurement, the tags `channel` (if the
+/// frame was tagged), `id` (hex, 3 digits for standard and 8 for extended IDs) and those added
+/// with `with_tag()`, one float field per signal and the receive time in nanoseconds. Non-finite
+/// signal values are left out, as InfluxDB rejects them.
+///
+/// The URL is the write endpoint, e.g. `http://localhost:8086/api/v2/write?org=lab&bucket=can`
+/// for InfluxDB 2, `http://localhost:8086/write?db=can` for InfluxDB 1 or
+/// `http://localhost:8428/write` for VictoriaMetrics. Only plain HTTP is supported; put a local
+/// proxy or Telegraf in between to reach a server over TLS.
+#[derive(Clone, Debug)]
+pub struct InfluxExporter {
+    endpoint: Endpoint,
+    authorization: Option<String>,
+    measurement: String,
+    tags: String,
+    batch_len: usize,
+    flush_interval: Duration,
+    max_buffered: usize,
+    max_backoff: Duration,
+}
+
+impl InfluxExporter {
+    /// Creates an exporter writing to `url` with the measurement name `can`
+    pub fn new(url: &str) -> std::io::Result<Self> {
+        Ok(Self {
+            endpoint: Endpoint::parse(url)?,
+            authorization: None,
+            measurement: "can".to_string(),
+            tags: String::new(),
+            batch_len: DEFAULT_BATCH_LEN,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            max_buffered: DEFAULT_MAX_BUFFERED,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        })
+    }
+
+    /// Sends `value` in the Authorization header, e.g. `Token <token>` for InfluxDB 2 or
+    /// `Bearer <token>` for VictoriaMetrics
+    pub fn with_authorization(mut self, value: &str) -> Self {
+        self.authorization = Some(value.to_string());
+        self
+    }
+
+    pub fn with_measurement(mut self, measurement: &str) -> Self {
+        self.measurement = escape_measurement(measurement);
+        self
+    }
+
+    /// Adds a tag to every line, e.g. the vehicle or test bench the data comes from
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags
+            .push_str(&format!(",{}={}", escape_key(key), escape_key(value)));
+        self
+    }
+
+    /// Sets the maximum number of lines sent in one request
+    pub fn with_batch_len(mut self, batch_len: usize) -> Self {
+        self.batch_len = batch_len.max(1);
+        self
+    }
+
+    /// Sets the time after which buffered lines are sent even if the batch is not full
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Sets the number of buffered lines above which the oldest ones are dropped while the server
+    /// is unreachable
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered.max(1);
+        self
+    }
+
+    /// Sets the upper bound of the wait between retries, which doubles after each failed request
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Encodes the signals of `frame` as a line, or returns None if it has no finite signal values
+    pub fn encode(&self, frame: &Annotated, time: SystemTime) -> Option<String> {
+        let fields: Vec<String> = frame
+            .signals
+            .iter()
+            .filter(|(_, value)| value.is_finite())
+            .map(|(name, value)| format!("{}={}", escape_key(name), value))
+            .collect();
+        if fields.is_empty() {
+            return None;
+        }
+
+        let mut line = self.measurement.clone();
+        if let Some(channel) = &frame.channel {
+            line.push_str(&format!(",channel={}", escape_key(channel)));
+        }
+        line.push_str(&format!(",id={}{}", frame.frame.id_hex(), self.tags));
+        let nanos = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_else(|e| -(e.duration().as_nanos() as i64));
+        line.push_str(&format!(" {} {}", fields.join(","), nanos));
+        Some(line)
+    }
+
+    /// Starts sending lines in a background task and returns the handle that buffers them
+    ///
+    /// Must be called from within a Tokio runtime. The task stops after the last handle was
+    /// dropped and the remaining lines were sent, or with `InfluxSink::close()`.
+    pub fn spawn(self) -> InfluxSink {
+        let shared = Arc::new(Shared {
+            lines: Mutex::new(VecDeque::new()),
+            batch_ready: Notify::new(),
+            closing: Notify::new(),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            last_error: Mutex::new(None),
+        });
+        let exporter = Arc::new(self);
+        let task = tokio::spawn(run_exporter(exporter.clone(), shared.clone()));
+        InfluxSink {
+            exporter,
+            shared,
+            task: Arc::new(Mutex::new(Some(task))),
+        }
+    }
+
+    // Sends one batch and returns an error of kind InvalidData if the server rejected the lines,
+    // which makes retrying pointless
+    async fn post(&self, body: &str) -> std::io::Result<()> {
+        let endpoint = &self.endpoint;
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n",
+            endpoint.target,
+            endpoint.host,
+            endpoint.port,
+            body.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            request.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+
+        let exchange = async {
+            let host = endpoint.host.trim_start_matches('[').trim_end_matches(']');
+            let mut stream = TcpStream::connect((host, endpoint.port)).await?;
+            stream.write_all(request.as_bytes()).await?;
+            stream.write_all(body.as_bytes()).await?;
+            let mut response = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !response.windows(2).any(|w| w == b"\r\n") {
+                let n = stream.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                response.extend_from_slice(&buf[..n]);
+            }
+            Ok::<_, IoError>(response)
+        };
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+            .await
+            .map_err(|_| IoError::new(ErrorKind::TimedOut, "Line protocol write timed out"))??;
+
+        let status_line = String::from_utf8_lossy(&response);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        let status: u16 = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| {
+                IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid HTTP response: {:?}", status_line),
+                )
+            })?;
+        match status {
+            200..=299 => Ok(()),
+            429 | 500..=599 => Err(IoError::other(format!("Server busy: {}", status_line))),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Server rejected lines: {}", status_line),
+            )),
+        }
+    }
+}
+
+struct Shared {
+    lines: Mutex<VecDeque<String>>,
+    batch_ready: Notify,
+    closing: Notify,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    last_error: Mutex<Option<String>>,
+}
+
+impl Shared {
+    // True once no more lines can arrive, after which failed batches are not retried
+    fn finishing(self: &Arc<Self>) -> bool {
+        self.closed.load(Ordering::Acquire) || Arc::strong_count(self) == 1
+    }
+
+    fn take_batch(&self, batch_len: usize) -> Vec<String> {
+        let mut lines = self.lines.lock().unwrap();
+        let len = lines.len().min(batch_len);
+        lines.drain(..len).collect()
+    }
+}
+
+// Sends buffered lines in batches until closed or no handle is left, retrying failed requests
+async fn run_exporter(exporter: Arc<InfluxExporter>, shared: Arc<Shared>) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let finishing = shared.finishing();
+        if !finishing {
+            let _ =
+                tokio::time::timeout(exporter.flush_interval, shared.batch_ready.notified()).await;
+        }
+
+        let batch = shared.take_batch(exporter.batch_len);
+        if batch.is_empty() {
+            if finishing {
+                return;
+            }
+            continue;
+        }
+
+        let body = batch.join("\n");
+        loop {
+            match exporter.post(&body).await {
+                Ok(()) => {
+                    backoff = INITIAL_BACKOFF;
+                    break;
+                }
+                Err(e) => {
+                    *shared.last_error.lock().unwrap() = Some(e.to_string());
+                    if e.kind() == ErrorKind::InvalidData || shared.finishing() {
+                        shared
+                            .dropped
+                            .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                        break;
+                    }
+                    tokio::select! {
+                        _ = tokio::time::sleep(backoff) => {}
+                        _ = shared.closing.notified() => {}
+                    }
+                    backoff = (backoff * 2).min(exporter.max_backoff);
+                }
+            }
+        }
+    }
+}
+
+/// Buffers lines for a running InfluxExporter
+///
+/// Writing never blocks: lines are sent by the background task, and while the server is
+/// unreachable the oldest lines are dropped once `with_max_buffered()` is exceeded. Clones share
+/// the same buffer. As a pipeline stage it exports every frame with signals, stamped with the time
+/// it passes the stage, and passes it on unchanged.
+#[derive(Clone)]
+pub struct InfluxSink {
+    exporter: Arc<InfluxExporter>,
+    shared: Arc<Shared>,
+    task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+impl InfluxSink {
+    /// Buffers the signals of `frame`, received at `time`. Frames without signals are ignored
+    pub fn write_annotated(&self, frame: &Annotated, time: SystemTime) {
+        if self.shared.closed.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(line) = self.exporter.encode(frame, time) else {
+            return;
+        };
+        let mut lines = self.shared.lines.lock().unwrap();
+        lines.push_back(line);
+        if lines.len() > self.exporter.max_buffered {
+            lines.pop_front();
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        if lines.len() >= self.exporter.batch_len {
+            self.shared.batch_ready.notify_one();
+        }
+    }
+
+    /// Returns the number of lines dropped because the buffer was full or the server rejected them
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the error of the last failed request, if any
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.last_error.lock().unwrap().clone()
+    }
+
+    /// Sends the buffered lines and stops the background task
+    ///
+    /// Failed requests are not retried; their lines count as dropped. Returns an error if any line
+    /// was dropped since the exporter was started. Other handles stop buffering.
+    pub async fn close(self) -> std::io::Result<()> {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.batch_ready.notify_one();
+        self.shared.closing.notify_one();
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.await.map_err(IoError::other)?;
+        }
+        match self.dropped() {
+            0 => Ok(()),
+            dropped => Err(IoError::other(format!(
+                "{} lines were dropped, last error: {}",
+                dropped,
+                self.last_error().unwrap_or_else(|| "none".to_string())
+            ))),
+        }
+    }
+}
+
+impl Stage for InfluxSink {
+    fn process(&mut self, frame: Annotated) -> Option<Annotated> {
+        self.write_annotated(&frame, SystemTime::now());
+        Some(frame)
+    }
+}
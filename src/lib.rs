@@ -1,8 +1,53 @@
+pub mod bitwatch;
 pub mod can;
-use can::CanFrame;
+pub mod canopen;
+pub mod capture;
+pub mod census;
+pub mod clock;
+pub mod dedicated;
+pub mod export;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "hotplug")]
+pub mod hotplug;
+#[cfg(feature = "influx")]
+pub mod influx;
+pub mod logops;
+pub mod lvc;
+pub mod meta;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod pcap;
+pub mod pipeline;
+#[cfg(feature = "profiles")]
+pub mod profiles;
+#[cfg(feature = "protobuf")]
+pub mod proto;
+#[cfg(feature = "ros2-msgs")]
+pub mod ros;
+pub mod series;
+pub mod simulator;
+pub mod slcan;
+pub mod spsc;
+#[cfg(feature = "sqlite")]
+pub mod store;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+pub mod testing;
+pub mod trigger;
+pub mod watchdog;
+#[cfg(any(target_os = "windows", feature = "wire"))]
+pub mod wire;
+#[cfg(feature = "zenoh")]
+pub mod zenoh;
+use can::{CanErrorCounters, CanFrame, InterfaceInfo};
 
 /// A generic async CAN interface for reading and writing CAN frames
-pub trait CanInterface: Sized {
+///
+/// Only `open`, `read_frame`, `write_frame` and `get_bitrate` must be implemented. `read_frames`
+/// and `write_frames` fall back to one frame at a time, and the other methods return an error of
+/// kind `Unsupported` unless the backend overrides them.
+pub trait CanInterface: Sized + Send {
     /// Opens a CAN interface
     fn open(interface: &str) -> impl std::future::Future<Output = std::io::Result<Self>> + Send;
 
@@ -10,16 +55,132 @@ pub trait CanInterface: Sized {
     fn read_frame(&mut self)
     -> impl std::future::Future<Output = std::io::Result<CanFrame>> + Send;
 
+    /// Read all frames currently available on the interface, waiting until there is at least one
+    fn read_frames(
+        &mut self,
+    ) -> impl std::future::Future<Output = std::io::Result<Vec<CanFrame>>> + Send {
+        async move { Ok(vec![self.read_frame().await?]) }
+    }
+
     /// Write a single CAN frame from the interface
     fn write_frame(
         &mut self,
         frame: CanFrame,
     ) -> impl std::future::Future<Output = std::io::Result<()>> + Send;
 
+    /// Write several CAN frames to the interface, in order
+    ///
+    /// If writing fails after some of the frames were sent, the error wraps a `PartialWrite` with
+    /// the number sent so a retry can resume from the first unsent frame.
+    fn write_frames(
+        &mut self,
+        frames: &[CanFrame],
+    ) -> impl std::future::Future<Output = std::io::Result<()>> + Send {
+        async move {
+            for (sent, frame) in frames.iter().enumerate() {
+                if let Err(e) = self.write_frame(frame.clone()).await {
+                    return Err(PartialWrite::wrap(sent, e));
+                }
+            }
+            Ok(())
+        }
+    }
+
     /// Returns the bitrate of the CAN bus. Returns None if no bitrate is configured
     fn get_bitrate(
         &mut self,
     ) -> impl std::future::Future<Output = std::io::Result<Option<u32>>> + Send;
+
+    /// Returns the controller's transmit/receive error counters. Returns None if the interface does not report them
+    fn get_error_counters(
+        &mut self,
+    ) -> impl std::future::Future<Output = std::io::Result<Option<CanErrorCounters>>> + Send {
+        async { Err(unsupported("get_error_counters")) }
+    }
+
+    /// Returns details of the adapter such as its driver, clock and supported bitrates
+    fn get_info(
+        &mut self,
+    ) -> impl std::future::Future<Output = std::io::Result<InterfaceInfo>> + Send {
+        async { Err(unsupported("get_info")) }
+    }
+
+    /// Returns the number of bytes written to the interface that the driver has not yet accepted
+    fn tx_pending(&mut self) -> impl std::future::Future<Output = std::io::Result<usize>> + Send {
+        async { Err(unsupported("tx_pending")) }
+    }
+
+    /// Waits until all previously written frames have been accepted by the driver
+    ///
+    /// Backends that can only observe a queue shared with other writers, such as `LinuxCan`, wait
+    /// for that whole queue, which may never drain on a busy bus or one without other nodes.
+    /// Use `tokio::time::timeout` to bound the wait.
+    fn flush(&mut self) -> impl std::future::Future<Output = std::io::Result<()>> + Send {
+        async { Err(unsupported("flush")) }
+    }
+}
+
+// Error returned by the default implementations of optional CanInterface methods
+fn unsupported(method: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{} is not supported by this interface", method),
+    )
+}
+
+/// Error reported by `read_frame` when frames were lost before they could be read
+///
+/// Returned wrapped in an `std::io::Error` of kind `Other`; the interface stays usable and the next
+/// read returns the frames received after the loss.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramesDropped(pub u64);
+
+impl std::fmt::Display for FramesDropped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} frames were dropped before they could be read",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for FramesDropped {}
+
+/// Error reported by `write_frames` when it fails after some of the frames were sent
+///
+/// Returned wrapped in an `std::io::Error` of the same kind as `source`. The first `sent` frames
+/// are on their way to the bus; retrying the whole batch would send them twice.
+#[derive(Debug)]
+pub struct PartialWrite {
+    pub sent: usize,
+    pub source: std::io::Error,
+}
+
+impl PartialWrite {
+    // Wraps a write error, unless nothing was sent in which case the caller can simply retry
+    fn wrap(sent: usize, source: std::io::Error) -> std::io::Error {
+        if sent == 0 {
+            return source;
+        }
+        std::io::Error::new(source.kind(), PartialWrite { sent, source })
+    }
+}
+
+impl std::fmt::Display for PartialWrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "write failed after {} frames were sent: {}",
+            self.sent, self.source
+        )
+    }
+}
+
+impl std::error::Error for PartialWrite {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
 }
 
 #[cfg(target_os = "macos")]
@@ -27,6 +188,10 @@ compile_error!("Currently only linux or windows are supported");
 
 #[cfg(target_os = "linux")]
 pub mod lin_can;
+#[cfg(target_os = "linux")]
+mod lin_netlink;
 
 #[cfg(target_os = "windows")]
 pub mod win_can;
+#[cfg(target_os = "windows")]
+mod win_pipe;
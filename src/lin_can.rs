@@ -3,38 +3,1299 @@
 ///
 /// Implementation of CanInterface for Linux using SocketCan.
 ///
-use crate::{CanInterface, can::CanFrame};
-use socketcan::{nl, tokio::CanSocket};
+use crate::{
+    CanInterface, FramesDropped, PartialWrite,
+    can::{BusState, CanErrorCounters, CanFrame, CanXlFrame, InterfaceInfo, Strictness},
+    lin_netlink,
+};
+use socketcan::{CanSocket, Socket, SocketOptions, frame::AsPtr, nl};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+use tokio::io::{Interest, unix::AsyncFd};
+use tokio::sync::watch;
 
+// Interval used when waiting for room in the socket's send buffer.
+const TX_RETRY_INTERVAL: Duration = Duration::from_micros(500);
+// Longest pause between two qdisc backlog queries in `flush()`
+const FLUSH_POLL_MAX: Duration = Duration::from_millis(20);
+// Maximum number of frames pulled from the kernel by a single recvmmsg call
+const RX_BATCH_LEN: usize = 32;
+// Maximum number of frames handed to the kernel by a single sendmmsg call
+const TX_BATCH_LEN: usize = 32;
+// IFLA_CAN_* link attributes from linux/can/netlink.h
+const IFLA_CAN_CLOCK: u16 = 3;
+const IFLA_CAN_TERMINATION_CONST: u16 = 12;
+const IFLA_CAN_BITRATE_CONST: u16 = 13;
+const IFLA_CAN_DATA_BITRATE_CONST: u16 = 14;
+const IFLA_CAN_BITRATE_MAX: u16 = 15;
+
+/// Bitrates tried by `LinuxCan::detect_bitrate`, most common first
+pub const STANDARD_BITRATES: [u32; 9] = [
+    500_000, 250_000, 1_000_000, 125_000, 800_000, 100_000, 50_000, 20_000, 10_000,
+];
+
+/// How `write_frame` behaves when the kernel transmit queue is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Return the ENOBUFS error from the kernel immediately (default)
+    #[default]
+    FailFast,
+    /// Wait until the driver accepts the frame instead of failing with ENOBUFS
+    Backpressure,
+    /// Like `Backpressure`, and additionally wait until the kernel echoes the frame back as sent
+    ///
+    /// Drivers that support local echo (IFF_ECHO) report a frame once the controller transmitted
+    /// it, otherwise the echo only confirms that the frame was queued to the driver. A frame that
+    /// is never acknowledged on the bus is never confirmed, so wrap writes in a timeout if the bus
+    /// may have no other nodes.
+    Confirmed,
+}
+
+/// Kernel traffic counters of a CAN network interface
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    /// CAN controller statistics. None for interfaces without a controller (e.g. vcan)
+    pub can: Option<CanDeviceStats>,
+}
+
+/// CAN controller event counters (struct can_device_stats)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CanDeviceStats {
+    pub bus_errors: u32,
+    pub error_warning: u32,
+    pub error_passive: u32,
+    pub bus_off: u32,
+    pub arbitration_lost: u32,
+    pub restarts: u32,
+}
+
+/// Detailed CAN bit-timing parameters of an interface
+///
+/// The kernel reports both the bitrate and the segments but accepts only one of them. When set, a
+/// non-zero `bitrate` (with `sample_point`) takes precedence and the segment fields are ignored, so
+/// timing read with `get_bit_timing()` can be set again as is. Zero `bitrate` to set the segments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BitTiming {
+    /// Bit-rate in bits/second
+    pub bitrate: u32,
+    /// Sample point in one-tenth of a percent
+    pub sample_point: u32,
+    /// Time quantum in nanoseconds
+    pub tq: u32,
+    /// Propagation segment in time quanta
+    pub prop_seg: u32,
+    /// Phase buffer segment 1 in time quanta
+    pub phase_seg1: u32,
+    /// Phase buffer segment 2 in time quanta
+    pub phase_seg2: u32,
+    /// Synchronisation jump width in time quanta
+    pub sjw: u32,
+    /// Bit-rate prescaler
+    pub brp: u32,
+}
+
+impl From<nl::CanBitTiming> for BitTiming {
+    fn from(bt: nl::CanBitTiming) -> Self {
+        Self {
+            bitrate: bt.bitrate,
+            sample_point: bt.sample_point,
+            tq: bt.tq,
+            prop_seg: bt.prop_seg,
+            phase_seg1: bt.phase_seg1,
+            phase_seg2: bt.phase_seg2,
+            sjw: bt.sjw,
+            brp: bt.brp,
+        }
+    }
+}
+
+// Keeps only the bitrate and sample point if a bitrate is given, as the kernel rejects timing with
+// both a bitrate and a time quantum
+impl From<BitTiming> for nl::CanBitTiming {
+    fn from(bt: BitTiming) -> Self {
+        if bt.bitrate != 0 {
+            return Self {
+                bitrate: bt.bitrate,
+                sample_point: bt.sample_point,
+                ..Self::default()
+            };
+        }
+        Self {
+            bitrate: bt.bitrate,
+            sample_point: bt.sample_point,
+            tq: bt.tq,
+            prop_seg: bt.prop_seg,
+            phase_seg1: bt.phase_seg1,
+            phase_seg2: bt.phase_seg2,
+            sjw: bt.sjw,
+            brp: bt.brp,
+        }
+    }
+}
+
+/// State of a network interface found while diagnosing a failed open
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkState {
+    /// Administratively up (`ip link set ... up`)
+    pub up: bool,
+    /// Up and able to pass traffic, e.g. not bus-off
+    pub running: bool,
+    /// Operational state as shown by `ip link`, e.g. "UP", "DOWN" or "UNKNOWN"
+    pub oper_state: &'static str,
+}
+
+/// Why `LinuxCan::open()` failed, with what was found out about the interface
+///
+/// Returned wrapped in an `std::io::Error` of kind `NotFound`, `InvalidInput`, `NetworkDown` or
+/// `PermissionDenied` respectively. Use `get_ref()` and `downcast_ref()` to inspect it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpenError {
+    /// No network interface has this name. Lists the CAN interfaces that exist
+    NoSuchInterface {
+        interface: String,
+        available: Vec<String>,
+    },
+    /// The interface exists but is not a CAN interface. `link_type` is its ARPHRD_* type
+    NotCan { interface: String, link_type: u16 },
+    /// The interface exists but is down
+    Down { interface: String, state: LinkState },
+    /// The process may not open a CAN socket on the interface
+    PermissionDenied { interface: String },
+}
+
+impl std::fmt::Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenError::NoSuchInterface {
+                interface,
+                available,
+            } if available.is_empty() => write!(
+                f,
+                "CAN interface {} does not exist, and no other CAN interface does either. Is the \
+                 adapter plugged in and its driver loaded?",
+                interface
+            ),
+            OpenError::NoSuchInterface {
+                interface,
+                available,
+            } => write!(
+                f,
+                "CAN interface {} does not exist. Available CAN interfaces: {}",
+                interface,
+                available.join(", ")
+            ),
+            OpenError::NotCan {
+                interface,
+                link_type,
+            } => write!(
+                f,
+                "{} is not a CAN interface (link type {})",
+                interface, link_type
+            ),
+            OpenError::Down { interface, state } => write!(
+                f,
+                "CAN interface {} is down (state {}). Bring it up with `ip link set {} up type \
+                 can bitrate <bitrate>`",
+                interface, state.oper_state, interface
+            ),
+            OpenError::PermissionDenied { interface } => {
+                write!(f, "Permission denied opening a CAN socket on {}", interface)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OpenError {}
+
+impl From<OpenError> for std::io::Error {
+    fn from(e: OpenError) -> Self {
+        let kind = match e {
+            OpenError::NoSuchInterface { .. } => std::io::ErrorKind::NotFound,
+            OpenError::NotCan { .. } => std::io::ErrorKind::InvalidInput,
+            OpenError::Down { .. } => std::io::ErrorKind::NetworkDown,
+            OpenError::PermissionDenied { .. } => std::io::ErrorKind::PermissionDenied,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
+/// A network namespace to open an interface in, see `LinuxCan::open_in()`
+#[derive(Clone, Copy, Debug)]
+pub enum NetNs<'a> {
+    /// A namespace created with `ip netns add`, found in /run/netns
+    Name(&'a str),
+    /// An open namespace file, e.g. /proc/<pid>/ns/net of a process in a container
+    Fd(BorrowedFd<'a>),
+}
+
+/// A classic CAN socket on a Linux interface
+///
+/// Received frames carry the kernel's receive timestamp in nanoseconds since the Unix epoch, taken
+/// from the system's realtime clock. Convert them with `clock::SystemClock`.
 pub struct LinuxCan {
-    socket: CanSocket,
+    socket: AsyncFd<CanSocket>,
     interface: String,
+    write_mode: WriteMode,
+    rx: RxState,
+    // Namespace the interface was opened in by `open_in()`
+    netns: Option<OwnedFd>,
+}
+
+// Receive side state filled by recv_frames
+#[derive(Default)]
+struct RxState {
+    // Frames received by the last recvmmsg call that have not been handed out yet
+    queue: VecDeque<CanFrame>,
+    // Last value of the kernel's cumulative socket drop counter (SO_RXQ_OVFL)
+    kernel_drops: u32,
+    // Drops that have not been reported to the reader yet
+    unreported_drops: u64,
+    total_drops: u64,
+    // Frames written in Confirmed mode whose echo has not been received yet
+    unconfirmed: u64,
+    strictness: Strictness,
+}
+
+impl RxState {
+    fn record_kernel_drops(&mut self, kernel_drops: u32) {
+        let new_drops = kernel_drops.wrapping_sub(self.kernel_drops) as u64;
+        self.kernel_drops = kernel_drops;
+        self.unreported_drops += new_drops;
+        self.total_drops += new_drops;
+    }
 }
 
 impl CanInterface for LinuxCan {
+    /// Open a SocketCAN interface
+    ///
+    /// Fails with an `OpenError` if the interface does not exist, is not a CAN interface, is down
+    /// or may not be opened.
     async fn open(interface: &str) -> std::io::Result<Self> {
+        Self::from_socket(interface, open_socket(interface)?)
+    }
+
+    /// Read a single CAN frame from the interface
+    ///
+    /// If the kernel dropped frames because the receive queue overflowed, an error wrapping
+    /// `FramesDropped` is returned once before the frames that followed the loss.
+    async fn read_frame(&mut self) -> std::io::Result<CanFrame> {
+        std::future::poll_fn(|cx| self.poll_read_frame(cx)).await
+    }
+
+    async fn read_frames(&mut self) -> std::io::Result<Vec<CanFrame>> {
+        let first = self.read_frame().await?;
+        Ok(std::iter::once(first)
+            .chain(self.rx.queue.drain(..))
+            .collect())
+    }
+
+    async fn write_frame(&mut self, frame: CanFrame) -> std::io::Result<()> {
+        let frame: socketcan::CanFrame = frame.into();
+        loop {
+            let result = self
+                .socket
+                .async_io(Interest::WRITABLE, |s| s.write_frame(&frame))
+                .await;
+            match result {
+                Err(e)
+                    if self.write_mode != WriteMode::FailFast
+                        && e.raw_os_error() == Some(libc::ENOBUFS) =>
+                {
+                    // SocketCAN does not signal writability when the qdisc is full, so poll
+                    tokio::time::sleep(TX_RETRY_INTERVAL).await;
+                }
+                Ok(()) if self.write_mode == WriteMode::Confirmed => {
+                    self.rx.unconfirmed += 1;
+                    return self.wait_confirmed().await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn write_frames(&mut self, frames: &[CanFrame]) -> std::io::Result<()> {
+        let raw_frames: Vec<libc::can_frame> = frames
+            .iter()
+            .map(|frame| {
+                let frame: socketcan::CanFrame = frame.clone().into();
+                // SAFETY: as_ptr points at the can_frame owned by `frame`, which is Copy
+                unsafe { *frame.as_ptr() }
+            })
+            .collect();
+
+        let mut sent = 0;
+        while sent < raw_frames.len() {
+            let batch = &raw_frames[sent..(sent + TX_BATCH_LEN).min(raw_frames.len())];
+            let result = self
+                .socket
+                .async_io(Interest::WRITABLE, |s| send_frames(s.as_raw_fd(), batch))
+                .await;
+            match result {
+                Ok(count) => {
+                    sent += count;
+                    if self.write_mode == WriteMode::Confirmed {
+                        self.rx.unconfirmed += count as u64;
+                    }
+                }
+                Err(e)
+                    if self.write_mode != WriteMode::FailFast
+                        && e.raw_os_error() == Some(libc::ENOBUFS) =>
+                {
+                    tokio::time::sleep(TX_RETRY_INTERVAL).await;
+                }
+                Err(e) => return Err(PartialWrite::wrap(sent, e)),
+            }
+        }
+        self.wait_confirmed().await
+    }
+
+    async fn get_bitrate(&mut self) -> std::io::Result<Option<u32>> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .bit_rate()
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })
+    }
+
+    async fn get_error_counters(&mut self) -> std::io::Result<Option<CanErrorCounters>> {
+        let counters = self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .berr_counter()
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })?;
+        Ok(counters.map(|c| CanErrorCounters {
+            tx_errors: c.txerr,
+            rx_errors: c.rxerr,
+        }))
+    }
+
+    async fn get_info(&mut self) -> std::io::Result<InterfaceInfo> {
+        let link = self.in_own_netns(|| {
+            lin_netlink::if_index(&self.interface).and_then(lin_netlink::query_link)
+        })?;
+        let link_info = lin_netlink::find_attr(&link.attrs, libc::IFLA_LINKINFO);
+        let can = link_info
+            .and_then(|info| lin_netlink::find_attr(info, libc::IFLA_INFO_DATA))
+            .unwrap_or_default();
+        let u32_attr =
+            |kind| lin_netlink::find_attr(can, kind).and_then(|a| lin_netlink::u32_at(a, 0));
+        let u32_list = |kind| {
+            lin_netlink::find_attr(can, kind)
+                .map(|a| {
+                    a.chunks_exact(4)
+                        .map(|c| u32::from_ne_bytes(c.try_into().unwrap()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        // Virtual devices such as vcan have no ethtool driver info, name them by their link kind
+        let driver = lin_netlink::driver_name(self.socket.as_raw_fd(), &self.interface)
+            .ok()
+            .or_else(|| {
+                let kind = lin_netlink::find_attr(link_info?, libc::IFLA_INFO_KIND)?;
+                Some(
+                    String::from_utf8_lossy(kind)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                )
+            });
+
+        Ok(InterfaceInfo {
+            driver,
+            clock_hz: u32_attr(IFLA_CAN_CLOCK),
+            bitrates: u32_list(IFLA_CAN_BITRATE_CONST),
+            data_bitrates: u32_list(IFLA_CAN_DATA_BITRATE_CONST),
+            max_bitrate: u32_attr(IFLA_CAN_BITRATE_MAX),
+            mtu: lin_netlink::find_attr(&link.attrs, libc::IFLA_MTU)
+                .and_then(|a| lin_netlink::u32_at(a, 0)),
+            terminations: lin_netlink::find_attr(can, IFLA_CAN_TERMINATION_CONST)
+                .map(|a| {
+                    a.chunks_exact(2)
+                        .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Returns the bytes waiting in the interface's queueing discipline
+    ///
+    /// CAN_RAW sockets do not report their own send queue, so this is the backlog of the
+    /// interface's root qdisc and includes frames written by other sockets. Interfaces without a
+    /// queue, such as vcan with its default `noqueue`, always report 0.
+    async fn tx_pending(&mut self) -> std::io::Result<usize> {
+        // The netlink queries block, and joining the namespace takes a thread of its own
+        let interface = self.interface.clone();
+        let netns = self.netns.as_ref().map(OwnedFd::try_clone).transpose()?;
+        let backlog = tokio::task::spawn_blocking(move || {
+            let query = || lin_netlink::if_index(&interface).and_then(lin_netlink::qdisc_backlog);
+            match netns {
+                Some(netns) => in_netns(netns.as_fd(), query),
+                None => query(),
+            }
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        Ok(backlog as usize)
+    }
+
+    /// Waits until the interface's queueing discipline is empty
+    ///
+    /// Like `tx_pending()` this covers the whole interface, so it does not return while other
+    /// sockets keep writing, or while frames cannot be sent because no other node acknowledges
+    /// them. Wrap it in `tokio::time::timeout` to bound the wait.
+    async fn flush(&mut self) -> std::io::Result<()> {
+        let mut interval = TX_RETRY_INTERVAL;
+        while self.tx_pending().await? > 0 {
+            tokio::time::sleep(interval).await;
+            interval = (interval * 2).min(FLUSH_POLL_MAX);
+        }
+        Ok(())
+    }
+}
+
+impl LinuxCan {
+    /// Open a SocketCAN interface that lives in the network namespace `netns`
+    ///
+    /// The socket is created on a short-lived thread that joins the namespace, which requires
+    /// CAP_SYS_ADMIN. Once open, the socket keeps working from any thread. The namespace is kept
+    /// open, and the configuration and statistics methods, such as `set_bit_timing()` and
+    /// `get_stats()`, join it the same way for each call.
+    pub async fn open_in(interface: &str, netns: NetNs<'_>) -> std::io::Result<Self> {
+        let netns = open_netns(netns)?;
+        let socket = in_netns(netns.as_fd(), || open_socket(interface))?;
+        let mut can = Self::from_socket(interface, socket)?;
+        can.netns = Some(netns);
+        Ok(can)
+    }
+
+    // Runs `f` in the namespace of the interface. Netlink sockets and interface names only refer
+    // to the right interface there
+    fn in_own_netns<R: Send>(
+        &self,
+        f: impl FnOnce() -> std::io::Result<R> + Send,
+    ) -> std::io::Result<R> {
+        match &self.netns {
+            Some(netns) => in_netns(netns.as_fd(), f),
+            None => f(),
+        }
+    }
+
+    fn from_socket(interface: &str, socket: CanSocket) -> std::io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        // Have the kernel attach its receive queue drop counter and receive time to every message
+        socket.set_socket_option(libc::SOL_SOCKET, libc::SO_RXQ_OVFL, &(1 as libc::c_int))?;
+        socket.set_socket_option(libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, &(1 as libc::c_int))?;
+
         Ok(LinuxCan {
-            socket: CanSocket::open(interface)?,
+            socket: AsyncFd::new(socket)?,
             interface: interface.to_string(),
+            write_mode: WriteMode::default(),
+            rx: RxState::default(),
+            netns: None,
         })
     }
 
-    async fn read_frame(&mut self) -> std::io::Result<CanFrame> {
-        match self.socket.read_frame().await {
-            Ok(frame) => Ok(frame.into()),
-            Err(e) => Err(e),
+    /// Attempts to read a frame without an async fn
+    ///
+    /// Returns `Poll::Pending` and registers the waker in `cx` when no frame is available. This
+    /// is the building block for custom executors and hand written futures; combine it with the
+    /// `AsRawFd` impl to integrate CAN readiness into an existing poll loop.
+    pub fn poll_read_frame(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<CanFrame>> {
+        loop {
+            if self.rx.unreported_drops > 0 {
+                let dropped = std::mem::take(&mut self.rx.unreported_drops);
+                return Poll::Ready(Err(std::io::Error::other(FramesDropped(dropped))));
+            }
+            if let Some(frame) = self.rx.queue.pop_front() {
+                return Poll::Ready(Ok(frame));
+            }
+
+            let rx = &mut self.rx;
+            let mut guard = ready!(self.socket.poll_read_ready(cx))?;
+            match guard.try_io(|s| recv_frames(s.as_raw_fd(), rx)) {
+                Ok(result) => result?,
+                // Readiness was stale and has been cleared, poll again to register the waker
+                Err(_would_block) => continue,
+            }
         }
     }
 
-    async fn write_frame(&mut self, frame: CanFrame) -> std::io::Result<()> {
-        self.socket.write_frame(frame.into()).await
+    /// Returns the total number of frames the kernel dropped from this socket's receive queue
+    pub fn dropped_frames(&self) -> u64 {
+        self.rx.total_drops
     }
 
-    async fn get_bitrate(&mut self) -> std::io::Result<Option<u32>> {
-        let iface = nl::CanInterface::open(&self.interface)?;
+    /// Returns the current write mode
+    pub fn write_mode(&self) -> WriteMode {
+        self.write_mode
+    }
 
+    /// Sets how `write_frame` behaves when the kernel transmit queue is full
+    ///
+    /// `WriteMode::Confirmed` enables CAN_RAW_RECV_OWN_MSGS on the socket, the other modes
+    /// disable it again. The echoed frames are consumed internally and never returned by reads.
+    pub fn set_write_mode(&mut self, mode: WriteMode) -> std::io::Result<()> {
+        let recv_own = (mode == WriteMode::Confirmed) as libc::c_int;
+        self.set_raw_option(libc::SOL_CAN_RAW, libc::CAN_RAW_RECV_OWN_MSGS, &recv_own)?;
+        self.write_mode = mode;
+        Ok(())
+    }
+
+    /// Returns how received frames are validated
+    pub fn strictness(&self) -> Strictness {
+        self.rx.strictness
+    }
+
+    /// Sets how received frames are validated. Defaults to `Strictness::Strict`
+    ///
+    /// With `Strictness::Preserve`, a DLC of 9 to 15 is kept in `CanFrame::len8_dlc()`. The kernel
+    /// only reports it if the interface has the `cc-len8-dlc` control mode enabled
+    /// (`ip link set can0 type can cc-len8-dlc on`), which also makes it send `len8_dlc` of
+    /// written frames.
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.rx.strictness = strictness;
+    }
+
+    // Receives until every frame written in Confirmed mode has been echoed back. Frames from
+    // other nodes received meanwhile are queued for the reader.
+    async fn wait_confirmed(&mut self) -> std::io::Result<()> {
+        while self.rx.unconfirmed > 0 {
+            let rx = &mut self.rx;
+            self.socket
+                .async_io(Interest::READABLE, |s| recv_frames(s.as_raw_fd(), rx))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the underlying socketcan socket, for socket options that LinuxCan does not wrap
+    ///
+    /// Options that change the frame layout delivered by the kernel (e.g. CAN_RAW_FD_FRAMES) will
+    /// break `read_frame`, which expects classic frames.
+    pub fn socket_ref(&self) -> &CanSocket {
+        self.socket.get_ref()
+    }
+
+    /// Sets a raw socket option (setsockopt) on the underlying socket
+    ///
+    /// `level` and `name` are the libc constants, e.g. `libc::SOL_CAN_RAW` and `libc::CAN_RAW_JOIN_FILTERS`.
+    pub fn set_raw_option<T>(&self, level: i32, name: i32, value: &T) -> std::io::Result<()> {
+        self.socket.get_ref().set_socket_option(level, name, value)
+    }
+
+    /// Sets the socket receive buffer size and returns the size granted by the kernel
+    ///
+    /// Tries SO_RCVBUFFORCE first, which may exceed net.core.rmem_max but requires CAP_NET_ADMIN,
+    /// and falls back to SO_RCVBUF. The kernel doubles the value for bookkeeping overhead and
+    /// reports the doubled size.
+    pub fn set_receive_buffer(&self, bytes: usize) -> std::io::Result<usize> {
+        let size = bytes.min(libc::c_int::MAX as usize / 2) as libc::c_int;
+        let socket = self.socket.get_ref();
+        if socket
+            .set_socket_option(libc::SOL_SOCKET, libc::SO_RCVBUFFORCE, &size)
+            .is_err()
+        {
+            socket.set_socket_option(libc::SOL_SOCKET, libc::SO_RCVBUF, &size)?;
+        }
+
+        let mut granted: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: granted and len are valid for writes of the sizes given
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVBUF,
+                (&mut granted as *mut libc::c_int).cast(),
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(granted.max(0) as usize)
+    }
+
+    /// Returns the detailed bit-timing parameters. Returns None if the interface has no bit-timing (e.g. vcan)
+    pub fn get_bit_timing(&self) -> std::io::Result<Option<BitTiming>> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .bit_timing()
+                .map(|bt| bt.map(BitTiming::from))
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })
+    }
+
+    /// Sets the bit-timing parameters. Either the bitrate or the individual segments must be given;
+    /// a non-zero bitrate takes precedence over the segments (see `BitTiming`).
+    ///
+    /// Requires CAP_NET_ADMIN and the interface must be down.
+    pub fn set_bit_timing(&self, timing: BitTiming) -> std::io::Result<()> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .set_bit_timing(timing.into())
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })
+    }
+
+    /// Returns the CAN controller clock frequency in Hz
+    pub fn get_clock_frequency(&self) -> std::io::Result<Option<u32>> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .clock()
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })
+    }
+
+    /// Returns the termination resistance in ohms. Returns None if the hardware has no switchable termination
+    pub fn get_termination(&self) -> std::io::Result<Option<u16>> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .termination()
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })
+    }
+
+    /// Sets the termination resistance in ohms. Most hardware only supports 0 (off) and 120.
+    ///
+    /// Requires CAP_NET_ADMIN.
+    pub fn set_termination(&self, ohms: u16) -> std::io::Result<()> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            iface
+                .set_termination(ohms)
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        })
+    }
+
+    /// Returns the kernel's interface counters (packets, bytes, drops, bus errors, restarts)
+    pub fn get_stats(&self) -> std::io::Result<InterfaceStats> {
+        self.in_own_netns(|| {
+            let link = lin_netlink::query_link(lin_netlink::if_index(&self.interface)?)?.attrs;
+
+            let mut stats = InterfaceStats::default();
+            if let Some(s) = lin_netlink::find_attr(&link, libc::IFLA_STATS64) {
+                let counter = |i| lin_netlink::u64_at(s, i).unwrap_or(0);
+                stats.rx_packets = counter(0);
+                stats.tx_packets = counter(1);
+                stats.rx_bytes = counter(2);
+                stats.tx_bytes = counter(3);
+                stats.rx_errors = counter(4);
+                stats.tx_errors = counter(5);
+                stats.rx_dropped = counter(6);
+                stats.tx_dropped = counter(7);
+            }
+
+            stats.can = lin_netlink::find_attr(&link, libc::IFLA_LINKINFO)
+                .and_then(|info| lin_netlink::find_attr(info, libc::IFLA_INFO_XSTATS))
+                .map(|x| {
+                    let counter = |i| lin_netlink::u32_at(x, i).unwrap_or(0);
+                    CanDeviceStats {
+                        bus_errors: counter(0),
+                        error_warning: counter(1),
+                        error_passive: counter(2),
+                        bus_off: counter(3),
+                        arbitration_lost: counter(4),
+                        restarts: counter(5),
+                    }
+                });
+
+            Ok(stats)
+        })
+    }
+
+    /// Returns the controller error state. Interfaces without a controller (e.g. vcan) always
+    /// report `BusState::ErrorActive`
+    pub fn get_bus_state(&self) -> std::io::Result<BusState> {
+        self.in_own_netns(|| {
+            let iface = nl::CanInterface::open(&self.interface)?;
+
+            let state = iface.state().map_err(nl_error)?;
+            Ok(state.map_or(BusState::ErrorActive, BusState::from))
+        })
+    }
+
+    /// Watches the controller error state
+    ///
+    /// Opens a separate socket that only receives controller error frames and updates the
+    /// returned channel as soon as the kernel reports a transition (warning, error passive,
+    /// bus-off, restart), independently of whether this interface is being read. The watcher
+    /// stops once every receiver has been dropped. Must be called from within a tokio runtime.
+    pub fn watch_bus_state(&self) -> std::io::Result<watch::Receiver<BusState>> {
+        let socket = self.in_own_netns(|| {
+            let socket = CanSocket::open(&self.interface)?;
+            socket.set_nonblocking(true)?;
+            socket.set_filter_drop_all()?;
+            socket.set_error_filter(
+                libc::CAN_ERR_CRTL | libc::CAN_ERR_BUSOFF | libc::CAN_ERR_RESTARTED,
+            )?;
+            Ok(socket)
+        })?;
+        let socket = AsyncFd::new(socket)?;
+
+        let (tx, rx) = watch::channel(self.get_bus_state()?);
+        tokio::spawn(async move {
+            loop {
+                let frame = tokio::select! {
+                    _ = tx.closed() => return,
+                    frame = socket.async_io(Interest::READABLE, |s| s.read_frame()) => frame,
+                };
+                let state = match frame {
+                    Ok(socketcan::CanFrame::Error(frame)) => bus_state_from_error(&frame),
+                    Ok(_) => None,
+                    // The interface went away
+                    Err(_) => return,
+                };
+                if let Some(state) = state {
+                    tx.send_if_modified(|current| std::mem::replace(current, state) != state);
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    /// Detects the bus bitrate by listening at each of `candidates` in turn
+    ///
+    /// For every candidate the interface is taken down, switched to listen-only mode so it never
+    /// acknowledges or disturbs traffic, set to the bitrate and brought back up. A bitrate is
+    /// accepted if at least one frame and no error frame is received within `dwell`. Afterwards
+    /// the original bit timing, listen-only setting and up/down state are restored, so the caller
+    /// applies the detected rate itself. Returns None if no candidate matched, e.g. on a silent bus.
+    ///
+    /// Requires CAP_NET_ADMIN and a controller that supports listen-only mode.
+    pub async fn detect_bitrate(
+        interface: &str,
+        candidates: &[u32],
+        dwell: Duration,
+    ) -> std::io::Result<Option<u32>> {
+        let iface = nl::CanInterface::open(interface)?;
+        let details = iface.details().map_err(nl_error)?;
+
+        let mut detected = Ok(None);
+        for &bitrate in candidates {
+            match probe_bitrate(&iface, interface, bitrate, dwell).await {
+                Ok(false) => continue,
+                Ok(true) => detected = Ok(Some(bitrate)),
+                Err(e) => detected = Err(e),
+            }
+            break;
+        }
+
+        let restored = restore_interface(&iface, &details);
+
+        let detected = detected?;
+        restored?;
+        Ok(detected)
+    }
+}
+
+impl From<nl::CanState> for BusState {
+    fn from(state: nl::CanState) -> Self {
+        match state {
+            nl::CanState::ErrorActive => BusState::ErrorActive,
+            nl::CanState::ErrorWarning => BusState::ErrorWarning,
+            nl::CanState::ErrorPassive => BusState::ErrorPassive,
+            nl::CanState::BusOff => BusState::BusOff,
+            nl::CanState::Stopped | nl::CanState::Sleeping => BusState::Stopped,
+        }
+    }
+}
+
+// Decodes the state transition reported by a controller error frame, see linux/can/error.h
+fn bus_state_from_error(frame: &socketcan::CanErrorFrame) -> Option<BusState> {
+    use socketcan::EmbeddedFrame;
+
+    let class = frame.error_bits();
+    if class & libc::CAN_ERR_BUSOFF != 0 {
+        return Some(BusState::BusOff);
+    }
+    if class & libc::CAN_ERR_CRTL != 0 {
+        let detail = frame.data().get(1).copied().unwrap_or(0) as libc::c_int;
+        if detail & (libc::CAN_ERR_CRTL_RX_PASSIVE | libc::CAN_ERR_CRTL_TX_PASSIVE) != 0 {
+            return Some(BusState::ErrorPassive);
+        }
+        if detail & (libc::CAN_ERR_CRTL_RX_WARNING | libc::CAN_ERR_CRTL_TX_WARNING) != 0 {
+            return Some(BusState::ErrorWarning);
+        }
+        if detail & libc::CAN_ERR_CRTL_ACTIVE != 0 {
+            return Some(BusState::ErrorActive);
+        }
+    }
+    if class & libc::CAN_ERR_RESTARTED != 0 {
+        return Some(BusState::ErrorActive);
+    }
+    None
+}
+
+// Restores the bit timing, listen-only mode and up state saved in `details`
+fn restore_interface(
+    iface: &nl::CanInterface,
+    details: &nl::InterfaceDetails,
+) -> std::io::Result<()> {
+    let listen_only = details
+        .can
+        .ctrl_mode
+        .is_some_and(|m| m.has_mode(nl::CanCtrlMode::ListenOnly));
+
+    iface.bring_down().map_err(nl_error)?;
+    iface
+        .set_ctrlmode(nl::CanCtrlMode::ListenOnly, listen_only)
+        .map_err(nl_error)?;
+    // The read back timing has both the bitrate and the segments set, which the kernel rejects
+    if let Some(timing) = details.can.bit_timing.filter(|t| t.bitrate != 0) {
         iface
-            .bit_rate()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+            .set_bitrate(timing.bitrate, Some(timing.sample_point))
+            .map_err(nl_error)?;
+    }
+    if details.is_up {
+        iface.bring_up().map_err(nl_error)?;
+    }
+    Ok(())
+}
+
+// Opens a CAN socket on `interface` and checks that the interface is usable
+fn open_socket(interface: &str) -> std::io::Result<CanSocket> {
+    let socket = CanSocket::open(interface).map_err(|e| diagnose_open(interface, e))?;
+    check_link_up(interface)?;
+    Ok(socket)
+}
+
+// Opens the namespace file of `netns`
+fn open_netns(netns: NetNs<'_>) -> std::io::Result<OwnedFd> {
+    match netns {
+        NetNs::Name(name) => {
+            if name.is_empty() || name.contains('/') {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Invalid network namespace name {:?}", name),
+                ));
+            }
+            let path = std::path::Path::new("/run/netns").join(name);
+            let file = std::fs::File::open(&path).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("Cannot open network namespace {}: {}", path.display(), e),
+                )
+            })?;
+            Ok(file.into())
+        }
+        NetNs::Fd(fd) => fd.try_clone_to_owned(),
+    }
+}
+
+// Runs `f` on a new thread that joined the network namespace `netns`. Sockets created by `f` stay
+// in that namespace.
+fn in_netns<R: Send>(
+    netns: BorrowedFd<'_>,
+    f: impl FnOnce() -> std::io::Result<R> + Send,
+) -> std::io::Result<R> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                // SAFETY: setns only changes the namespace of this thread, which ends with `f`
+                if unsafe { libc::setns(netns.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+                    let e = std::io::Error::last_os_error();
+                    return Err(match e.raw_os_error() {
+                        Some(libc::EPERM) => std::io::Error::new(
+                            std::io::ErrorKind::PermissionDenied,
+                            "Joining a network namespace requires CAP_SYS_ADMIN",
+                        ),
+                        Some(libc::EINVAL) => std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "The file descriptor is not a network namespace",
+                        ),
+                        _ => e,
+                    });
+                }
+                f()
+            })
+            .join()
+            .unwrap_or_else(|_| Err(std::io::Error::other("Namespace thread panicked")))
+    })
+}
+
+// Explains why a CAN socket could not be opened on `interface`, or returns `e` if it cannot tell
+fn diagnose_open(interface: &str, e: std::io::Error) -> std::io::Error {
+    let interface = interface.to_string();
+    if matches!(e.raw_os_error(), Some(libc::EACCES | libc::EPERM)) {
+        return OpenError::PermissionDenied { interface }.into();
+    }
+    let Ok(index) = lin_netlink::if_index(&interface) else {
+        return OpenError::NoSuchInterface {
+            interface,
+            available: can_interfaces(),
+        }
+        .into();
+    };
+    match lin_netlink::query_link(index) {
+        Ok(link) if link.kind != libc::ARPHRD_CAN => OpenError::NotCan {
+            interface,
+            link_type: link.kind,
+        }
+        .into(),
+        _ => e,
+    }
+}
+
+// Fails with OpenError::Down if `interface` is administratively down. Binding a CAN socket succeeds
+// on a down interface, but nothing is ever received and every write fails with ENETDOWN.
+fn check_link_up(interface: &str) -> std::io::Result<()> {
+    // The socket is usable even if the link cannot be queried, so only a positive result fails
+    let Ok(link) = lin_netlink::if_index(interface).and_then(lin_netlink::query_link) else {
+        return Ok(());
+    };
+    if link.flags & libc::IFF_UP as u32 != 0 {
+        return Ok(());
+    }
+    let oper_state = lin_netlink::find_attr(&link.attrs, libc::IFLA_OPERSTATE)
+        .and_then(|state| state.first())
+        .map_or("UNKNOWN", |&state| match state {
+            1 => "NOTPRESENT",
+            2 => "DOWN",
+            3 => "LOWERLAYERDOWN",
+            4 => "TESTING",
+            5 => "DORMANT",
+            6 => "UP",
+            _ => "UNKNOWN",
+        });
+    Err(OpenError::Down {
+        interface: interface.to_string(),
+        state: LinkState {
+            up: false,
+            running: link.flags & libc::IFF_RUNNING as u32 != 0,
+            oper_state,
+        },
+    }
+    .into())
+}
+
+/// Returns the names of the CAN interfaces in the current network namespace, sorted
+pub fn can_interfaces() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| {
+            std::fs::read_to_string(entry.path().join("type"))
+                .is_ok_and(|kind| kind.trim() == libc::ARPHRD_CAN.to_string())
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+fn nl_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+// Listens in listen-only mode at `bitrate` for `dwell` and reports whether frames were received
+// without errors
+async fn probe_bitrate(
+    iface: &nl::CanInterface,
+    interface: &str,
+    bitrate: u32,
+    dwell: Duration,
+) -> std::io::Result<bool> {
+    iface.bring_down().map_err(nl_error)?;
+    iface
+        .set_ctrlmode(nl::CanCtrlMode::ListenOnly, true)
+        .map_err(nl_error)?;
+    iface.set_bitrate(bitrate, None).map_err(nl_error)?;
+    iface.bring_up().map_err(nl_error)?;
+
+    let mut can = LinuxCan::open(interface).await?;
+    can.socket_ref().set_error_filter_accept_all()?;
+
+    let deadline = tokio::time::Instant::now() + dwell;
+    let mut received = false;
+    loop {
+        match tokio::time::timeout_at(deadline, can.read_frame()).await {
+            Err(_) => return Ok(received),
+            Ok(Ok(frame)) if frame.is_error() => return Ok(false),
+            Ok(Ok(_)) => received = true,
+            // Frames were lost, but those that arrived were valid
+            Ok(Err(e)) if e.get_ref().is_some_and(|e| e.is::<FramesDropped>()) => received = true,
+            Ok(Err(e)) => return Err(e),
+        }
+    }
+}
+
+impl AsRawFd for LinuxCan {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+// Receives up to RX_BATCH_LEN classic frames with a single recvmmsg call
+fn recv_frames(fd: RawFd, rx: &mut RxState) -> std::io::Result<()> {
+    // SAFETY: can_frame, iovec and mmsghdr are plain C structs for which all-zero is a valid value
+    let mut frames: [libc::can_frame; RX_BATCH_LEN] = unsafe { std::mem::zeroed() };
+    let mut iovecs: [libc::iovec; RX_BATCH_LEN] = unsafe { std::mem::zeroed() };
+    let mut msgs: [libc::mmsghdr; RX_BATCH_LEN] = unsafe { std::mem::zeroed() };
+    // Room for the SO_RXQ_OVFL and SO_TIMESTAMPNS control messages of each frame (u64 for cmsghdr
+    // alignment)
+    let mut controls = [[0u64; 8]; RX_BATCH_LEN];
+
+    for (((frame, iov), msg), control) in frames
+        .iter_mut()
+        .zip(iovecs.iter_mut())
+        .zip(msgs.iter_mut())
+        .zip(controls.iter_mut())
+    {
+        iov.iov_base = (frame as *mut libc::can_frame).cast();
+        iov.iov_len = std::mem::size_of::<libc::can_frame>();
+        msg.msg_hdr.msg_iov = iov;
+        msg.msg_hdr.msg_iovlen = 1;
+        msg.msg_hdr.msg_control = control.as_mut_ptr().cast();
+        msg.msg_hdr.msg_controllen = std::mem::size_of_val(control) as _;
+    }
+
+    // SAFETY: every message points at a valid iovec which points at a can_frame sized buffer. The
+    // socket is non-blocking, so a missing timeout is fine.
+    let count = unsafe {
+        libc::recvmmsg(
+            fd,
+            msgs.as_mut_ptr(),
+            RX_BATCH_LEN as libc::c_uint,
+            0,
+            std::ptr::null_mut(),
+        )
+    };
+    if count < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut received = false;
+    for (frame, msg) in frames.iter().zip(msgs.iter()).take(count as usize) {
+        let mut timestamp = None;
+        // SAFETY: msg_control/msg_controllen were set up above and updated by the kernel
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg.msg_hdr) };
+        while !cmsg.is_null() {
+            // SAFETY: cmsg is non-null and points into this message's control buffer
+            let header = unsafe { &*cmsg };
+            if header.cmsg_level == libc::SOL_SOCKET && header.cmsg_type == libc::SO_RXQ_OVFL {
+                // SAFETY: the SO_RXQ_OVFL payload is a single u32
+                let drops =
+                    unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<u32>()) };
+                rx.record_kernel_drops(drops);
+            } else if header.cmsg_level == libc::SOL_SOCKET
+                && header.cmsg_type == libc::SCM_TIMESTAMPNS
+            {
+                // SAFETY: the SCM_TIMESTAMPNS payload is a struct timespec
+                let ts = unsafe {
+                    std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<libc::timespec>())
+                };
+                // time_t and c_long are narrower than u64 on 32-bit targets
+                #[allow(clippy::unnecessary_cast)]
+                let nanos = ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64;
+                timestamp = Some(nanos);
+            }
+            // SAFETY: both pointers belong to the same message
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg.msg_hdr, cmsg) };
+        }
+
+        // The kernel only ever delivers complete can_frames on a classic raw socket
+        if msg.msg_len as usize != std::mem::size_of::<libc::can_frame>() {
+            continue;
+        }
+        received = true;
+        if msg.msg_hdr.msg_flags & libc::MSG_CONFIRM != 0 {
+            // Echo of a frame sent by this socket, only delivered in WriteMode::Confirmed
+            rx.unconfirmed = rx.unconfirmed.saturating_sub(1);
+        } else {
+            let mut received: CanFrame = socketcan::CanFrame::from(*frame).into();
+            received.set_timestamp(timestamp);
+            if rx.strictness == Strictness::Preserve && frame.can_dlc == 8 {
+                // Only set by the kernel with the cc-len8-dlc control mode
+                let _ = received.set_len8_dlc(Some(frame.len8_dlc));
+            }
+            rx.queue.push_back(received);
+        }
+    }
+    if !received && rx.unreported_drops == 0 {
+        // Only truncated messages were received, wait for more data
+        return Err(std::io::ErrorKind::WouldBlock.into());
+    }
+    Ok(())
+}
+
+// Sends classic frames with a single sendmmsg call. Returns how many were accepted by the kernel.
+fn send_frames(fd: RawFd, frames: &[libc::can_frame]) -> std::io::Result<usize> {
+    let mut iovecs: Vec<libc::iovec> = frames
+        .iter()
+        .map(|frame| libc::iovec {
+            iov_base: (frame as *const libc::can_frame).cast_mut().cast(),
+            iov_len: std::mem::size_of::<libc::can_frame>(),
+        })
+        .collect();
+    let mut msgs: Vec<libc::mmsghdr> = iovecs
+        .iter_mut()
+        .map(|iov| {
+            // SAFETY: mmsghdr is a plain C struct for which all-zero is a valid value
+            let mut msg: libc::mmsghdr = unsafe { std::mem::zeroed() };
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+            msg
+        })
+        .collect();
+
+    // SAFETY: every message points at a valid iovec which points at a can_frame. The kernel only
+    // reads from the frame buffers.
+    let count = unsafe { libc::sendmmsg(fd, msgs.as_mut_ptr(), msgs.len() as libc::c_uint, 0) };
+    if count < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
+/// A CAN XL socket on a Linux interface
+///
+/// Requires kernel support for CAN XL (Linux 6.2+) and an XL capable interface. Classic and FD
+/// frames arriving on the interface are skipped; use `LinuxCan` to receive those.
+pub struct LinuxCanXl {
+    socket: AsyncFd<socketcan::CanSocket>,
+}
+
+impl LinuxCanXl {
+    /// Opens a CAN XL socket on the interface
+    pub fn open(interface: &str) -> std::io::Result<Self> {
+        let socket =
+            socketcan::CanSocket::open(interface).map_err(|e| diagnose_open(interface, e))?;
+        socket
+            .set_socket_option(
+                libc::SOL_CAN_RAW,
+                libc::CAN_RAW_XL_FRAMES,
+                &(1 as libc::c_int),
+            )
+            .map_err(|e| match e.raw_os_error() {
+                Some(libc::ENOPROTOOPT) => std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "The running kernel does not support CAN XL frames",
+                ),
+                _ => e,
+            })?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket: AsyncFd::new(socket)?,
+        })
+    }
+
+    /// Read a single CAN XL frame from the interface
+    pub async fn read_frame(&mut self) -> std::io::Result<CanXlFrame> {
+        let mut buf = [0u8; libc::CANXL_MTU];
+        loop {
+            let len = self
+                .socket
+                .async_io(Interest::READABLE, |s| s.as_raw_socket().read(&mut buf))
+                .await?;
+
+            // Only XL frames carry the XLF flag, anything else is a classic or FD frame
+            if len < libc::CANXL_HDR_SIZE || buf[4] & libc::CANXL_XLF as u8 == 0 {
+                continue;
+            }
+
+            let prio = u32::from_ne_bytes(buf[0..4].try_into().unwrap()) & libc::CANXL_PRIO_MASK;
+            let data_len = u16::from_ne_bytes(buf[6..8].try_into().unwrap()) as usize;
+            let af = u32::from_ne_bytes(buf[8..12].try_into().unwrap());
+            let data_end = (libc::CANXL_HDR_SIZE + data_len).min(len);
+
+            let mut frame = CanXlFrame::new(
+                prio as u16,
+                buf[5],
+                af,
+                &buf[libc::CANXL_HDR_SIZE..data_end],
+            )
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            frame.set_sec(buf[4] & libc::CANXL_SEC as u8 != 0);
+            return Ok(frame);
+        }
+    }
+
+    /// Write a single CAN XL frame to the interface
+    pub async fn write_frame(&mut self, frame: CanXlFrame) -> std::io::Result<()> {
+        let mut flags = libc::CANXL_XLF as u8;
+        if frame.is_sec() {
+            flags |= libc::CANXL_SEC as u8;
+        }
+
+        let mut buf = Vec::with_capacity(libc::CANXL_HDR_SIZE + frame.data().len());
+        buf.extend_from_slice(&(frame.priority() as u32).to_ne_bytes());
+        buf.push(flags);
+        buf.push(frame.sdu_type());
+        buf.extend_from_slice(&(frame.data().len() as u16).to_ne_bytes());
+        buf.extend_from_slice(&frame.acceptance_field().to_ne_bytes());
+        buf.extend_from_slice(frame.data());
+
+        let written = self
+            .socket
+            .async_io(Interest::WRITABLE, |s| s.as_raw_socket().write(&buf))
+            .await?;
+        if written != buf.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WriteZero,
+                "CAN XL frame was only partially written",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_timing_with_bitrate_drops_segments() {
+        let read_back = BitTiming {
+            bitrate: 500_000,
+            sample_point: 875,
+            tq: 125,
+            prop_seg: 6,
+            phase_seg1: 7,
+            phase_seg2: 2,
+            sjw: 1,
+            brp: 10,
+        };
+        let timing = nl::CanBitTiming::from(read_back);
+        assert_eq!(
+            (timing.bitrate, timing.sample_point, timing.tq, timing.brp),
+            (500_000, 875, 0, 0)
+        );
+
+        let segments = nl::CanBitTiming::from(BitTiming {
+            bitrate: 0,
+            ..read_back
+        });
+        assert_eq!((segments.bitrate, segments.tq, segments.brp), (0, 125, 10));
     }
 }
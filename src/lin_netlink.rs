@@ -0,0 +1,245 @@
+///
+/// lin_netlink.rs
+///
+/// Minimal rtnetlink and ethtool queries for link attributes that the socketcan crate does not expose.
+///
+use std::ffi::CString;
+use std::io::{Error as IoError, ErrorKind};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+const NLMSG_HDR_LEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const TCMSG_LEN: usize = 20;
+// TC_H_ROOT, the parent handle of an interface's root qdisc
+const TC_H_ROOT: u32 = 0xFFFF_FFFF;
+// TCA_STATS2 nests TCA_STATS_QUEUE, a struct gnet_stats_queue (qlen, backlog, drops, ...)
+const TCA_STATS2: u16 = 7;
+const TCA_STATS_QUEUE: u16 = 3;
+const RECV_BUF_LEN: usize = 32 * 1024;
+// ETHTOOL_GDRVINFO from linux/ethtool.h, and the size of its struct ethtool_drvinfo
+const ETHTOOL_GDRVINFO: u32 = 0x03;
+const ETHTOOL_DRVINFO_LEN: usize = 196;
+const ETHTOOL_DRIVER_LEN: usize = 32;
+
+/// Returns the kernel interface index for `name`
+pub(crate) fn if_index(name: &str) -> std::io::Result<u32> {
+    let c_name = CString::new(name).map_err(|_| {
+        IoError::new(
+            ErrorKind::InvalidInput,
+            "Interface name contains a NUL byte",
+        )
+    })?;
+    // SAFETY: c_name is a valid NUL terminated string
+    let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if index == 0 {
+        return Err(IoError::last_os_error());
+    }
+    Ok(index)
+}
+
+/// Link of an interface as reported by RTM_GETLINK
+pub(crate) struct Link {
+    /// Hardware type, ARPHRD_*
+    pub kind: u16,
+    /// Device flags, IFF_*
+    pub flags: u32,
+    /// Raw link attributes (IFLA_*), to be walked with `attrs()`
+    pub attrs: Vec<u8>,
+}
+
+fn route_socket() -> std::io::Result<OwnedFd> {
+    // SAFETY: plain socket(2) call, ownership of the returned descriptor is taken immediately
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(IoError::last_os_error());
+    }
+    // SAFETY: fd is a freshly created, valid descriptor
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Queries the kernel for the link of the interface with `index`
+pub(crate) fn query_link(index: u32) -> std::io::Result<Link> {
+    let fd = route_socket()?;
+
+    const REQ_LEN: usize = NLMSG_HDR_LEN + IFINFOMSG_LEN;
+    let mut req = [0u8; REQ_LEN];
+    req[0..4].copy_from_slice(&(REQ_LEN as u32).to_ne_bytes());
+    req[4..6].copy_from_slice(&libc::RTM_GETLINK.to_ne_bytes());
+    req[6..8].copy_from_slice(&(libc::NLM_F_REQUEST as u16).to_ne_bytes());
+    req[NLMSG_HDR_LEN] = libc::AF_UNSPEC as u8;
+    req[NLMSG_HDR_LEN + 4..NLMSG_HDR_LEN + 8].copy_from_slice(&(index as i32).to_ne_bytes());
+
+    // SAFETY: req is a valid buffer of the given length. The kernel is the default destination.
+    let sent = unsafe { libc::send(fd.as_raw_fd(), req.as_ptr().cast(), req.len(), 0) };
+    if sent < 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    let mut buf = vec![0u8; RECV_BUF_LEN];
+    // SAFETY: buf is a valid, writable buffer of the given length
+    let len = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+    if len < 0 {
+        return Err(IoError::last_os_error());
+    }
+    buf.truncate(len as usize);
+
+    if buf.len() < NLMSG_HDR_LEN {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            "Truncated netlink reply",
+        ));
+    }
+    let msg_len = (u32::from_ne_bytes(buf[0..4].try_into().unwrap()) as usize).min(buf.len());
+    let msg_type = u16::from_ne_bytes(buf[4..6].try_into().unwrap());
+
+    if msg_type == libc::NLMSG_ERROR as u16 {
+        let code = buf
+            .get(NLMSG_HDR_LEN..NLMSG_HDR_LEN + 4)
+            .map(|b| i32::from_ne_bytes(b.try_into().unwrap()))
+            .unwrap_or(-libc::EIO);
+        return Err(IoError::from_raw_os_error(-code));
+    }
+    if msg_type != libc::RTM_NEWLINK || msg_len < NLMSG_HDR_LEN + IFINFOMSG_LEN {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            "Unexpected netlink reply to link query",
+        ));
+    }
+
+    let info = &buf[NLMSG_HDR_LEN..NLMSG_HDR_LEN + IFINFOMSG_LEN];
+    Ok(Link {
+        kind: u16::from_ne_bytes([info[2], info[3]]),
+        flags: u32::from_ne_bytes(info[8..12].try_into().unwrap()),
+        attrs: buf[NLMSG_HDR_LEN + IFINFOMSG_LEN..msg_len].to_vec(),
+    })
+}
+
+/// Returns the bytes queued in the root qdisc of the interface with `index`, 0 if it has none
+///
+/// These are frames written by any socket that the driver has not taken yet.
+pub(crate) fn qdisc_backlog(index: u32) -> std::io::Result<u32> {
+    let fd = route_socket()?;
+
+    const REQ_LEN: usize = NLMSG_HDR_LEN + TCMSG_LEN;
+    let mut req = [0u8; REQ_LEN];
+    req[0..4].copy_from_slice(&(REQ_LEN as u32).to_ne_bytes());
+    req[4..6].copy_from_slice(&libc::RTM_GETQDISC.to_ne_bytes());
+    req[6..8].copy_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+    req[NLMSG_HDR_LEN] = libc::AF_UNSPEC as u8;
+    req[NLMSG_HDR_LEN + 4..NLMSG_HDR_LEN + 8].copy_from_slice(&(index as i32).to_ne_bytes());
+
+    // SAFETY: req is a valid buffer of the given length. The kernel is the default destination.
+    if unsafe { libc::send(fd.as_raw_fd(), req.as_ptr().cast(), req.len(), 0) } < 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    // The dump may span several datagrams and ends with NLMSG_DONE
+    let mut backlog = 0;
+    let mut buf = vec![0u8; RECV_BUF_LEN];
+    loop {
+        // SAFETY: buf is a valid, writable buffer of the given length
+        let len = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if len < 0 {
+            return Err(IoError::last_os_error());
+        }
+        let mut msgs = &buf[..len as usize];
+        while msgs.len() >= NLMSG_HDR_LEN {
+            let msg_len = (u32::from_ne_bytes(msgs[0..4].try_into().unwrap()) as usize)
+                .clamp(NLMSG_HDR_LEN, msgs.len());
+            let msg_type = u16::from_ne_bytes(msgs[4..6].try_into().unwrap());
+            let msg = &msgs[NLMSG_HDR_LEN..msg_len];
+            msgs = &msgs[((msg_len + 3) & !3).min(msgs.len())..];
+
+            match msg_type as i32 {
+                libc::NLMSG_DONE => return Ok(backlog),
+                libc::NLMSG_ERROR => {
+                    let code = msg
+                        .get(0..4)
+                        .map(|b| i32::from_ne_bytes(b.try_into().unwrap()))
+                        .unwrap_or(-libc::EIO);
+                    return Err(IoError::from_raw_os_error(-code));
+                }
+                _ if msg_type == libc::RTM_NEWQDISC && msg.len() >= TCMSG_LEN => {
+                    let qdisc_index = u32_at(msg, 1);
+                    let parent = u32_at(msg, 3);
+                    if qdisc_index != Some(index) || parent != Some(TC_H_ROOT) {
+                        continue;
+                    }
+                    backlog = find_attr(&msg[TCMSG_LEN..], TCA_STATS2)
+                        .and_then(|stats| find_attr(stats, TCA_STATS_QUEUE))
+                        .and_then(|queue| u32_at(queue, 1))
+                        .unwrap_or(0);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Iterates over the (type, payload) pairs of a block of netlink attributes
+pub(crate) fn attrs(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if buf.len() < 4 {
+            return None;
+        }
+        let len = u16::from_ne_bytes([buf[0], buf[1]]) as usize;
+        // Strip the NLA_F_NESTED / NLA_F_NET_BYTEORDER flag bits
+        let kind = u16::from_ne_bytes([buf[2], buf[3]]) & 0x3FFF;
+        if len < 4 || len > buf.len() {
+            return None;
+        }
+        let payload = &buf[4..len];
+        buf = &buf[((len + 3) & !3).min(buf.len())..];
+        Some((kind, payload))
+    })
+}
+
+/// Returns the payload of the first attribute of type `kind`
+pub(crate) fn find_attr(buf: &[u8], kind: u16) -> Option<&[u8]> {
+    attrs(buf).find(|(k, _)| *k == kind).map(|(_, p)| p)
+}
+
+/// Reads the native endian u32 at word `index` of a payload
+pub(crate) fn u32_at(payload: &[u8], index: usize) -> Option<u32> {
+    let bytes = payload.get(index * 4..index * 4 + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads the native endian u64 at word `index` of a payload
+pub(crate) fn u64_at(payload: &[u8], index: usize) -> Option<u64> {
+    let bytes = payload.get(index * 8..index * 8 + 8)?;
+    Some(u64::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+/// Returns the driver name that ethtool reports for the interface `name`
+///
+/// The ioctl is issued on `fd`, any socket in the interface's network namespace.
+pub(crate) fn driver_name(fd: RawFd, name: &str) -> std::io::Result<String> {
+    if name.len() >= libc::IFNAMSIZ {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Interface name is too long",
+        ));
+    }
+    let mut info = [0u8; ETHTOOL_DRVINFO_LEN];
+    info[0..4].copy_from_slice(&ETHTOOL_GDRVINFO.to_ne_bytes());
+    // SAFETY: ifreq is plain data, all zeroes is a valid value
+    let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, src) in req.ifr_name.iter_mut().zip(name.bytes()) {
+        *dst = src as libc::c_char;
+    }
+    req.ifr_ifru.ifru_data = info.as_mut_ptr().cast();
+    // SAFETY: req names the interface and points at a buffer the size of struct ethtool_drvinfo
+    if unsafe { libc::ioctl(fd, libc::SIOCETHTOOL, &mut req) } < 0 {
+        return Err(IoError::last_os_error());
+    }
+    let driver = &info[4..4 + ETHTOOL_DRIVER_LEN];
+    let len = driver.iter().position(|&b| b == 0).unwrap_or(driver.len());
+    Ok(String::from_utf8_lossy(&driver[..len]).into_owned())
+}
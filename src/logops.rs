@@ -0,0 +1,118 @@
+///
+/// logops.rs
+///
+/// Streaming cut, filter and merge operations over timestamped frame logs (e.g. `PcapReader`).
+///
+use crate::can::CanFrame;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::time::SystemTime;
+
+/// A frame read from a log, as yielded by `PcapReader`
+pub type LogItem = std::io::Result<(SystemTime, CanFrame)>;
+
+/// Keeps the frames with `start <= time < end`. Errors are passed through.
+///
+/// With `stop_at_end`, iteration ends at the first frame at or after `end`, which avoids reading
+/// the rest of a large file but is only correct for logs in time order.
+pub fn cut<I>(
+    frames: I,
+    start: SystemTime,
+    end: SystemTime,
+    stop_at_end: bool,
+) -> impl Iterator<Item = LogItem>
+where
+    I: IntoIterator<Item = LogItem>,
+{
+    frames
+        .into_iter()
+        .take_while(move |item| !stop_at_end || item.as_ref().map_or(true, |(t, _)| *t < end))
+        .filter(move |item| {
+            item.as_ref()
+                .map_or(true, |(t, _)| (start..end).contains(t))
+        })
+}
+
+/// Keeps the frames whose ID is in `ids`, regardless of the ID format. Errors are passed through.
+pub fn filter_ids<I>(frames: I, ids: &[u32]) -> impl Iterator<Item = LogItem> + use<I>
+where
+    I: IntoIterator<Item = LogItem>,
+{
+    let ids: HashSet<u32> = ids.iter().copied().collect();
+    frames
+        .into_iter()
+        .filter(move |item| item.as_ref().map_or(true, |(_, f)| ids.contains(&f.id())))
+}
+
+/// Merges logs that are each in time order into one stream in time order
+///
+/// Yields the index of the source log with each frame. Only the next frame of every log is held
+/// in memory. Frames with equal timestamps are yielded in log order. An error from a log is
+/// yielded in place of its next frame and that log is not read further.
+pub fn merge<I>(logs: Vec<I>) -> Merge<I::IntoIter>
+where
+    I: IntoIterator<Item = LogItem>,
+{
+    let mut merge = Merge {
+        logs: logs.into_iter().map(IntoIterator::into_iter).collect(),
+        heap: BinaryHeap::new(),
+        errors: Vec::new(),
+    };
+    for index in 0..merge.logs.len() {
+        merge.advance(index);
+    }
+    merge
+}
+
+/// Iterator returned by `merge()`
+pub struct Merge<I> {
+    logs: Vec<I>,
+    heap: BinaryHeap<Reverse<(SystemTime, usize, FrameSlot)>>,
+    errors: Vec<(usize, std::io::Error)>,
+}
+
+// Orders heap entries by time and log index only
+struct FrameSlot(CanFrame);
+
+impl PartialEq for FrameSlot {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for FrameSlot {}
+
+impl PartialOrd for FrameSlot {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FrameSlot {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl<I: Iterator<Item = LogItem>> Merge<I> {
+    fn advance(&mut self, index: usize) {
+        match self.logs[index].next() {
+            Some(Ok((time, frame))) => self.heap.push(Reverse((time, index, FrameSlot(frame)))),
+            Some(Err(e)) => self.errors.push((index, e)),
+            None => {}
+        }
+    }
+}
+
+impl<I: Iterator<Item = LogItem>> Iterator for Merge<I> {
+    type Item = std::io::Result<(usize, SystemTime, CanFrame)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((_, e)) = self.errors.pop() {
+            return Some(Err(e));
+        }
+        let Reverse((time, index, FrameSlot(frame))) = self.heap.pop()?;
+        self.advance(index);
+        Some(Ok((index, time, frame)))
+    }
+}
@@ -0,0 +1,176 @@
+///
+/// lvc.rs
+///
+/// Last-value cache: the latest frame of every CAN ID, for dashboards and control loops.
+///
+use crate::can::{CanFrame, FrameKey};
+use crate::pipeline::{Annotated, Stage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The latest frame of one ID and how fresh it is, as returned by `LvcCache`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LastValue {
+    pub frame: CanFrame,
+    /// When the frame was put into the cache
+    pub received: Instant,
+    /// Time since `received`, at the time of the query
+    pub age: Duration,
+    /// Time between the last two frames of this ID. None after the first frame
+    pub period: Option<Duration>,
+    /// Number of frames of this ID seen so far
+    pub count: u64,
+    /// Whether `age` exceeds the maximum age of the cache or of this ID
+    pub stale: bool,
+}
+
+struct Entry {
+    frame: CanFrame,
+    received: Instant,
+    period: Option<Duration>,
+    count: u64,
+}
+
+#[derive(Default)]
+struct LvcState {
+    entries: HashMap<FrameKey, Entry>,
+    max_age: Option<Duration>,
+    max_ages: HashMap<FrameKey, Duration>,
+}
+
+impl LvcState {
+    fn last_value(&self, key: &FrameKey, entry: &Entry, now: Instant) -> LastValue {
+        let age = now.duration_since(entry.received);
+        let max_age = self.max_ages.get(key).or(self.max_age.as_ref());
+        LastValue {
+            frame: entry.frame.clone(),
+            received: entry.received,
+            age,
+            period: entry.period,
+            count: entry.count,
+            stale: max_age.is_some_and(|max_age| age > *max_age),
+        }
+    }
+}
+
+/// Keeps the latest frame of every CAN ID
+///
+/// Feed received frames with `observe()` or put the cache into a pipeline, where it records every
+/// frame and passes it on unchanged. Clones share the same cache, so one handle can be updated by
+/// the read loop while others are queried synchronously, e.g. from a UI thread. Error frames are
+/// ignored. Standard and extended frames with the same numeric ID are kept apart.
+#[derive(Clone, Default)]
+pub struct LvcCache {
+    state: Arc<Mutex<LvcState>>,
+}
+
+impl LvcCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks values older than `max_age` as stale, unless their ID has its own maximum age
+    pub fn with_max_age(self, max_age: Duration) -> Self {
+        self.state.lock().unwrap().max_age = Some(max_age);
+        self
+    }
+
+    /// Marks values of one ID as stale once they are older than `max_age`, e.g. a few times the
+    /// cycle time of a periodic frame
+    pub fn with_id_max_age(self, id: u32, extended: bool, max_age: Duration) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .max_ages
+            .insert(FrameKey::from_id(id, extended), max_age);
+        self
+    }
+
+    /// Records a received frame as the latest value of its ID
+    pub fn observe(&self, frame: &CanFrame) {
+        if frame.is_error() {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state
+            .entries
+            .entry(FrameKey::id(frame))
+            .and_modify(|entry| {
+                entry.period = Some(now.duration_since(entry.received));
+                entry.frame = frame.clone();
+                entry.received = now;
+                entry.count += 1;
+            })
+            .or_insert_with(|| Entry {
+                frame: frame.clone(),
+                received: now,
+                period: None,
+                count: 1,
+            });
+    }
+
+    /// Returns the latest value of an ID, or None if it was not seen yet
+    pub fn get(&self, id: u32, extended: bool) -> Option<LastValue> {
+        let state = self.state.lock().unwrap();
+        let key = FrameKey::from_id(id, extended);
+        let entry = state.entries.get(&key)?;
+        Some(state.last_value(&key, entry, Instant::now()))
+    }
+
+    /// Returns the latest frame of an ID, stale or not
+    pub fn frame(&self, id: u32, extended: bool) -> Option<CanFrame> {
+        let state = self.state.lock().unwrap();
+        let key = FrameKey::from_id(id, extended);
+        state.entries.get(&key).map(|entry| entry.frame.clone())
+    }
+
+    /// Returns the latest value of every ID, standard IDs first, each in ascending order
+    pub fn snapshot(&self) -> Vec<LastValue> {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let mut values: Vec<_> = state
+            .entries
+            .iter()
+            .map(|(key, entry)| state.last_value(key, entry, now))
+            .collect();
+        values.sort_by_key(|value| (value.frame.is_extended(), value.frame.id()));
+        values
+    }
+
+    /// Returns the values that are currently stale, in the order of `snapshot()`
+    pub fn stale(&self) -> Vec<LastValue> {
+        let mut values = self.snapshot();
+        values.retain(|value| value.stale);
+        values
+    }
+
+    /// Forgets the value of one ID
+    pub fn remove(&self, id: u32, extended: bool) {
+        let key = FrameKey::from_id(id, extended);
+        self.state.lock().unwrap().entries.remove(&key);
+    }
+
+    /// Forgets all values. Maximum ages are kept
+    pub fn clear(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    /// Returns the number of IDs in the cache
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Stage for LvcCache {
+    fn process(&mut self, frame: Annotated) -> Option<Annotated> {
+        self.observe(&frame.frame);
+        Some(frame)
+    }
+}
@@ -0,0 +1,87 @@
+///
+/// meta.rs
+///
+/// Per-interface receive metadata (sequence numbers and inter-frame deltas).
+///
+use crate::FramesDropped;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Receive metadata attached to a frame by a FrameSequencer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameMeta {
+    /// Monotonically increasing number of the frame on its interface, starting at 0
+    pub sequence: u64,
+    /// Time at which the frame was tagged
+    pub received_at: Instant,
+    /// Time since the previous frame on the same interface. None for the first frame.
+    pub delta: Option<Duration>,
+}
+
+/// Assigns sequence numbers and inter-frame deltas to the frames of one interface
+///
+/// Tag each frame as soon as it is read. Gaps in the sequence further down a processing chain
+/// (e.g. after a lossy channel) show exactly how many frames were lost. Frames lost before they
+/// were read, reported by the interface as `FramesDropped`, leave a gap too once they are passed
+/// to `record_dropped()`.
+#[derive(Debug, Default)]
+pub struct FrameSequencer {
+    next_sequence: u64,
+    last_received: Option<Instant>,
+    dropped: u64,
+}
+
+impl FrameSequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the metadata for the frame that was just received
+    pub fn tag(&mut self) -> FrameMeta {
+        let now = Instant::now();
+        let meta = FrameMeta {
+            sequence: self.next_sequence,
+            received_at: now,
+            delta: self.last_received.map(|last| now - last),
+        };
+        self.next_sequence += 1;
+        self.last_received = Some(now);
+        meta
+    }
+
+    /// Skips `count` sequence numbers for frames that were lost before they could be tagged
+    pub fn record_dropped(&mut self, count: u64) {
+        self.next_sequence += count;
+        self.dropped += count;
+    }
+
+    /// Passes the number of lost frames to `record_dropped()` if `error` is a `FramesDropped`.
+    /// Returns whether it was
+    pub fn record_error(&mut self, error: &std::io::Error) -> bool {
+        match error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<FramesDropped>())
+        {
+            Some(FramesDropped(count)) => {
+                self.record_dropped(*count);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of frames tagged so far
+    pub fn count(&self) -> u64 {
+        self.next_sequence - self.dropped
+    }
+
+    /// Returns the number of frames recorded as dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Restarts the sequence at 0, e.g. after reopening the interface
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,174 @@
+///
+/// mqtt.rs
+///
+/// Bridges a CanInterface to an MQTT broker.
+///
+use crate::{CanInterface, FramesDropped, can::CanFrame, pipeline::Annotated};
+use rumqttc::{AsyncClient, ClientError, Event, EventLoop, Packet, QoS};
+use std::io::{Error as IoError, ErrorKind};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Publishes received frames and decoded signals to MQTT topics and writes frames published on a
+/// command topic to the bus
+///
+/// Topic templates may contain `{channel}`, `{id}` (hex, 3 digits for standard and 8 for extended
+/// IDs) and, for signals, `{signal}`. Frames are published in the JSON format of `CanFrame`, signal
+/// values as plain decimal numbers.
+pub struct MqttBridge {
+    client: AsyncClient,
+    channel: String,
+    frame_topic: String,
+    signal_topic: String,
+    tx_topic: Option<String>,
+    qos: QoS,
+    retain: bool,
+    dropped: AtomicU64,
+    malformed: AtomicU64,
+}
+
+impl MqttBridge {
+    /// Creates a bridge publishing to `can/{channel}/rx/{id}` and `can/{channel}/signals/{signal}`
+    /// with QoS 0
+    pub fn new(client: AsyncClient, channel: &str) -> Self {
+        Self {
+            client,
+            channel: channel.to_string(),
+            frame_topic: "can/{channel}/rx/{id}".to_string(),
+            signal_topic: "can/{channel}/signals/{signal}".to_string(),
+            tx_topic: None,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            dropped: AtomicU64::new(0),
+            malformed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_frame_topic(mut self, template: &str) -> Self {
+        self.frame_topic = template.to_string();
+        self
+    }
+
+    pub fn with_signal_topic(mut self, template: &str) -> Self {
+        self.signal_topic = template.to_string();
+        self
+    }
+
+    /// Writes frames published on `template` (e.g. `can/{channel}/tx`) to the bus in `run()`
+    pub fn with_tx_topic(mut self, template: &str) -> Self {
+        self.tx_topic = Some(template.to_string());
+        self
+    }
+
+    pub fn with_qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Publishes with the retain flag, so new subscribers get the latest value of every topic
+    pub fn with_retain(mut self, retain: bool) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    fn topic(&self, template: &str, frame: Option<&CanFrame>, signal: Option<&str>) -> String {
+        let mut topic = template.replace("{channel}", &self.channel);
+        if let Some(frame) = frame {
+            topic = topic.replace("{id}", &frame.id_hex());
+        }
+        if let Some(signal) = signal {
+            topic = topic.replace("{signal}", signal);
+        }
+        topic
+    }
+
+    /// Publishes a frame to its frame topic
+    pub async fn publish_frame(&self, frame: &CanFrame) -> std::io::Result<()> {
+        let payload = serde_json::to_vec(frame).map_err(IoError::other)?;
+        let topic = self.topic(&self.frame_topic, Some(frame), None);
+        self.client
+            .publish(topic, self.qos, self.retain, payload)
+            .await
+            .map_err(IoError::other)
+    }
+
+    /// Returns the number of received frames `run()` dropped because the client's request queue
+    /// was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of messages on the command topic that `run()` skipped because they were
+    /// not a valid frame
+    pub fn malformed(&self) -> u64 {
+        self.malformed.load(Ordering::Relaxed)
+    }
+
+    /// Publishes each decoded signal of a pipeline frame to its signal topic
+    pub async fn publish_signals(&self, frame: &Annotated) -> std::io::Result<()> {
+        for (name, value) in &frame.signals {
+            let topic = self.topic(&self.signal_topic, Some(&frame.frame), Some(name));
+            self.client
+                .publish(topic, self.qos, self.retain, value.to_string())
+                .await
+                .map_err(IoError::other)?;
+        }
+        Ok(())
+    }
+
+    /// Forwards frames between `can` and the broker until an error occurs
+    ///
+    /// `eventloop` must belong to the client passed to `new()`; it is polled here, so no other
+    /// task may poll it. Received frames are queued without waiting, so a slow broker cannot stall
+    /// the event loop; if the client's request queue is full they are dropped and counted in
+    /// `dropped()`. Messages on the command topic that are not a valid frame are skipped and
+    /// counted in `malformed()`.
+    pub async fn run<T: CanInterface>(
+        &self,
+        can: &mut T,
+        mut eventloop: EventLoop,
+    ) -> std::io::Result<()> {
+        let tx_topic = self
+            .tx_topic
+            .as_ref()
+            .map(|template| self.topic(template, None, None));
+        if let Some(tx_topic) = &tx_topic {
+            self.client
+                .subscribe(tx_topic.as_str(), self.qos)
+                .await
+                .map_err(IoError::other)?;
+        }
+
+        loop {
+            tokio::select! {
+                result = can.read_frame() => match result {
+                    Ok(frame) => {
+                        let payload = serde_json::to_vec(&frame).map_err(IoError::other)?;
+                        let topic = self.topic(&self.frame_topic, Some(&frame), None);
+                        match self.client.try_publish(topic, self.qos, self.retain, payload) {
+                            Ok(()) => {}
+                            Err(ClientError::TryRequest(_)) => {
+                                self.dropped.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Err(e) => return Err(IoError::other(e)),
+                        }
+                    }
+                    Err(e) if e.get_ref().is_some_and(|e| e.is::<FramesDropped>()) => {}
+                    Err(e) => return Err(e),
+                },
+                event = eventloop.poll() => {
+                    let event = event.map_err(|e| IoError::new(ErrorKind::ConnectionAborted, e))?;
+                    if let Event::Incoming(Packet::Publish(publish)) = event
+                        && tx_topic.as_deref() == Some(publish.topic.as_str())
+                    {
+                        match serde_json::from_slice::<CanFrame>(&publish.payload) {
+                            Ok(frame) => can.write_frame(frame).await?,
+                            Err(_) => {
+                                self.malformed.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
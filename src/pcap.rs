@@ -0,0 +1,557 @@
+///
+/// pcap.rs
+///
+/// Reading and writing pcap and pcapng captures with the SocketCAN link type.
+///
+use crate::can::{CanFrame, Strictness};
+use std::io::{Error as IoError, ErrorKind, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// LINKTYPE_CAN_SOCKETCAN (DLT 227)
+pub const LINKTYPE_CAN_SOCKETCAN: u32 = 227;
+
+/// Length of a classic CAN record in LINKTYPE_CAN_SOCKETCAN format
+pub const SOCKETCAN_RECORD_LEN: usize = crate::can::LINUX_CAN_FRAME_LEN;
+
+/// Encodes a frame as a LINKTYPE_CAN_SOCKETCAN record (struct can_frame with a big-endian ID)
+pub fn encode_socketcan(frame: &CanFrame) -> [u8; SOCKETCAN_RECORD_LEN] {
+    let mut record = frame.to_linux_raw();
+    let id = u32::from_ne_bytes(record[..4].try_into().unwrap());
+    record[..4].copy_from_slice(&id.to_be_bytes());
+    record
+}
+
+/// Decodes a LINKTYPE_CAN_SOCKETCAN record into a frame
+pub fn decode_socketcan(record: &[u8]) -> std::io::Result<CanFrame> {
+    decode_socketcan_with(record, Strictness::Strict)
+}
+
+/// Like `decode_socketcan()`, validating the frame with `strictness`
+pub fn decode_socketcan_with(record: &[u8], strictness: Strictness) -> std::io::Result<CanFrame> {
+    if record.len() != SOCKETCAN_RECORD_LEN {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!(
+                "SocketCAN record of {} bytes is not a classic CAN frame",
+                record.len()
+            ),
+        ));
+    }
+    let mut raw = [0u8; SOCKETCAN_RECORD_LEN];
+    raw.copy_from_slice(record);
+    let id = u32::from_be_bytes(raw[..4].try_into().unwrap());
+    raw[..4].copy_from_slice(&id.to_ne_bytes());
+    CanFrame::from_linux_raw_with(&raw, strictness)
+        .map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+}
+
+/// Writes frames as a classic pcap stream with microsecond timestamps
+pub struct PcapWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the pcap file header to `writer`
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a frame received at `time`
+    pub fn write_frame(&mut self, frame: &CanFrame, time: SystemTime) -> std::io::Result<()> {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut record = Vec::with_capacity(16 + SOCKETCAN_RECORD_LEN);
+        record.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        record.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        record.extend_from_slice(&encode_socketcan(frame));
+        self.writer.write_all(&record)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes frames as a pcapng stream with a single SocketCAN interface and microsecond timestamps
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Writes the section header and interface description blocks to `writer`
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        let mut shb = Vec::with_capacity(16);
+        shb.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        // Section length not specified
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut writer, PCAPNG_SECTION_HEADER, &shb)?;
+
+        let mut idb = Vec::with_capacity(8);
+        idb.extend_from_slice(&(LINKTYPE_CAN_SOCKETCAN as u16).to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        write_block(&mut writer, PCAPNG_INTERFACE_DESCRIPTION, &idb)?;
+
+        Ok(Self { writer })
+    }
+
+    /// Writes a frame received at `time`
+    pub fn write_frame(&mut self, frame: &CanFrame, time: SystemTime) -> std::io::Result<()> {
+        let micros = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let mut epb = Vec::with_capacity(20 + SOCKETCAN_RECORD_LEN);
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(micros as u32).to_le_bytes());
+        epb.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        epb.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        epb.extend_from_slice(&encode_socketcan(frame));
+        write_block(&mut self.writer, PCAPNG_ENHANCED_PACKET, &epb)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_OPTION_TSRESOL: u16 = 9;
+// Upper bound for a single record or block, protecting against corrupt length fields
+const MAX_BLOCK_LEN: usize = 1 << 20;
+
+// Writes a little-endian pcapng block, padding the body to 32 bits
+fn write_block<W: Write>(writer: &mut W, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+    let padding = (4 - body.len() % 4) % 4;
+    let total = (12 + body.len() + padding) as u32;
+    let mut block = Vec::with_capacity(total as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&total.to_le_bytes());
+    block.extend_from_slice(body);
+    block.resize(block.len() + padding, 0);
+    block.extend_from_slice(&total.to_le_bytes());
+    writer.write_all(&block)
+}
+
+enum Format {
+    Pcap {
+        nanos: bool,
+    },
+    Pcapng {
+        /// Link type and if_tsresol value of each interface in the current section
+        interfaces: Vec<(u16, u8)>,
+    },
+}
+
+/// Reads frames from a pcap or pcapng capture with SocketCAN packets
+///
+/// Iterating yields each frame with its capture time. Packets of other link types in a pcapng
+/// file are skipped.
+pub struct PcapReader<R: Read> {
+    reader: R,
+    format: Format,
+    big_endian: bool,
+    strictness: Strictness,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Reads the file header and detects the format and byte order
+    pub fn new(mut reader: R) -> std::io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if u32::from_le_bytes(magic) == PCAPNG_SECTION_HEADER {
+            let mut pcap = Self {
+                reader,
+                format: Format::Pcapng {
+                    interfaces: Vec::new(),
+                },
+                big_endian: false,
+                strictness: Strictness::Strict,
+            };
+            pcap.read_section_header()?;
+            return Ok(pcap);
+        }
+
+        let (nanos, big_endian) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, false),
+            (PCAP_MAGIC_NANOS, _) => (true, false),
+            (_, PCAP_MAGIC_MICROS) => (false, true),
+            (_, PCAP_MAGIC_NANOS) => (true, true),
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "Not a pcap or pcapng file",
+                ));
+            }
+        };
+        let mut pcap = Self {
+            reader,
+            format: Format::Pcap { nanos },
+            big_endian,
+            strictness: Strictness::Strict,
+        };
+        let mut header = [0u8; 20];
+        pcap.reader.read_exact(&mut header)?;
+        let link_type = pcap.u32_at(&header, 16) & 0x0FFF_FFFF;
+        if link_type != LINKTYPE_CAN_SOCKETCAN {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported pcap link type {}", link_type),
+            ));
+        }
+        Ok(pcap)
+    }
+
+    /// Sets how recorded frames are validated. Defaults to `Strictness::Strict`
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Reads the next frame. Returns None at the end of the capture
+    pub fn read_frame(&mut self) -> std::io::Result<Option<(SystemTime, CanFrame)>> {
+        match self.format {
+            Format::Pcap { nanos } => self.read_pcap_record(nanos),
+            Format::Pcapng { .. } => self.read_pcapng_packet(),
+        }
+    }
+
+    fn read_pcap_record(&mut self, nanos: bool) -> std::io::Result<Option<(SystemTime, CanFrame)>> {
+        let mut header = [0u8; 16];
+        if !self.read_exact_or_eof(&mut header)? {
+            return Ok(None);
+        }
+        let secs = self.u32_at(&header, 0) as u64;
+        let fraction = self.u32_at(&header, 4);
+        let captured = self.u32_at(&header, 8) as usize;
+        let record = self.read_body(captured)?;
+
+        let fraction = if nanos {
+            Duration::from_nanos(fraction as u64)
+        } else {
+            Duration::from_micros(fraction as u64)
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(secs) + fraction;
+        Ok(Some((
+            time,
+            decode_socketcan_with(&record, self.strictness)?,
+        )))
+    }
+
+    fn read_pcapng_packet(&mut self) -> std::io::Result<Option<(SystemTime, CanFrame)>> {
+        loop {
+            let mut header = [0u8; 8];
+            if !self.read_exact_or_eof(&mut header)? {
+                return Ok(None);
+            }
+            // The byte order of a new section is only known after reading its header
+            if u32::from_le_bytes(header[..4].try_into().unwrap()) == PCAPNG_SECTION_HEADER {
+                self.read_section_body(header[4..].try_into().unwrap())?;
+                continue;
+            }
+
+            let block_type = self.u32_at(&header, 0);
+            let total = self.u32_at(&header, 4) as usize;
+            if total < 12 || !total.is_multiple_of(4) {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid pcapng block length {}", total),
+                ));
+            }
+            let body = self.read_body(total - 8)?;
+            let body = &body[..body.len() - 4];
+
+            match block_type {
+                PCAPNG_INTERFACE_DESCRIPTION => self.add_interface(body)?,
+                PCAPNG_ENHANCED_PACKET => {
+                    if let Some(packet) = self.decode_enhanced_packet(body)? {
+                        return Ok(Some(packet));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_section_header(&mut self) -> std::io::Result<()> {
+        let mut length = [0u8; 4];
+        self.reader.read_exact(&mut length)?;
+        self.read_section_body(length)
+    }
+
+    // Reads the rest of a section header block after its type, given the raw length bytes
+    fn read_section_body(&mut self, length: [u8; 4]) -> std::io::Result<()> {
+        let mut magic = [0u8; 4];
+        self.reader.read_exact(&mut magic)?;
+        self.big_endian = match u32::from_le_bytes(magic) {
+            PCAPNG_BYTE_ORDER_MAGIC => false,
+            m if m.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "Invalid pcapng byte order magic",
+                ));
+            }
+        };
+        let total = self.u32_at(&length, 0) as usize;
+        if total < 28 {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "Invalid pcapng section header length",
+            ));
+        }
+        self.read_body(total - 12)?;
+        self.format = Format::Pcapng {
+            interfaces: Vec::new(),
+        };
+        Ok(())
+    }
+
+    fn add_interface(&mut self, body: &[u8]) -> std::io::Result<()> {
+        if body.len() < 8 {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "Truncated pcapng interface description",
+            ));
+        }
+        let link_type = self.u16_at(body, 0);
+        // Microseconds unless the interface says otherwise
+        let mut tsresol = 6;
+
+        let mut options = &body[8..];
+        while options.len() >= 4 {
+            let code = self.u16_at(options, 0);
+            let len = self.u16_at(options, 2) as usize;
+            let Some(value) = options.get(4..4 + len) else {
+                break;
+            };
+            if code == PCAPNG_OPTION_TSRESOL && len == 1 {
+                tsresol = value[0];
+            }
+            if code == 0 {
+                break;
+            }
+            options = options.get(4 + len.div_ceil(4) * 4..).unwrap_or_default();
+        }
+
+        if let Format::Pcapng { interfaces } = &mut self.format {
+            interfaces.push((link_type, tsresol));
+        }
+        Ok(())
+    }
+
+    fn decode_enhanced_packet(
+        &self,
+        body: &[u8],
+    ) -> std::io::Result<Option<(SystemTime, CanFrame)>> {
+        if body.len() < 20 {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "Truncated pcapng enhanced packet",
+            ));
+        }
+        let Format::Pcapng { interfaces } = &self.format else {
+            unreachable!();
+        };
+        let interface = self.u32_at(body, 0) as usize;
+        let Some(&(link_type, tsresol)) = interfaces.get(interface) else {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Packet references unknown interface {}", interface),
+            ));
+        };
+        if link_type as u32 != LINKTYPE_CAN_SOCKETCAN {
+            return Ok(None);
+        }
+
+        let ticks = (self.u32_at(body, 4) as u64) << 32 | self.u32_at(body, 8) as u64;
+        let captured = self.u32_at(body, 12) as usize;
+        let record = body.get(20..20 + captured).ok_or_else(|| {
+            IoError::new(ErrorKind::InvalidData, "Truncated pcapng enhanced packet")
+        })?;
+        Ok(Some((
+            UNIX_EPOCH + ticks_to_duration(ticks, tsresol),
+            decode_socketcan_with(record, self.strictness)?,
+        )))
+    }
+
+    fn read_body(&mut self, len: usize) -> std::io::Result<Vec<u8>> {
+        if len > MAX_BLOCK_LEN {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Capture record of {} bytes is too long", len),
+            ));
+        }
+        let mut body = vec![0u8; len];
+        self.reader.read_exact(&mut body)?;
+        Ok(body)
+    }
+
+    // Fills `buf`, returning false on a clean end of file before the first byte
+    fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> std::io::Result<bool> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn u32_at(&self, buf: &[u8], offset: usize) -> u32 {
+        let bytes = buf[offset..offset + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u16_at(&self, buf: &[u8], offset: usize) -> u16 {
+        let bytes = buf[offset..offset + 2].try_into().unwrap();
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = std::io::Result<(SystemTime, CanFrame)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+// Converts a timestamp in ticks of 10^-tsresol seconds, or 2^-(tsresol & 0x7F) if the top bit is
+// set, to the time since the epoch. Integer arithmetic keeps sub-nanosecond and binary resolutions
+// exact down to the nanosecond.
+fn ticks_to_duration(ticks: u64, tsresol: u8) -> Duration {
+    let exponent = (tsresol & 0x7F) as u32;
+    let nanos = ticks as u128 * 1_000_000_000;
+    let nanos = if tsresol & 0x80 != 0 {
+        nanos.checked_shr(exponent).unwrap_or(0)
+    } else {
+        10u128
+            .checked_pow(exponent)
+            .map_or(0, |divisor| nanos / divisor)
+    };
+    Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A pcapng capture with one SocketCAN interface of resolution `tsresol` and one packet at
+    // `ticks`
+    fn capture(tsresol: Option<u8>, ticks: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut shb = Vec::new();
+        shb.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut data, PCAPNG_SECTION_HEADER, &shb).unwrap();
+
+        let mut idb = Vec::new();
+        idb.extend_from_slice(&(LINKTYPE_CAN_SOCKETCAN as u16).to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        if let Some(tsresol) = tsresol {
+            idb.extend_from_slice(&PCAPNG_OPTION_TSRESOL.to_le_bytes());
+            idb.extend_from_slice(&1u16.to_le_bytes());
+            idb.extend_from_slice(&[tsresol, 0, 0, 0]);
+            idb.extend_from_slice(&[0u8; 4]);
+        }
+        write_block(&mut data, PCAPNG_INTERFACE_DESCRIPTION, &idb).unwrap();
+
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(ticks as u32).to_le_bytes());
+        epb.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        epb.extend_from_slice(&(SOCKETCAN_RECORD_LEN as u32).to_le_bytes());
+        epb.extend_from_slice(&encode_socketcan(&CanFrame::standard(0x123, &[1, 2])));
+        write_block(&mut data, PCAPNG_ENHANCED_PACKET, &epb).unwrap();
+        data
+    }
+
+    fn read_time(data: &[u8]) -> Duration {
+        let (time, frame) = PcapReader::new(data)
+            .unwrap()
+            .read_frame()
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, CanFrame::standard(0x123, &[1, 2]));
+        time.duration_since(UNIX_EPOCH).unwrap()
+    }
+
+    #[test]
+    fn pcapng_timestamp_resolutions() {
+        let micros = 1_700_000_000_123_456;
+        assert_eq!(
+            read_time(&capture(None, micros)),
+            Duration::from_micros(micros)
+        );
+        assert_eq!(
+            read_time(&capture(Some(6), micros)),
+            Duration::from_micros(micros)
+        );
+
+        let nanos = 1_700_000_000_123_456_789;
+        assert_eq!(
+            read_time(&capture(Some(9), nanos)),
+            Duration::from_nanos(nanos)
+        );
+
+        // Picoseconds only cover a few months in 64 bits
+        assert_eq!(
+            read_time(&capture(Some(12), 5_123_456_789_012)),
+            Duration::from_nanos(5_123_456_789)
+        );
+
+        // 2^-20 s ticks, which are not a whole number of nanoseconds
+        assert_eq!(
+            read_time(&capture(Some(0x80 | 20), 1_700_000_000 << 20)),
+            Duration::from_secs(1_700_000_000)
+        );
+        assert_eq!(
+            read_time(&capture(Some(0x80 | 20), (3 << 20) + 3)),
+            Duration::new(3, 2861)
+        );
+    }
+}
@@ -0,0 +1,257 @@
+///
+/// pipeline.rs
+///
+/// Composable processing stages applied to received frames before delivery.
+///
+use crate::{
+    CanInterface, FramesDropped,
+    can::CanFrame,
+    meta::{FrameMeta, FrameSequencer},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A received frame together with the annotations added by pipeline stages
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotated {
+    pub frame: CanFrame,
+    /// Name of the channel the frame was received on, set by `TagChannel`
+    pub channel: Option<Arc<str>>,
+    /// Receive metadata, set by `Sequence`
+    pub meta: Option<FrameMeta>,
+    /// Decoded signal values, set by `Decode`
+    pub signals: Vec<(String, f64)>,
+}
+
+impl Annotated {
+    pub fn new(frame: CanFrame) -> Self {
+        Self {
+            frame,
+            channel: None,
+            meta: None,
+            signals: Vec::new(),
+        }
+    }
+}
+
+/// A step of a pipeline. Returning None drops the frame.
+pub trait Stage: Send {
+    fn process(&mut self, frame: Annotated) -> Option<Annotated>;
+
+    /// Called when the interface reports that `count` frames were lost before they were read
+    fn frames_dropped(&mut self, _count: u64) {}
+}
+
+impl<F> Stage for F
+where
+    F: FnMut(Annotated) -> Option<Annotated> + Send,
+{
+    fn process(&mut self, frame: Annotated) -> Option<Annotated> {
+        self(frame)
+    }
+}
+
+/// Reads frames from a CanInterface and runs them through a chain of stages, in order
+pub struct Pipeline<T: CanInterface> {
+    can: T,
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl<T: CanInterface> Pipeline<T> {
+    pub fn new(can: T) -> Self {
+        Self {
+            can,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Appends a stage to the end of the pipeline
+    pub fn pipe<S: Stage + 'static>(mut self, stage: S) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Reads frames until one passes every stage and returns it
+    ///
+    /// A `FramesDropped` error is passed to every stage before it is returned.
+    pub async fn read_frame(&mut self) -> std::io::Result<Annotated> {
+        loop {
+            let frame = match self.can.read_frame().await {
+                Ok(frame) => frame,
+                Err(e) => {
+                    if let Some(FramesDropped(count)) =
+                        e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>())
+                    {
+                        for stage in &mut self.stages {
+                            stage.frames_dropped(*count);
+                        }
+                    }
+                    return Err(e);
+                }
+            };
+            if let Some(frame) = self.process(frame) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Runs a frame obtained elsewhere through the stages
+    pub fn process(&mut self, frame: CanFrame) -> Option<Annotated> {
+        self.stages
+            .iter_mut()
+            .try_fold(Annotated::new(frame), |frame, stage| stage.process(frame))
+    }
+
+    /// Returns the underlying interface, e.g. to write frames
+    pub fn interface(&mut self) -> &mut T {
+        &mut self.can
+    }
+
+    pub fn into_inner(self) -> T {
+        self.can
+    }
+}
+
+/// Adds `pipe()` to every CanInterface
+pub trait PipeExt: CanInterface {
+    /// Starts a pipeline reading from this interface with `stage` as first stage
+    fn pipe<S: Stage + 'static>(self, stage: S) -> Pipeline<Self> {
+        Pipeline::new(self).pipe(stage)
+    }
+}
+
+impl<T: CanInterface> PipeExt for T {}
+
+/// Tags every frame with a channel name
+pub struct TagChannel(Arc<str>);
+
+impl TagChannel {
+    pub fn new(channel: &str) -> Self {
+        Self(channel.into())
+    }
+}
+
+impl Stage for TagChannel {
+    fn process(&mut self, mut frame: Annotated) -> Option<Annotated> {
+        frame.channel = Some(self.0.clone());
+        Some(frame)
+    }
+}
+
+/// Attaches sequence numbers and inter-frame deltas. Frames reported as dropped leave a gap
+#[derive(Default)]
+pub struct Sequence(FrameSequencer);
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Stage for Sequence {
+    fn process(&mut self, mut frame: Annotated) -> Option<Annotated> {
+        frame.meta = Some(self.0.tag());
+        Some(frame)
+    }
+
+    fn frames_dropped(&mut self, count: u64) {
+        self.0.record_dropped(count);
+    }
+}
+
+/// Decodes signals from each frame with a user supplied function
+pub struct Decode<F>(F);
+
+impl<F> Decode<F>
+where
+    F: FnMut(&CanFrame) -> Vec<(String, f64)> + Send,
+{
+    pub fn new(decoder: F) -> Self {
+        Self(decoder)
+    }
+}
+
+impl<F> Stage for Decode<F>
+where
+    F: FnMut(&CanFrame) -> Vec<(String, f64)> + Send,
+{
+    fn process(&mut self, mut frame: Annotated) -> Option<Annotated> {
+        let signals = (self.0)(&frame.frame);
+        frame.signals.extend(signals);
+        Some(frame)
+    }
+}
+
+/// Drops frames identical (ID and data) to one seen less than `window` ago
+pub struct Dedup {
+    window: Duration,
+    last_seen: HashMap<(u32, bool, Vec<u8>), Instant>,
+}
+
+impl Dedup {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_seen: HashMap::new(),
+        }
+    }
+}
+
+impl Stage for Dedup {
+    fn process(&mut self, frame: Annotated) -> Option<Annotated> {
+        let now = Instant::now();
+        self.last_seen
+            .retain(|_, seen| now.duration_since(*seen) < self.window);
+        let key = (
+            frame.frame.id(),
+            frame.frame.is_extended(),
+            frame.frame.data().to_vec(),
+        );
+        match self.last_seen.insert(key, now) {
+            Some(_) => None,
+            None => Some(frame),
+        }
+    }
+}
+
+/// Discards the frames an adapter delivers while it settles after being opened
+///
+/// Frames are dropped for `duration` after the stage is created, so build it right after opening
+/// the interface. With `until_clean()`, error frames received after the grace period are dropped
+/// as well until the first data frame arrives. From then on every frame passes, including later
+/// error frames.
+pub struct WarmUp {
+    deadline: Instant,
+    until_clean: bool,
+    settled: bool,
+}
+
+impl WarmUp {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + duration,
+            until_clean: false,
+            settled: false,
+        }
+    }
+
+    /// After the grace period, also wait for the first data frame that is not an error frame
+    pub fn until_clean(mut self) -> Self {
+        self.until_clean = true;
+        self
+    }
+}
+
+impl Stage for WarmUp {
+    fn process(&mut self, frame: Annotated) -> Option<Annotated> {
+        if !self.settled {
+            if Instant::now() < self.deadline || (self.until_clean && frame.frame.is_error()) {
+                return None;
+            }
+            self.settled = true;
+        }
+        Some(frame)
+    }
+}
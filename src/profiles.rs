@@ -0,0 +1,215 @@
+///
+/// profiles.rs
+///
+/// Named interface profiles loaded from a TOML file, so applications open interfaces by role.
+///
+use crate::{CanInterface, can::CanFrame, pipeline::Annotated, pipeline::Stage};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Environment variable holding the path of the profiles file used by `Profiles::from_env()`
+pub const PROFILES_PATH_VAR: &str = "CROSSCAN_PROFILES";
+
+/// Prefix of the per-profile override variables, e.g. `CROSSCAN_PROFILE_DRIVETRAIN_CHANNEL`
+pub const PROFILE_VAR_PREFIX: &str = "CROSSCAN_PROFILE_";
+
+/// Name of the backend implemented by this platform's interface type
+#[cfg(target_os = "linux")]
+pub const PLATFORM_BACKEND: &str = "socketcan";
+#[cfg(target_os = "windows")]
+pub const PLATFORM_BACKEND: &str = "serial";
+
+/// An acceptance filter: a frame passes if `frame.id() & mask == id & mask`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileFilter {
+    pub id: u32,
+    pub mask: u32,
+    /// Only match standard (false) or extended (true) frames. Matches both if unset
+    #[serde(default)]
+    pub extended: Option<bool>,
+}
+
+impl ProfileFilter {
+    pub fn matches(&self, frame: &CanFrame) -> bool {
+        if self.extended.is_some_and(|ext| ext != frame.is_extended()) {
+            return false;
+        }
+        frame.id() & self.mask == self.id & self.mask
+    }
+}
+
+/// The interface settings for one logical role, e.g. "drivetrain"
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Channel passed to `CanInterface::open`, e.g. "can0" or "COM5"
+    pub channel: String,
+    /// Backend the profile is meant for ("socketcan" or "serial"). Any backend if unset
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Expected bus bitrate, checked when the profile is opened
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    /// Acceptance filters. An empty list accepts every frame
+    #[serde(default)]
+    pub filters: Vec<ProfileFilter>,
+}
+
+impl Profile {
+    /// Returns true if `frame` passes at least one of the profile's filters
+    pub fn accepts(&self, frame: &CanFrame) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|f| f.matches(frame))
+    }
+
+    /// Returns a pipeline stage that drops frames rejected by the profile's filters
+    pub fn filter_stage(&self) -> impl Stage + use<> {
+        let profile = self.clone();
+        move |annotated: Annotated| profile.accepts(&annotated.frame).then_some(annotated)
+    }
+
+    /// Opens the profile's channel
+    ///
+    /// Fails with `Unsupported` if the profile names another backend, and with `InvalidInput` if a
+    /// bitrate is configured and the interface reports a different one. The bitrate is only
+    /// checked, not applied, since changing it usually requires privileges and a downed interface.
+    /// Filters are not installed on the interface; apply them with `accepts()` or `filter_stage()`.
+    pub async fn open<T: CanInterface>(&self) -> std::io::Result<T> {
+        if let Some(backend) = &self.backend
+            && backend != PLATFORM_BACKEND
+        {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "Profile is for backend {}, this platform uses {}",
+                    backend, PLATFORM_BACKEND
+                ),
+            ));
+        }
+
+        let mut can = T::open(&self.channel).await?;
+
+        if let Some(expected) = self.bitrate {
+            match can.get_bitrate().await? {
+                Some(actual) if actual != expected => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "{} runs at {} bit/s, profile expects {} bit/s",
+                            self.channel, actual, expected
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(can)
+    }
+
+    fn apply_env_overrides(&mut self, name: &str) -> std::io::Result<()> {
+        let prefix = env_prefix(name);
+
+        if let Ok(channel) = std::env::var(format!("{}CHANNEL", prefix)) {
+            self.channel = channel;
+        }
+        if let Ok(backend) = std::env::var(format!("{}BACKEND", prefix)) {
+            self.backend = (!backend.is_empty()).then_some(backend);
+        }
+        if let Ok(bitrate) = std::env::var(format!("{}BITRATE", prefix)) {
+            self.bitrate = if bitrate.is_empty() {
+                None
+            } else {
+                Some(bitrate.parse().map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid bitrate in {}BITRATE: {}", prefix, bitrate),
+                    )
+                })?)
+            };
+        }
+        Ok(())
+    }
+}
+
+/// A set of named profiles
+///
+/// ```toml
+/// [drivetrain]
+/// channel = "can0"
+/// backend = "socketcan"
+/// bitrate = 500000
+/// filters = [{ id = 0x180, mask = 0x780 }]
+/// ```
+///
+/// Each field except `filters` can be overridden per profile with environment variables named
+/// `CROSSCAN_PROFILE_<NAME>_CHANNEL`, `_BACKEND` and `_BITRATE`, where `<NAME>` is the profile
+/// name upper-cased with non-alphanumeric characters replaced by `_`. An empty value clears an
+/// optional field.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profiles {
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Loads profiles from a TOML file and applies environment overrides
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    /// Loads profiles from the file named by `CROSSCAN_PROFILES`
+    pub fn from_env() -> std::io::Result<Self> {
+        let path = std::env::var_os(PROFILES_PATH_VAR).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{} is not set", PROFILES_PATH_VAR),
+            )
+        })?;
+        Self::load(path)
+    }
+
+    /// Parses profiles from TOML text and applies environment overrides
+    pub fn parse(text: &str) -> std::io::Result<Self> {
+        let mut profiles: BTreeMap<String, Profile> =
+            toml::from_str(text).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+
+        for (name, profile) in &mut profiles {
+            profile.apply_env_overrides(name)?;
+        }
+        Ok(Self { profiles })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Returns the profile names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Opens the interface for the profile `name`. See `Profile::open()`
+    pub async fn open<T: CanInterface>(&self, name: &str) -> std::io::Result<T> {
+        let profile = self
+            .get(name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Unknown profile: {}", name)))?;
+        profile.open().await
+    }
+}
+
+fn env_prefix(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}_", PROFILE_VAR_PREFIX, name)
+}
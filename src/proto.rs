@@ -0,0 +1,193 @@
+///
+/// proto.rs
+///
+/// Protobuf messages for frames and interface events, matching proto/crosscan.proto.
+///
+use crate::can;
+
+/// A classic CAN frame
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CanFrame {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: Vec<u8>,
+    #[prost(bool, tag = "3")]
+    pub extended: bool,
+    #[prost(bool, tag = "4")]
+    pub rtr: bool,
+    #[prost(bool, tag = "5")]
+    pub error: bool,
+    #[prost(uint32, tag = "6")]
+    pub dlc: u32,
+    #[prost(uint64, optional, tag = "7")]
+    pub timestamp: Option<u64>,
+}
+
+/// Frames were lost before they could be read
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct FramesDropped {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+
+/// Transmit and receive error counters of the controller
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct ErrorCounters {
+    #[prost(uint32, tag = "1")]
+    pub tx_errors: u32,
+    #[prost(uint32, tag = "2")]
+    pub rx_errors: u32,
+}
+
+/// Something that happened on an interface
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InterfaceEvent {
+    #[prost(oneof = "interface_event::Event", tags = "1, 2, 3")]
+    pub event: Option<interface_event::Event>,
+}
+
+pub mod interface_event {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "1")]
+        Frame(super::CanFrame),
+        #[prost(message, tag = "2")]
+        FramesDropped(super::FramesDropped),
+        #[prost(message, tag = "3")]
+        ErrorCounters(super::ErrorCounters),
+    }
+}
+
+/// Acceptance filter: a frame passes if `frame.id & mask == id & mask`
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct Filter {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(uint32, tag = "2")]
+    pub mask: u32,
+    /// Only match extended (true) or standard (false) frames if set
+    #[prost(bool, optional, tag = "3")]
+    pub extended: Option<bool>,
+}
+
+impl Filter {
+    pub fn matches(&self, frame: &can::CanFrame) -> bool {
+        frame.id() & self.mask == self.id & self.mask
+            && self.extended.is_none_or(|e| e == frame.is_extended())
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubscribeRequest {
+    #[prost(string, tag = "1")]
+    pub channel: String,
+    #[prost(message, repeated, tag = "2")]
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SendRequest {
+    #[prost(string, tag = "1")]
+    pub channel: String,
+    #[prost(message, optional, tag = "2")]
+    pub frame: Option<CanFrame>,
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct SendResponse {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetConfigRequest {
+    #[prost(string, tag = "1")]
+    pub channel: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InterfaceConfig {
+    #[prost(string, tag = "1")]
+    pub channel: String,
+    #[prost(uint32, optional, tag = "2")]
+    pub bitrate: Option<u32>,
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct ListChannelsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListChannelsResponse {
+    #[prost(string, repeated, tag = "1")]
+    pub channels: Vec<String>,
+}
+
+impl From<&can::CanFrame> for CanFrame {
+    fn from(frame: &can::CanFrame) -> Self {
+        let data = if frame.is_rtr() || frame.is_error() {
+            Vec::new()
+        } else {
+            frame.data().to_vec()
+        };
+        Self {
+            id: frame.id(),
+            data,
+            extended: frame.is_extended(),
+            rtr: frame.is_rtr(),
+            error: frame.is_error(),
+            dlc: frame.dlc() as u32,
+            timestamp: frame.timestamp(),
+        }
+    }
+}
+
+impl TryFrom<&CanFrame> for can::CanFrame {
+    type Error = &'static str;
+
+    fn try_from(frame: &CanFrame) -> Result<Self, Self::Error> {
+        let mut converted = if frame.error {
+            can::CanFrame::new_error(frame.id)
+        } else if frame.rtr {
+            can::CanFrame::new_remote(frame.id, frame.dlc as usize, frame.extended)
+        } else if frame.extended {
+            can::CanFrame::new_eff(frame.id, &frame.data)
+        } else {
+            can::CanFrame::new(frame.id, &frame.data)
+        }?;
+        converted.set_timestamp(frame.timestamp);
+        Ok(converted)
+    }
+}
+
+impl From<can::CanErrorCounters> for ErrorCounters {
+    fn from(counters: can::CanErrorCounters) -> Self {
+        Self {
+            tx_errors: counters.tx_errors as u32,
+            rx_errors: counters.rx_errors as u32,
+        }
+    }
+}
+
+impl From<&can::CanFrame> for InterfaceEvent {
+    fn from(frame: &can::CanFrame) -> Self {
+        Self {
+            event: Some(interface_event::Event::Frame(frame.into())),
+        }
+    }
+}
+
+impl From<crate::FramesDropped> for InterfaceEvent {
+    fn from(dropped: crate::FramesDropped) -> Self {
+        Self {
+            event: Some(interface_event::Event::FramesDropped(FramesDropped {
+                count: dropped.0,
+            })),
+        }
+    }
+}
+
+impl From<can::CanErrorCounters> for InterfaceEvent {
+    fn from(counters: can::CanErrorCounters) -> Self {
+        Self {
+            event: Some(interface_event::Event::ErrorCounters(counters.into())),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+///
+/// ros.rs
+///
+/// ROS 2 `can_msgs/msg/Frame` representation and conversions.
+///
+use crate::can::CanFrame;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `builtin_interfaces/msg/Time`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RosTime {
+    pub sec: i32,
+    pub nanosec: u32,
+}
+
+impl From<SystemTime> for RosTime {
+    fn from(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self {
+            sec: since_epoch.as_secs() as i32,
+            nanosec: since_epoch.subsec_nanos(),
+        }
+    }
+}
+
+/// `std_msgs/msg/Header`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RosHeader {
+    pub stamp: RosTime,
+    pub frame_id: String,
+}
+
+/// `can_msgs/msg/Frame`, as used by socketcan_bridge and ros2_socketcan
+///
+/// Field order matches the message definition, so the struct can be (de)serialized with any
+/// serde CDR implementation used by Rust ROS 2 clients.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RosCanFrame {
+    pub header: RosHeader,
+    pub id: u32,
+    pub is_rtr: bool,
+    pub is_extended: bool,
+    pub is_error: bool,
+    pub dlc: u8,
+    pub data: [u8; 8],
+}
+
+impl RosCanFrame {
+    /// Converts a frame, stamping the header with `stamp` and `frame_id`
+    pub fn from_frame(frame: &CanFrame, stamp: SystemTime, frame_id: &str) -> Self {
+        let mut data = [0u8; 8];
+        if !frame.is_rtr() {
+            data[..frame.data().len()].copy_from_slice(frame.data());
+        }
+        Self {
+            header: RosHeader {
+                stamp: stamp.into(),
+                frame_id: frame_id.to_string(),
+            },
+            id: frame.id(),
+            is_rtr: frame.is_rtr(),
+            is_extended: frame.is_extended(),
+            is_error: frame.is_error(),
+            dlc: frame.dlc() as u8,
+            data,
+        }
+    }
+}
+
+impl TryFrom<&RosCanFrame> for CanFrame {
+    type Error = &'static str;
+
+    fn try_from(frame: &RosCanFrame) -> Result<Self, Self::Error> {
+        if frame.is_error {
+            CanFrame::new_error(frame.id)
+        } else if frame.is_rtr {
+            CanFrame::new_remote(frame.id, frame.dlc as usize, frame.is_extended)
+        } else {
+            let data = frame
+                .data
+                .get(..frame.dlc as usize)
+                .ok_or("CAN data must be <= 8 bytes")?;
+            if frame.is_extended {
+                CanFrame::new_eff(frame.id, data)
+            } else {
+                CanFrame::new(frame.id, data)
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+///
+/// series.rs
+///
+/// Downsamples decoded signal values into time buckets for plotting.
+///
+use crate::pipeline::Annotated;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Aggregate of the values of one signal within a time bucket
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SeriesPoint {
+    /// Start of the bucket
+    pub time: SystemTime,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    /// Number of values in the bucket
+    pub count: u64,
+}
+
+struct Bucket {
+    index: u128,
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+/// Aggregates the values of one signal into fixed-length buckets
+///
+/// Buckets are aligned to multiples of the bucket length since the Unix epoch, so series of
+/// different signals with the same rate line up. A bucket is emitted by the first value that falls
+/// into a later bucket; call `flush()` from a timer to emit the last bucket when values stop.
+/// Buckets without values are skipped.
+pub struct SignalSeries {
+    name: String,
+    bucket: Duration,
+    current: Option<Bucket>,
+}
+
+/// Creates a series for `signal` emitting `rate_hz` points per second
+///
+/// Fails with `InvalidInput` if `rate_hz` is not a positive, finite rate with buckets of at least
+/// 1 ns.
+pub fn signal_series(signal: &str, rate_hz: f64) -> std::io::Result<SignalSeries> {
+    let bucket = Duration::try_from_secs_f64(1.0 / rate_hz)
+        .ok()
+        .filter(|bucket| !bucket.is_zero() && rate_hz.is_finite())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid series rate {} Hz", rate_hz),
+            )
+        })?;
+    Ok(SignalSeries::new(signal, bucket))
+}
+
+impl SignalSeries {
+    /// Creates a series for `signal` with buckets of length `bucket`
+    ///
+    /// Panics if `bucket` is zero.
+    pub fn new(signal: &str, bucket: Duration) -> Self {
+        assert!(!bucket.is_zero(), "Bucket length must not be zero");
+        Self {
+            name: signal.to_string(),
+            bucket,
+            current: None,
+        }
+    }
+
+    pub fn signal(&self) -> &str {
+        &self.name
+    }
+
+    pub fn bucket(&self) -> Duration {
+        self.bucket
+    }
+
+    /// Adds the value of the signal in `frame`, if it carries it
+    pub fn observe(&mut self, frame: &Annotated, time: SystemTime) -> Option<SeriesPoint> {
+        let value = frame
+            .signals
+            .iter()
+            .find(|(name, _)| *name == self.name)
+            .map(|(_, value)| *value)?;
+        self.push(value, time)
+    }
+
+    /// Adds a value received at `time`. Returns the previous bucket once `time` is past it.
+    ///
+    /// NaN values are ignored. Values older than the current bucket are counted in it.
+    pub fn push(&mut self, value: f64, time: SystemTime) -> Option<SeriesPoint> {
+        if value.is_nan() {
+            return None;
+        }
+        let index = self.bucket_index(time);
+
+        let finished = match &mut self.current {
+            Some(bucket) if index <= bucket.index => {
+                bucket.min = bucket.min.min(value);
+                bucket.max = bucket.max.max(value);
+                bucket.sum += value;
+                bucket.count += 1;
+                return None;
+            }
+            _ => self.flush(),
+        };
+        self.current = Some(Bucket {
+            index,
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+        });
+        finished
+    }
+
+    /// Emits the current bucket, even if it has not ended yet
+    pub fn flush(&mut self) -> Option<SeriesPoint> {
+        let bucket = self.current.take()?;
+        Some(SeriesPoint {
+            time: self.bucket_start(bucket.index),
+            min: bucket.min,
+            mean: bucket.sum / bucket.count as f64,
+            max: bucket.max,
+            count: bucket.count,
+        })
+    }
+
+    /// Returns when the current bucket ends, e.g. to schedule `flush()`
+    pub fn bucket_end(&self) -> Option<SystemTime> {
+        let bucket = self.current.as_ref()?;
+        Some(self.bucket_start(bucket.index + 1))
+    }
+
+    fn bucket_start(&self, index: u128) -> SystemTime {
+        let nanos = self.bucket.as_nanos() * index;
+        UNIX_EPOCH
+            + Duration::new(
+                (nanos / 1_000_000_000) as u64,
+                (nanos % 1_000_000_000) as u32,
+            )
+    }
+
+    fn bucket_index(&self, time: SystemTime) -> u128 {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        since_epoch.as_nanos() / self.bucket.as_nanos()
+    }
+}
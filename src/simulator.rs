@@ -0,0 +1,214 @@
+///
+/// simulator.rs
+///
+/// Framework for simulating ECUs that respond to frames on a CanInterface.
+///
+use crate::{CanInterface, FramesDropped, can::CanFrame};
+use std::time::Duration;
+
+type Matcher = Box<dyn Fn(&CanFrame) -> bool + Send>;
+type Handler = Box<dyn FnMut(&CanFrame) -> Vec<CanFrame> + Send>;
+
+struct Responder {
+    matcher: Matcher,
+    handler: Handler,
+    delay: Duration,
+}
+
+/// A set of responders that answer received frames, e.g. to stand in for a device on a bench
+#[derive(Default)]
+pub struct Simulator {
+    responders: Vec<Responder>,
+}
+
+/// Builder returned by `Simulator::on()`
+pub struct ResponderBuilder<'a> {
+    simulator: &'a mut Simulator,
+    matcher: Matcher,
+    delay: Duration,
+}
+
+impl ResponderBuilder<'_> {
+    /// Wait `delay` after the request before sending the reply
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Registers the handler producing the reply frames for a matching request
+    ///
+    /// The handler is `FnMut`, so it can keep state between requests. Returning no frames sends nothing.
+    pub fn reply<H>(self, handler: H)
+    where
+        H: FnMut(&CanFrame) -> Vec<CanFrame> + Send + 'static,
+    {
+        self.simulator.responders.push(Responder {
+            matcher: self.matcher,
+            handler: Box::new(handler),
+            delay: self.delay,
+        });
+    }
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts registering a responder for frames that satisfy `matcher`
+    pub fn on<M>(&mut self, matcher: M) -> ResponderBuilder<'_>
+    where
+        M: Fn(&CanFrame) -> bool + Send + 'static,
+    {
+        ResponderBuilder {
+            simulator: self,
+            matcher: Box::new(matcher),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Runs every matching responder for `request` and writes their replies, in registration order
+    pub async fn handle<T: CanInterface>(
+        &mut self,
+        can: &mut T,
+        request: &CanFrame,
+    ) -> std::io::Result<()> {
+        for responder in &mut self.responders {
+            if !(responder.matcher)(request) {
+                continue;
+            }
+            let replies = (responder.handler)(request);
+            if !responder.delay.is_zero() {
+                tokio::time::sleep(responder.delay).await;
+            }
+            can.write_frames(&replies).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads frames from `can` and answers them until an I/O error occurs
+    ///
+    /// Requests are handled one at a time, so a reply delay also delays later requests. Frames the
+    /// interface reports as dropped are not answered and do not stop the simulator.
+    pub async fn run<T: CanInterface>(&mut self, can: &mut T) -> std::io::Result<()> {
+        loop {
+            let request = match can.read_frame().await {
+                Ok(request) => request,
+                Err(e) if e.get_ref().is_some_and(|e| e.is::<FramesDropped>()) => continue,
+                Err(e) => return Err(e),
+            };
+            self.handle(can, &request).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBus;
+    use tokio::time::Instant;
+
+    // A TestBus whose first read reports a loss
+    struct LossyBus {
+        bus: TestBus,
+        dropped: bool,
+    }
+
+    impl CanInterface for LossyBus {
+        async fn open(_interface: &str) -> std::io::Result<Self> {
+            unimplemented!()
+        }
+
+        async fn read_frame(&mut self) -> std::io::Result<CanFrame> {
+            if !std::mem::replace(&mut self.dropped, true) {
+                return Err(std::io::Error::other(FramesDropped(3)));
+            }
+            self.bus.read_frame().await
+        }
+
+        async fn write_frame(&mut self, frame: CanFrame) -> std::io::Result<()> {
+            self.bus.write_frame(frame).await
+        }
+
+        async fn get_bitrate(&mut self) -> std::io::Result<Option<u32>> {
+            Ok(None)
+        }
+    }
+
+    fn echo_simulator() -> Simulator {
+        let mut simulator = Simulator::new();
+        simulator
+            .on(|f| f.id() == 0x7E0)
+            .reply(|f| vec![CanFrame::standard(0x7E8, f.data())]);
+        simulator
+    }
+
+    #[tokio::test]
+    async fn replies_only_to_matching_frames() {
+        let mut simulator = echo_simulator();
+        let mut seen = 0;
+        simulator.on(|f| f.id() == 0x7E0).reply(move |_| {
+            seen += 1;
+            vec![CanFrame::standard(0x100, &[seen])]
+        });
+        let mut bus = TestBus::new();
+
+        simulator
+            .handle(&mut bus, &CanFrame::standard(0x123, &[1]))
+            .await
+            .unwrap();
+        assert!(bus.sent().is_empty());
+
+        for data in [[1], [2]] {
+            simulator
+                .handle(&mut bus, &CanFrame::standard(0x7E0, &data))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            bus.sent(),
+            vec![
+                CanFrame::standard(0x7E8, &[1]),
+                CanFrame::standard(0x100, &[1]),
+                CanFrame::standard(0x7E8, &[2]),
+                CanFrame::standard(0x100, &[2]),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_replies() {
+        let mut simulator = Simulator::new();
+        simulator
+            .on(|_| true)
+            .delay(Duration::from_millis(50))
+            .reply(|_| vec![CanFrame::standard(0x1, &[])]);
+        let mut bus = TestBus::new();
+
+        let start = Instant::now();
+        simulator
+            .handle(&mut bus, &CanFrame::standard(0x2, &[]))
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+        assert_eq!(bus.sent(), vec![CanFrame::standard(0x1, &[])]);
+    }
+
+    #[tokio::test]
+    async fn run_survives_dropped_frames() {
+        let bus = TestBus::new();
+        let mut lossy = LossyBus {
+            bus: bus.clone(),
+            dropped: false,
+        };
+        let task = tokio::spawn(async move { echo_simulator().run(&mut lossy).await });
+
+        bus.inject(CanFrame::standard(0x7E0, &[9]));
+        while bus.sent().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert!(!task.is_finished());
+        assert_eq!(bus.sent(), vec![CanFrame::standard(0x7E8, &[9])]);
+        task.abort();
+    }
+}
@@ -0,0 +1,177 @@
+///
+/// slcan.rs
+///
+/// Lawicel/SLCAN ASCII protocol codec, usable over any byte transport.
+///
+use crate::can::{CanFrame, Strictness};
+use std::io::{Error as IoError, ErrorKind};
+
+/// Terminator of every SLCAN command and frame
+pub const SLCAN_TERMINATOR: char = '\r';
+
+/// Bitrates selected by the `S0`-`S8` setup commands
+const BITRATES: [u32; 9] = [
+    10_000, 20_000, 50_000, 100_000, 125_000, 250_000, 500_000, 800_000, 1_000_000,
+];
+
+fn invalid(message: impl Into<String>) -> IoError {
+    IoError::new(ErrorKind::InvalidData, message.into())
+}
+
+/// Encodes a frame as SLCAN text, e.g. `t1230311AABB`, without the trailing `\r`
+pub fn encode(frame: &CanFrame) -> std::io::Result<String> {
+    if frame.is_error() {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Error frames have no SLCAN representation",
+        ));
+    }
+    let mut text = match (frame.is_rtr(), frame.is_extended()) {
+        (false, false) => format!("t{:03X}", frame.id()),
+        (false, true) => format!("T{:08X}", frame.id()),
+        (true, false) => format!("r{:03X}", frame.id()),
+        (true, true) => format!("R{:08X}", frame.id()),
+    };
+    let dlc = frame.len8_dlc().map_or(frame.dlc(), usize::from);
+    text.push_str(&format!("{:X}", dlc));
+    if !frame.is_rtr() {
+        for byte in frame.data() {
+            text.push_str(&format!("{:02X}", byte));
+        }
+    }
+    Ok(text)
+}
+
+/// Encodes a frame followed by a Lawicel timestamp (milliseconds, wrapping at 60000)
+pub fn encode_with_timestamp(frame: &CanFrame, millis: u16) -> std::io::Result<String> {
+    Ok(format!("{}{:04X}", encode(frame)?, millis % 60_000))
+}
+
+/// Decodes an SLCAN frame such as `t1230311AABB` or `R1234567880`
+///
+/// A trailing `\r` and a 4 digit timestamp are accepted and ignored. CAN FD frames (`d`, `D`,
+/// `b`, `B`) are rejected because CanFrame is classic CAN only.
+pub fn decode(text: &str) -> std::io::Result<CanFrame> {
+    decode_with(text, Strictness::Strict)
+}
+
+/// Like `decode()`, with `Strictness::Preserve` accepting a DLC of 9 to F, followed by 8 data
+/// bytes, which is kept as `CanFrame::len8_dlc()`
+pub fn decode_with(text: &str, strictness: Strictness) -> std::io::Result<CanFrame> {
+    let text = text.trim_end_matches(SLCAN_TERMINATOR);
+    if !text.is_ascii() {
+        return Err(invalid("SLCAN frames are ASCII only"));
+    }
+    let (kind, rest) = text
+        .split_at_checked(1)
+        .ok_or_else(|| invalid("Empty SLCAN frame"))?;
+    let (extended, rtr) = match kind {
+        "t" => (false, false),
+        "T" => (true, false),
+        "r" => (false, true),
+        "R" => (true, true),
+        "d" | "D" | "b" | "B" => {
+            return Err(invalid("SLCAN CAN FD frames are not supported"));
+        }
+        _ => return Err(invalid(format!("Unknown SLCAN frame type {:?}", kind))),
+    };
+
+    let id_len = if extended { 8 } else { 3 };
+    // from_str_radix alone would also accept a leading `+`
+    let hex = |digits: &str| {
+        let invalid_digits = || invalid(format!("Invalid hex digits {:?} in SLCAN frame", digits));
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid_digits());
+        }
+        u32::from_str_radix(digits, 16).map_err(|_| invalid_digits())
+    };
+    let truncated = || invalid(format!("Truncated SLCAN frame {:?}", text));
+
+    let id = hex(rest.get(..id_len).ok_or_else(truncated)?)?;
+    let dlc_digit = rest.get(id_len..id_len + 1).ok_or_else(truncated)?;
+    let (dlc, len8_dlc) = match (strictness, u8::from_str_radix(dlc_digit, 16)) {
+        (Strictness::Preserve, Ok(dlc @ 9..=15)) => (8, Some(dlc)),
+        (_, Ok(dlc @ 0..=8)) => (dlc as usize, None),
+        _ => return Err(invalid(format!("Invalid SLCAN DLC {:?}", dlc_digit))),
+    };
+    let data_end = id_len + 1 + if rtr { 0 } else { dlc * 2 };
+    let data_hex = rest.get(id_len + 1..data_end).ok_or_else(truncated)?;
+    match rest.len() - data_end {
+        // Optional timestamp
+        0 => {}
+        4 => {
+            hex(&rest[data_end..])?;
+        }
+        _ => {
+            return Err(invalid(format!(
+                "Unexpected length of SLCAN frame {:?}",
+                text
+            )));
+        }
+    }
+
+    let frame = if rtr {
+        CanFrame::new_remote(id, dlc, extended)
+    } else {
+        let data = (0..dlc)
+            .map(|i| hex(&data_hex[i * 2..i * 2 + 2]).map(|b| b as u8))
+            .collect::<std::io::Result<Vec<u8>>>()?;
+        if extended {
+            CanFrame::new_eff(id, &data)
+        } else {
+            CanFrame::new(id, &data)
+        }
+    };
+    let mut frame = frame.map_err(invalid)?;
+    frame.set_len8_dlc(len8_dlc).map_err(invalid)?;
+    Ok(frame)
+}
+
+/// Returns the `Sn` setup command selecting `bitrate`, if it is one of the standard rates
+pub fn bitrate_command(bitrate: u32) -> Option<String> {
+    BITRATES
+        .iter()
+        .position(|b| *b == bitrate)
+        .map(|i| format!("S{}", i))
+}
+
+/// Returns the bitrate selected by an `Sn` setup command
+pub fn parse_bitrate_command(command: &str) -> Option<u32> {
+    let index: usize = command
+        .trim_end_matches(SLCAN_TERMINATOR)
+        .strip_prefix('S')?
+        .parse()
+        .ok()?;
+    BITRATES.get(index).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_frames() {
+        assert_eq!(
+            decode("t1232ABCD\r").unwrap(),
+            CanFrame::standard(0x123, &[0xAB, 0xCD])
+        );
+        assert_eq!(
+            decode("T01ABCDEF1011234").unwrap(),
+            CanFrame::extended(0x1ABCDEF, &[0x01])
+        );
+        assert_eq!(decode("r1233").unwrap(), CanFrame::remote(0x123, 3, false));
+    }
+
+    #[test]
+    fn rejects_signs_and_non_hex_digits() {
+        for text in [
+            "t+1201AB",
+            "t1202+1AB",
+            "t12010G",
+            "t1201AB+123",
+            "t 1201AB",
+        ] {
+            assert!(decode(text).is_err(), "{:?} was accepted", text);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+///
+/// spsc.rs
+///
+/// Fixed-capacity lock-free single-producer single-consumer ring, and a reader that fills it.
+///
+use crate::{CanInterface, FramesDropped, can::CanFrame};
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Keeps the indices written by the two ends on separate cache lines
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    // Count of values read, only written by the consumer
+    head: CachePadded<AtomicUsize>,
+    // Count of values written, only written by the producer
+    tail: CachePadded<AtomicUsize>,
+    // Set when either end is dropped
+    closed: AtomicBool,
+}
+
+// SAFETY: the producer only writes a slot before publishing it by advancing `tail`, and the
+// consumer only reads a slot after observing that and before releasing it by advancing `head`,
+// so a slot is never accessed from both ends at once
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index & self.mask].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let head = *self.head.0.get_mut();
+        let tail = *self.tail.0.get_mut();
+        let mut index = head;
+        while index != tail {
+            // SAFETY: slots between head and tail were written and not yet read
+            unsafe { (*self.slot(index)).assume_init_drop() };
+            index = index.wrapping_add(1);
+        }
+    }
+}
+
+/// Creates a ring holding at least `capacity` values, rounded up to a power of two
+///
+/// Panics if `capacity` is 0.
+pub fn ring<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "Ring capacity must not be 0");
+    let capacity = capacity.next_power_of_two();
+    let ring = Arc::new(Ring {
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        mask: capacity - 1,
+        head: CachePadded(AtomicUsize::new(0)),
+        tail: CachePadded(AtomicUsize::new(0)),
+        closed: AtomicBool::new(false),
+    });
+    (
+        Producer {
+            ring: ring.clone(),
+            cached_head: 0,
+        },
+        Consumer {
+            ring,
+            cached_tail: 0,
+        },
+    )
+}
+
+/// Writing end of a `ring()`
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+    // Last head seen, so a push only reads the consumer's index when the ring looks full
+    cached_head: usize,
+}
+
+impl<T> Producer<T> {
+    /// Appends `value`, or returns it if the ring is full. Never blocks.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.ring.tail.0.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.cached_head) > self.ring.mask {
+            self.cached_head = self.ring.head.0.load(Ordering::Acquire);
+            if tail.wrapping_sub(self.cached_head) > self.ring.mask {
+                return Err(value);
+            }
+        }
+        // SAFETY: the slot is free, the consumer released it before advancing head past it
+        unsafe { (*self.ring.slot(tail)).write(value) };
+        self.ring
+            .tail
+            .0
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the number of slots of the ring
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    /// Returns true once the consumer was dropped
+    pub fn is_closed(&self) -> bool {
+        self.ring.closed.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+/// Reading end of a `ring()`
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+    // Last tail seen, so a pop only reads the producer's index when the ring looks empty
+    cached_tail: usize,
+}
+
+impl<T> Consumer<T> {
+    /// Removes the oldest value, or returns None if the ring is empty. Never blocks.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.0.load(Ordering::Relaxed);
+        if head == self.cached_tail {
+            self.cached_tail = self.ring.tail.0.load(Ordering::Acquire);
+            if head == self.cached_tail {
+                return None;
+            }
+        }
+        // SAFETY: the producer wrote the slot before advancing tail past it
+        let value = unsafe { (*self.ring.slot(head)).assume_init_read() };
+        self.ring
+            .head
+            .0
+            .store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Returns the number of values waiting in the ring
+    pub fn len(&self) -> usize {
+        let tail = self.ring.tail.0.load(Ordering::Acquire);
+        tail.wrapping_sub(self.ring.head.0.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of slots of the ring
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    /// Returns true once the producer was dropped. Values pushed before are still returned.
+    pub fn is_closed(&self) -> bool {
+        self.ring.closed.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+#[derive(Default)]
+struct ReaderShared {
+    dropped: AtomicU64,
+    error: Mutex<Option<std::io::Error>>,
+}
+
+/// Reads an interface into a lock-free ring, for a single latency-critical consumer
+///
+/// A task on the tokio runtime reads the interface and pushes the frames into the ring. The
+/// consumer, typically a control loop on its own thread, polls with `try_read()` or spins with
+/// `read_spin()` without ever parking or touching a tokio channel. When the ring is full, new
+/// frames are dropped and counted in `dropped()`, together with losses reported by the interface.
+///
+/// `benches/spsc_latency.rs` compares the receive latency against a tokio mpsc channel.
+pub struct FastReader {
+    consumer: Consumer<CanFrame>,
+    shared: Arc<ReaderShared>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl FastReader {
+    /// Starts reading `can` into a ring of at least `capacity` frames
+    ///
+    /// Must be called from within a tokio runtime. Panics if `capacity` is 0.
+    pub fn spawn<T: CanInterface + Send + 'static>(mut can: T, capacity: usize) -> Self {
+        let (mut producer, consumer) = ring(capacity);
+        let shared = Arc::new(ReaderShared::default());
+        let task_shared = shared.clone();
+        let task = tokio::spawn(async move {
+            while !producer.is_closed() {
+                match can.read_frames().await {
+                    Ok(frames) => {
+                        let mut lost = 0;
+                        for frame in frames {
+                            if producer.push(frame).is_err() {
+                                lost += 1;
+                            }
+                        }
+                        task_shared.dropped.fetch_add(lost, Ordering::Relaxed);
+                    }
+                    Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<FramesDropped>()) {
+                        Some(FramesDropped(count)) => {
+                            task_shared.dropped.fetch_add(*count, Ordering::Relaxed);
+                        }
+                        None => {
+                            *task_shared.error.lock().unwrap() = Some(e);
+                            return;
+                        }
+                    },
+                }
+            }
+        });
+        Self {
+            consumer,
+            shared,
+            task,
+        }
+    }
+
+    /// Returns the next frame, or None if none is waiting. Never blocks.
+    ///
+    /// Once the reader stopped and every frame before was returned, returns the error that
+    /// stopped it.
+    pub fn try_read(&mut self) -> std::io::Result<Option<CanFrame>> {
+        if let Some(frame) = self.consumer.pop() {
+            return Ok(Some(frame));
+        }
+        if !self.consumer.is_closed() {
+            return Ok(None);
+        }
+        // The reader may have pushed more frames between the pop and its exit
+        if let Some(frame) = self.consumer.pop() {
+            return Ok(Some(frame));
+        }
+        Err(self.shared.error.lock().unwrap().take().unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Reader stopped")
+        }))
+    }
+
+    /// Busy-waits for the next frame for at most `timeout`
+    ///
+    /// Keeps the calling thread spinning, so only use it on a thread that has a core to itself.
+    pub fn read_spin(&mut self, timeout: Duration) -> std::io::Result<Option<CanFrame>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(frame) = self.try_read()? {
+                return Ok(Some(frame));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Returns the number of frames waiting in the ring
+    pub fn len(&self) -> usize {
+        self.consumer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+
+    /// Returns the number of frames lost because the ring was full or the interface dropped them
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for FastReader {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,445 @@
+///
+/// store.rs
+///
+/// Persistent, searchable frame archive in a SQLite database.
+///
+use crate::{
+    can::CanFrame,
+    export::{from_unix_micros, unix_micros},
+    pipeline::Annotated,
+};
+use rusqlite::{Connection, ToSql, params};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Number of frames written per transaction
+pub const DEFAULT_BATCH_LEN: usize = 1000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS frames (
+        time INTEGER NOT NULL,
+        channel TEXT,
+        id INTEGER NOT NULL,
+        extended INTEGER NOT NULL,
+        rtr INTEGER NOT NULL,
+        error INTEGER NOT NULL,
+        dlc INTEGER NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS frames_time ON frames (time);
+    CREATE INDEX IF NOT EXISTS frames_id ON frames (id, extended, time);
+    CREATE INDEX IF NOT EXISTS frames_channel ON frames (channel, time);
+";
+
+fn to_io_error(e: rusqlite::Error) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+/// A frame read back from a FrameStore
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredFrame {
+    pub time: SystemTime,
+    pub channel: Option<String>,
+    pub frame: CanFrame,
+}
+
+/// Selects frames in `FrameStore::query()` and `FrameStore::count()`
+///
+/// Conditions are combined, so `FrameQuery::new().with_id(0x123, false).with_channel("can0")`
+/// matches frames with ID 0x123 received on can0. Without conditions every frame matches.
+#[derive(Clone, Debug, Default)]
+pub struct FrameQuery {
+    id: Option<(u32, bool)>,
+    channel: Option<String>,
+    start: Option<SystemTime>,
+    end: Option<SystemTime>,
+    limit: Option<usize>,
+    newest_first: bool,
+}
+
+impl FrameQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches frames with this ID only
+    pub fn with_id(mut self, id: u32, extended: bool) -> Self {
+        self.id = Some((id, extended));
+        self
+    }
+
+    /// Matches frames received on this channel only
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    /// Matches frames received at or after `start`
+    pub fn with_start(mut self, start: SystemTime) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Matches frames received before `end`
+    pub fn with_end(mut self, end: SystemTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Returns at most `limit` frames. Ignored by `count()`
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns the newest frames first instead of the oldest, e.g. with `with_limit()` for the
+    /// latest frames
+    pub fn newest_first(mut self) -> Self {
+        self.newest_first = true;
+        self
+    }
+
+    // WHERE clause and its parameters
+    fn filter(&self) -> (String, Vec<Box<dyn ToSql>>) {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some((id, extended)) = self.id {
+            conditions.push("id = ? AND extended = ?");
+            values.push(Box::new(id));
+            values.push(Box::new(extended));
+        }
+        if let Some(channel) = &self.channel {
+            conditions.push("channel = ?");
+            values.push(Box::new(channel.clone()));
+        }
+        if let Some(start) = self.start {
+            conditions.push("time >= ?");
+            values.push(Box::new(unix_micros(start)));
+        }
+        if let Some(end) = self.end {
+            conditions.push("time < ?");
+            values.push(Box::new(unix_micros(end)));
+        }
+        if conditions.is_empty() {
+            return (String::new(), values);
+        }
+        (format!(" WHERE {}", conditions.join(" AND ")), values)
+    }
+}
+
+/// Writes frames to a SQLite database and searches them
+///
+/// Frames go into the table `frames` with the columns `time` (microseconds since the Unix epoch),
+/// `channel` (text or NULL), `id`, `extended`, `rtr`, `error`, `dlc` and `data` (blob), indexed by
+/// time, by ID and by channel. Other tools can read the database while it is written.
+///
+/// Frames are written in transactions of `DEFAULT_BATCH_LEN` frames, so the last frames are only
+/// visible to other connections after the batch is full or `flush()` was called. Dropping the
+/// store flushes it, ignoring errors.
+pub struct FrameStore {
+    conn: Connection,
+    batch_len: usize,
+    pending: usize,
+}
+
+impl FrameStore {
+    /// Opens or creates the database file at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let conn = Connection::open(path).map_err(to_io_error)?;
+        // Lets readers search the archive while frames are written
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(to_io_error)?;
+        Self::with_connection(conn)
+    }
+
+    /// Creates a database in memory that is lost when the store is dropped
+    pub fn open_in_memory() -> std::io::Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(to_io_error)?)
+    }
+
+    fn with_connection(conn: Connection) -> std::io::Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(to_io_error)?;
+        Ok(Self {
+            conn,
+            batch_len: DEFAULT_BATCH_LEN,
+            pending: 0,
+        })
+    }
+
+    /// Sets the number of frames written per transaction. Larger batches write faster
+    pub fn with_batch_len(mut self, batch_len: usize) -> Self {
+        self.batch_len = batch_len.max(1);
+        self
+    }
+
+    /// Writes a frame received at `time`
+    pub fn write_frame(&mut self, frame: &CanFrame, time: SystemTime) -> std::io::Result<()> {
+        self.write_row(frame, None, time)
+    }
+
+    /// Writes a frame together with its channel. Decoded signals are not stored
+    pub fn write_annotated(&mut self, frame: &Annotated, time: SystemTime) -> std::io::Result<()> {
+        self.write_row(&frame.frame, frame.channel.as_deref(), time)
+    }
+
+    /// Commits the frames written so far
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT").map_err(to_io_error)?;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+
+    fn write_row(
+        &mut self,
+        frame: &CanFrame,
+        channel: Option<&str>,
+        time: SystemTime,
+    ) -> std::io::Result<()> {
+        if self.conn.is_autocommit() {
+            self.conn.execute_batch("BEGIN").map_err(to_io_error)?;
+        }
+        self.conn
+            .prepare_cached(
+                "INSERT INTO frames (time, channel, id, extended, rtr, error, dlc, data) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .and_then(|mut insert| {
+                insert.execute(params![
+                    unix_micros(time),
+                    channel,
+                    frame.id(),
+                    frame.is_extended(),
+                    frame.is_rtr(),
+                    frame.is_error(),
+                    frame.dlc() as u8,
+                    frame.data(),
+                ])
+            })
+            .map_err(to_io_error)?;
+        self.pending += 1;
+        if self.pending >= self.batch_len {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the frames matching `query`, in the order they were received
+    ///
+    /// Includes frames written but not yet flushed.
+    pub fn query(&self, query: &FrameQuery) -> std::io::Result<Vec<StoredFrame>> {
+        let (filter, mut values) = query.filter();
+        let order = if query.newest_first { "DESC" } else { "ASC" };
+        let mut sql = format!(
+            "SELECT time, channel, id, extended, rtr, error, dlc, data FROM frames{} \
+             ORDER BY time {}, rowid {}",
+            filter, order, order
+        );
+        if let Some(limit) = query.limit {
+            sql.push_str(" LIMIT ?");
+            values.push(Box::new(limit as i64));
+        }
+
+        let mut statement = self.conn.prepare(&sql).map_err(to_io_error)?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, u32>(2)?,
+                    row.get::<_, bool>(3)?,
+                    row.get::<_, bool>(4)?,
+                    row.get::<_, bool>(5)?,
+                    row.get::<_, u8>(6)? as usize,
+                    row.get::<_, Vec<u8>>(7)?,
+                ))
+            })
+            .map_err(to_io_error)?;
+
+        rows.map(|row| {
+            let (time, channel, id, extended, rtr, error, dlc, data) = row.map_err(to_io_error)?;
+            let frame = if error {
+                CanFrame::new_error(id)
+            } else if rtr {
+                CanFrame::new_remote(id, dlc, extended)
+            } else if extended {
+                CanFrame::new_eff(id, &data)
+            } else {
+                CanFrame::new(id, &data)
+            }
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            Ok(StoredFrame {
+                time: from_unix_micros(time),
+                channel,
+                frame,
+            })
+        })
+        .collect()
+    }
+
+    /// Returns the number of frames matching `query`
+    pub fn count(&self, query: &FrameQuery) -> std::io::Result<u64> {
+        let (filter, values) = query.filter();
+        self.conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM frames{}", filter),
+                rusqlite::params_from_iter(values.iter()),
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as u64)
+            .map_err(to_io_error)
+    }
+
+    /// Returns every ID in the store with its number of frames, standard IDs first, each in
+    /// ascending order
+    pub fn ids(&self) -> std::io::Result<Vec<(u32, bool, u64)>> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT id, extended, COUNT(*) FROM frames WHERE error = 0 \
+                 GROUP BY extended, id ORDER BY extended, id",
+            )
+            .map_err(to_io_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get::<_, i64>(2)? as u64))
+            })
+            .map_err(to_io_error)?;
+        rows.map(|row| row.map_err(to_io_error)).collect()
+    }
+
+    /// Returns the names of all channels in the store, in ascending order
+    pub fn channels(&self) -> std::io::Result<Vec<String>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT DISTINCT channel FROM frames WHERE channel IS NOT NULL ORDER BY 1")
+            .map_err(to_io_error)?;
+        let rows = statement
+            .query_map([], |row| row.get(0))
+            .map_err(to_io_error)?;
+        rows.map(|row| row.map_err(to_io_error)).collect()
+    }
+}
+
+impl Drop for FrameStore {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    // Frames 0x100 at 10, 0x200 at 20 and 0x100 extended at 30 on can0, 0x100 at 40 on can1
+    fn store() -> FrameStore {
+        let mut store = FrameStore::open_in_memory().unwrap();
+        let write = |store: &mut FrameStore, frame: CanFrame, channel: &str, secs| {
+            let mut annotated = Annotated::new(frame);
+            annotated.channel = Some(channel.into());
+            store.write_annotated(&annotated, at(secs)).unwrap();
+        };
+        write(&mut store, CanFrame::new(0x100, &[1]).unwrap(), "can0", 10);
+        write(&mut store, CanFrame::new(0x200, &[2]).unwrap(), "can0", 20);
+        write(
+            &mut store,
+            CanFrame::new_eff(0x100, &[3]).unwrap(),
+            "can0",
+            30,
+        );
+        write(&mut store, CanFrame::new(0x100, &[4]).unwrap(), "can1", 40);
+        store
+    }
+
+    fn payloads(store: &FrameStore, query: &FrameQuery) -> Vec<u8> {
+        store
+            .query(query)
+            .unwrap()
+            .iter()
+            .map(|stored| stored.frame.data()[0])
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_frames() {
+        let store = store();
+        let frames = store.query(&FrameQuery::new()).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[2].time, at(30));
+        assert_eq!(frames[2].channel.as_deref(), Some("can0"));
+        assert_eq!(frames[2].frame, CanFrame::new_eff(0x100, &[3]).unwrap());
+    }
+
+    #[test]
+    fn filters_by_id_and_extended_flag() {
+        let store = store();
+        assert_eq!(
+            payloads(&store, &FrameQuery::new().with_id(0x100, false)),
+            vec![1, 4]
+        );
+        assert_eq!(
+            payloads(&store, &FrameQuery::new().with_id(0x100, true)),
+            vec![3]
+        );
+        assert!(payloads(&store, &FrameQuery::new().with_id(0x300, false)).is_empty());
+    }
+
+    #[test]
+    fn filters_by_channel_and_time() {
+        let store = store();
+        assert_eq!(
+            payloads(&store, &FrameQuery::new().with_channel("can1")),
+            vec![4]
+        );
+        let range = FrameQuery::new().with_start(at(20)).with_end(at(40));
+        assert_eq!(payloads(&store, &range), vec![2, 3]);
+        let combined = FrameQuery::new()
+            .with_id(0x100, false)
+            .with_channel("can0")
+            .with_end(at(40));
+        assert_eq!(payloads(&store, &combined), vec![1]);
+    }
+
+    #[test]
+    fn orders_and_limits() {
+        let store = store();
+        let latest = FrameQuery::new().newest_first().with_limit(2);
+        assert_eq!(payloads(&store, &latest), vec![4, 3]);
+        assert_eq!(payloads(&store, &FrameQuery::new().with_limit(1)), vec![1]);
+        // count() ignores the limit
+        assert_eq!(store.count(&latest).unwrap(), 4);
+        assert_eq!(
+            store
+                .count(&FrameQuery::new().with_channel("can0"))
+                .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn lists_ids_and_channels() {
+        let store = store();
+        assert_eq!(
+            store.ids().unwrap(),
+            vec![(0x100, false, 2), (0x200, false, 1), (0x100, true, 1)]
+        );
+        assert_eq!(store.channels().unwrap(), vec!["can0", "can1"]);
+    }
+
+    #[test]
+    fn times_before_the_epoch() {
+        let mut store = FrameStore::open_in_memory().unwrap();
+        let before = UNIX_EPOCH - Duration::from_micros(1500);
+        store
+            .write_frame(&CanFrame::new(0x1, &[]).unwrap(), before)
+            .unwrap();
+        let query = FrameQuery::new().with_end(UNIX_EPOCH);
+        assert_eq!(store.query(&query).unwrap()[0].time, before);
+    }
+}
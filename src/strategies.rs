@@ -0,0 +1,69 @@
+///
+/// strategies.rs
+///
+/// Proptest strategies generating valid CAN frames, biased towards boundary values.
+///
+use crate::can::CanFrame;
+use proptest::prelude::*;
+
+/// Standard (11-bit) IDs, including 0 and 0x7FF
+pub fn standard_id() -> impl Strategy<Value = u32> {
+    prop_oneof![Just(0), Just(0x7FF), 0..=0x7FFu32]
+}
+
+/// Extended (29-bit) IDs, including 0 and 0x1FFFFFFF
+pub fn extended_id() -> impl Strategy<Value = u32> {
+    prop_oneof![Just(0), Just(0x1FFF_FFFF), 0..=0x1FFF_FFFFu32]
+}
+
+/// Payloads of 0 to 8 bytes, including empty, all-zero and all-0xFF full payloads
+pub fn payload() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        Just(Vec::new()),
+        Just(vec![0x00; 8]),
+        Just(vec![0xFF; 8]),
+        proptest::collection::vec(any::<u8>(), 0..=8),
+    ]
+}
+
+/// Data frames with standard or extended IDs
+pub fn data_frame() -> impl Strategy<Value = CanFrame> {
+    prop_oneof![
+        (standard_id(), payload()).prop_map(|(id, data)| CanFrame::new(id, &data).unwrap()),
+        (extended_id(), payload()).prop_map(|(id, data)| CanFrame::new_eff(id, &data).unwrap()),
+    ]
+}
+
+/// Remote frames with standard or extended IDs and a DLC of 0 to 8
+pub fn remote_frame() -> impl Strategy<Value = CanFrame> {
+    prop_oneof![
+        (standard_id(), 0..=8usize)
+            .prop_map(|(id, dlc)| CanFrame::new_remote(id, dlc, false).unwrap()),
+        (extended_id(), 0..=8usize)
+            .prop_map(|(id, dlc)| CanFrame::new_remote(id, dlc, true).unwrap()),
+    ]
+}
+
+/// Error frames with arbitrary error class bits
+pub fn error_frame() -> impl Strategy<Value = CanFrame> {
+    extended_id().prop_map(|id| CanFrame::new_error(id).unwrap())
+}
+
+/// Frames of every kind, mostly data frames, with or without a timestamp
+pub fn frame() -> impl Strategy<Value = CanFrame> {
+    let frame = prop_oneof![8 => data_frame(), 1 => remote_frame(), 1 => error_frame()];
+    (frame, proptest::option::of(any::<u64>())).prop_map(|(mut frame, timestamp)| {
+        frame.set_timestamp(timestamp);
+        frame
+    })
+}
+
+/// Makes `any::<CanFrame>()` generate `frame()`
+impl Arbitrary for CanFrame {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        frame().boxed()
+    }
+}
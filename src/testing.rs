@@ -0,0 +1,322 @@
+///
+/// testing.rs
+///
+/// Helpers for testing code built on CanInterface, in memory or against real interfaces.
+///
+use crate::{
+    CanInterface,
+    can::{CAN_MTU, CanErrorCounters, CanFrame, InterfaceInfo},
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+#[cfg(target_os = "linux")]
+pub use linux::{VirtualCan, create_vcan};
+
+#[derive(Default)]
+struct TestBusState {
+    sent: Vec<CanFrame>,
+    incoming: VecDeque<CanFrame>,
+}
+
+/// An in-memory CanInterface that records every frame written by the code under test
+///
+/// Clones share the same bus, so a test can keep one handle while handing another to the code
+/// under test. Frames passed to `inject()` are returned by `read_frame()`.
+#[derive(Clone, Default)]
+pub struct TestBus {
+    state: Arc<Mutex<TestBusState>>,
+    incoming: Arc<Notify>,
+    sent: Arc<Notify>,
+}
+
+impl TestBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `frame` available to the next `read_frame()` call
+    pub fn inject(&self, frame: CanFrame) {
+        self.state.lock().unwrap().incoming.push_back(frame);
+        self.incoming.notify_one();
+    }
+
+    /// Returns all frames written so far, oldest first
+    pub fn sent(&self) -> Vec<CanFrame> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Forgets all frames written so far
+    pub fn clear_sent(&self) {
+        self.state.lock().unwrap().sent.clear();
+    }
+
+    /// Panics unless at least one written frame satisfies `matcher`
+    #[track_caller]
+    pub fn assert_sent<F: Fn(&CanFrame) -> bool>(&self, matcher: F) {
+        let sent = self.sent();
+        assert!(
+            sent.iter().any(matcher),
+            "No matching frame was sent. Sent frames: {:#?}",
+            sent
+        );
+    }
+
+    /// Panics unless the `expected` frames were written in this order
+    ///
+    /// Other frames may be interleaved between the expected ones.
+    #[track_caller]
+    pub fn assert_sequence(&self, expected: &[CanFrame]) {
+        let sent = self.sent();
+        let mut remaining = sent.iter();
+        for (i, frame) in expected.iter().enumerate() {
+            assert!(
+                remaining.any(|s| s == frame),
+                "Expected frame {} of the sequence was not sent in order: {:?}\nSent frames: {:#?}",
+                i,
+                frame,
+                sent
+            );
+        }
+    }
+
+    /// Waits for `duration` and panics if any frame is written in the meantime
+    #[track_caller]
+    pub fn expect_no_tx_for(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        let caller = std::panic::Location::caller();
+        let before = self.state.lock().unwrap().sent.len();
+        let bus = self.clone();
+        async move {
+            let deadline = tokio::time::Instant::now() + duration;
+            loop {
+                let sent = bus.sent.notified();
+                let frames = bus.sent();
+                if frames.len() > before {
+                    panic!(
+                        "Unexpected frame sent within {:?} ({}): {:?}",
+                        duration, caller, frames[before]
+                    );
+                }
+                if tokio::time::timeout_at(deadline, sent).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl CanInterface for TestBus {
+    /// Creates a new, empty bus. The interface name is ignored.
+    async fn open(_interface: &str) -> std::io::Result<Self> {
+        Ok(Self::new())
+    }
+
+    async fn read_frame(&mut self) -> std::io::Result<CanFrame> {
+        loop {
+            let notified = self.incoming.notified();
+            if let Some(frame) = self.state.lock().unwrap().incoming.pop_front() {
+                return Ok(frame);
+            }
+            notified.await;
+        }
+    }
+
+    async fn read_frames(&mut self) -> std::io::Result<Vec<CanFrame>> {
+        let first = self.read_frame().await?;
+        let mut state = self.state.lock().unwrap();
+        Ok(std::iter::once(first)
+            .chain(state.incoming.drain(..))
+            .collect())
+    }
+
+    async fn write_frame(&mut self, frame: CanFrame) -> std::io::Result<()> {
+        self.state.lock().unwrap().sent.push(frame);
+        self.sent.notify_waiters();
+        Ok(())
+    }
+
+    async fn write_frames(&mut self, frames: &[CanFrame]) -> std::io::Result<()> {
+        self.state.lock().unwrap().sent.extend_from_slice(frames);
+        self.sent.notify_waiters();
+        Ok(())
+    }
+
+    async fn get_bitrate(&mut self) -> std::io::Result<Option<u32>> {
+        Ok(None)
+    }
+
+    async fn get_error_counters(&mut self) -> std::io::Result<Option<CanErrorCounters>> {
+        Ok(None)
+    }
+
+    async fn get_info(&mut self) -> std::io::Result<InterfaceInfo> {
+        Ok(InterfaceInfo {
+            driver: Some("testbus".to_string()),
+            mtu: Some(CAN_MTU),
+            ..InterfaceInfo::default()
+        })
+    }
+
+    async fn tx_pending(&mut self) -> std::io::Result<usize> {
+        Ok(0)
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use socketcan::nl;
+    use std::io::{Error as IoError, ErrorKind};
+
+    // Bit number of CAP_NET_ADMIN in the capability sets
+    const CAP_NET_ADMIN: u32 = 12;
+
+    /// A virtual SocketCAN interface that is deleted again when dropped
+    pub struct VirtualCan {
+        name: String,
+        iface: Option<nl::CanInterface>,
+    }
+
+    impl VirtualCan {
+        /// Name of the interface, to be passed to `LinuxCan::open`
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    impl Drop for VirtualCan {
+        fn drop(&mut self) {
+            if let Some(iface) = self.iface.take() {
+                let _ = iface.delete();
+            }
+        }
+    }
+
+    /// Creates and brings up a virtual CAN interface named `name`
+    ///
+    /// Requires CAP_NET_ADMIN and the vcan kernel module. The interface is removed when the returned
+    /// guard is dropped.
+    pub fn create_vcan(name: &str) -> std::io::Result<VirtualCan> {
+        if name.is_empty() || name.len() >= libc::IFNAMSIZ {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Interface name must be between 1 and 15 characters",
+            ));
+        }
+        if !has_net_admin()? {
+            return Err(IoError::new(
+                ErrorKind::PermissionDenied,
+                "Creating a vcan interface requires CAP_NET_ADMIN",
+            ));
+        }
+
+        let iface = nl::CanInterface::create_vcan(name, None).map_err(|e| {
+            IoError::other(format!("Failed to create vcan interface {:?}: {}", name, e))
+        })?;
+        let vcan = VirtualCan {
+            name: name.to_string(),
+            iface: Some(iface),
+        };
+
+        if let Some(iface) = &vcan.iface {
+            iface.bring_up().map_err(|e| {
+                IoError::other(format!(
+                    "Failed to bring up vcan interface {:?}: {}",
+                    name, e
+                ))
+            })?;
+        }
+
+        Ok(vcan)
+    }
+
+    fn has_net_admin() -> std::io::Result<bool> {
+        let status = std::fs::read_to_string("/proc/self/status")?;
+        let cap_eff = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+            .unwrap_or(0);
+        Ok(cap_eff & (1 << CAP_NET_ADMIN) != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(id: u32) -> CanFrame {
+        CanFrame::standard(id, &[id as u8])
+    }
+
+    #[tokio::test]
+    async fn injected_frames_are_read_in_order() {
+        let bus = TestBus::new();
+        let mut reader = bus.clone();
+        bus.inject(frame(1));
+        bus.inject(frame(2));
+        bus.inject(frame(3));
+
+        assert_eq!(reader.read_frame().await.unwrap(), frame(1));
+        assert_eq!(
+            reader.read_frames().await.unwrap(),
+            vec![frame(2), frame(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn records_written_frames() {
+        let bus = TestBus::new();
+        let mut writer = bus.clone();
+        writer.write_frame(frame(1)).await.unwrap();
+        writer.write_frames(&[frame(2), frame(3)]).await.unwrap();
+
+        assert_eq!(bus.sent(), vec![frame(1), frame(2), frame(3)]);
+        bus.assert_sent(|f| f.id() == 2);
+        bus.assert_sequence(&[frame(1), frame(3)]);
+
+        bus.clear_sent();
+        assert!(bus.sent().is_empty());
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Expected frame 1 of the sequence")]
+    async fn assert_sequence_rejects_wrong_order() {
+        let mut bus = TestBus::new();
+        bus.write_frames(&[frame(1), frame(2)]).await.unwrap();
+        bus.assert_sequence(&[frame(2), frame(1)]);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "No matching frame was sent")]
+    async fn assert_sent_rejects_missing_frame() {
+        let mut bus = TestBus::new();
+        bus.write_frame(frame(1)).await.unwrap();
+        bus.assert_sent(|f| f.id() == 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expect_no_tx_for_passes_on_a_quiet_bus() {
+        let mut bus = TestBus::new();
+        bus.write_frame(frame(1)).await.unwrap();
+        bus.expect_no_tx_for(Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[should_panic(expected = "Unexpected frame sent")]
+    async fn expect_no_tx_for_catches_a_write() {
+        let bus = TestBus::new();
+        let mut writer = bus.clone();
+        let quiet = bus.expect_no_tx_for(Duration::from_millis(100));
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            writer.write_frame(frame(1)).await.unwrap();
+        });
+        quiet.await;
+    }
+}
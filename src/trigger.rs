@@ -0,0 +1,404 @@
+///
+/// trigger.rs
+///
+/// Content based triggers that fire callbacks when matching frames are observed, and a
+/// pre-trigger ring buffer capture built on them.
+///
+use crate::{
+    can::CanFrame,
+    pipeline::{Annotated, Stage},
+};
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Default maximum number of frames kept by a PreTriggerCapture
+pub const DEFAULT_CAPTURE_CAPACITY: usize = 100_000;
+
+type Matcher = Box<dyn Fn(&CanFrame) -> bool + Send>;
+type Handler = Box<dyn FnMut(&CanFrame) + Send>;
+
+/// When a trigger fires
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Fire on every matching frame (default)
+    #[default]
+    Always,
+    /// Fire on the first matching frame, then disarm
+    OneShot,
+    /// Fire on the n-th matching frame, then disarm
+    Count(u32),
+}
+
+struct Entry {
+    matcher: Matcher,
+    handler: Handler,
+    mode: TriggerMode,
+    matches: u32,
+    armed: bool,
+}
+
+/// A set of triggers that frames are checked against
+///
+/// Feed received frames into `observe()`, or add the set to a pipeline since it also implements
+/// `Stage` (frames pass through unchanged). Handlers run synchronously inside `observe()`, so
+/// keep them short and hand longer work to a task, e.g. through `notify()`.
+#[derive(Default)]
+pub struct Triggers {
+    entries: Vec<Entry>,
+}
+
+/// Builder returned by `Triggers::on()`
+pub struct TriggerBuilder<'a> {
+    triggers: &'a mut Triggers,
+    matcher: Matcher,
+    mode: TriggerMode,
+}
+
+impl TriggerBuilder<'_> {
+    /// Fire only once, on the first matching frame
+    pub fn once(mut self) -> Self {
+        self.mode = TriggerMode::OneShot;
+        self
+    }
+
+    /// Fire once, on the `count`-th matching frame
+    pub fn after(mut self, count: u32) -> Self {
+        self.mode = TriggerMode::Count(count.max(1));
+        self
+    }
+
+    /// Registers the callback run with the frame that fired the trigger
+    pub fn call<H>(self, handler: H)
+    where
+        H: FnMut(&CanFrame) + Send + 'static,
+    {
+        self.triggers.entries.push(Entry {
+            matcher: self.matcher,
+            handler: Box::new(handler),
+            mode: self.mode,
+            matches: 0,
+            armed: true,
+        });
+    }
+
+    /// Registers the trigger and returns a channel receiving the frames that fired it
+    pub fn notify(self) -> mpsc::UnboundedReceiver<CanFrame> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.call(move |frame| {
+            let _ = tx.send(frame.clone());
+        });
+        rx
+    }
+}
+
+impl Triggers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts registering a trigger for frames that satisfy `matcher`
+    pub fn on<M>(&mut self, matcher: M) -> TriggerBuilder<'_>
+    where
+        M: Fn(&CanFrame) -> bool + Send + 'static,
+    {
+        TriggerBuilder {
+            triggers: self,
+            matcher: Box::new(matcher),
+            mode: TriggerMode::default(),
+        }
+    }
+
+    /// Starts registering a trigger for data frames with `id` whose payload satisfies `condition`
+    ///
+    /// E.g. `on_id(0x1F0, |data| data.get(3).is_some_and(|b| *b > 200))`
+    pub fn on_id<C>(&mut self, id: u32, condition: C) -> TriggerBuilder<'_>
+    where
+        C: Fn(&[u8]) -> bool + Send + 'static,
+    {
+        self.on(move |frame| {
+            frame.id() == id && !frame.is_rtr() && !frame.is_error() && condition(frame.data())
+        })
+    }
+
+    /// Checks `frame` against every armed trigger and runs the handlers of those that fire, in
+    /// registration order. Returns the number of triggers fired.
+    pub fn observe(&mut self, frame: &CanFrame) -> usize {
+        let mut fired = 0;
+        for entry in self.entries.iter_mut().filter(|e| e.armed) {
+            if !(entry.matcher)(frame) {
+                continue;
+            }
+            entry.matches = entry.matches.saturating_add(1);
+            let fire = match entry.mode {
+                TriggerMode::Always | TriggerMode::OneShot => true,
+                TriggerMode::Count(count) => entry.matches >= count,
+            };
+            if !fire {
+                continue;
+            }
+            if entry.mode != TriggerMode::Always {
+                entry.armed = false;
+            }
+            (entry.handler)(frame);
+            fired += 1;
+        }
+        fired
+    }
+
+    /// Re-arms every one-shot and counting trigger and resets their match counts
+    pub fn rearm(&mut self) {
+        for entry in &mut self.entries {
+            entry.matches = 0;
+            entry.armed = true;
+        }
+    }
+
+    /// Returns the number of triggers that can still fire
+    pub fn armed(&self) -> usize {
+        self.entries.iter().filter(|e| e.armed).count()
+    }
+}
+
+impl Stage for Triggers {
+    fn process(&mut self, frame: Annotated) -> Option<Annotated> {
+        self.observe(&frame.frame);
+        Some(frame)
+    }
+}
+
+/// Frames recorded around a trigger event, with their receive times
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    /// Pre-trigger frames, the triggering frame and post-trigger frames, in receive order
+    pub frames: Vec<(SystemTime, CanFrame)>,
+    /// Index of the triggering frame in `frames`
+    pub trigger_index: usize,
+}
+
+impl Capture {
+    pub fn trigger(&self) -> &CanFrame {
+        &self.frames[self.trigger_index].1
+    }
+}
+
+/// Keeps the most recent frames in a ring buffer and hands them out when a trigger fires
+///
+/// Every frame passed to `observe()` is kept for the pre-trigger window, bounded by the capacity.
+/// When one of the triggers fires, the buffered frames and the triggering frame start a capture
+/// that collects frames for the post-trigger window and is then returned by `observe()`. On a
+/// quiet bus the window may pass without another frame; call `finish()` from a timer to close
+/// the capture in that case. Triggers are not checked while a capture is in progress; the frame
+/// that ends it is checked, and may start the next capture.
+pub struct PreTriggerCapture {
+    triggers: Triggers,
+    pre_trigger: Duration,
+    post_trigger: Duration,
+    capacity: usize,
+    ring: VecDeque<(Instant, SystemTime, CanFrame)>,
+    capture: Option<(Instant, Capture)>,
+}
+
+impl PreTriggerCapture {
+    pub fn new(triggers: Triggers, pre_trigger: Duration) -> Self {
+        Self {
+            triggers,
+            pre_trigger,
+            post_trigger: Duration::ZERO,
+            capacity: DEFAULT_CAPTURE_CAPACITY,
+            ring: VecDeque::new(),
+            capture: None,
+        }
+    }
+
+    /// Keep recording for `duration` after the trigger fired (default: none)
+    pub fn with_post_trigger(mut self, duration: Duration) -> Self {
+        self.post_trigger = duration;
+        self
+    }
+
+    /// Limits the number of buffered pre-trigger frames
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn triggers_mut(&mut self) -> &mut Triggers {
+        &mut self.triggers
+    }
+
+    /// Returns true while frames are being collected after a trigger
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Records a received frame. Returns the capture once its post-trigger window has ended.
+    pub fn observe(&mut self, frame: &CanFrame) -> Option<Capture> {
+        let now = Instant::now();
+        let time = SystemTime::now();
+
+        if let Some((deadline, capture)) = &mut self.capture
+            && now < *deadline
+        {
+            capture.frames.push((time, frame.clone()));
+            return None;
+        }
+        // The window ended before this frame arrived, so it is checked for the next capture
+        let finished = self.finish();
+
+        if self.triggers.observe(frame) == 0 {
+            self.record(now, time, frame);
+            return finished;
+        }
+
+        self.prune(now);
+        let mut frames: Vec<_> = self.ring.drain(..).map(|(_, t, f)| (t, f)).collect();
+        let trigger_index = frames.len();
+        frames.push((time, frame.clone()));
+        let capture = Capture {
+            frames,
+            trigger_index,
+        };
+
+        // Without a post-trigger window no capture was in progress, so `finished` is None
+        if self.post_trigger.is_zero() {
+            return Some(capture);
+        }
+        self.capture = Some((now + self.post_trigger, capture));
+        finished
+    }
+
+    /// Ends the capture in progress, if any, and returns it
+    pub fn finish(&mut self) -> Option<Capture> {
+        self.capture.take().map(|(_, capture)| capture)
+    }
+
+    fn record(&mut self, now: Instant, time: SystemTime, frame: &CanFrame) {
+        self.ring.push_back((now, time, frame.clone()));
+        if self.ring.len() > self.capacity {
+            self.ring.pop_front();
+        }
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self
+            .ring
+            .front()
+            .is_some_and(|(seen, _, _)| now.duration_since(*seen) > self.pre_trigger)
+        {
+            self.ring.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::advance;
+
+    fn frame(id: u32) -> CanFrame {
+        CanFrame::new(id, &[id as u8]).unwrap()
+    }
+
+    fn ids(capture: &Capture) -> Vec<u32> {
+        capture.frames.iter().map(|(_, f)| f.id()).collect()
+    }
+
+    fn capture_on(id: u32, pre: Duration, post: Duration) -> PreTriggerCapture {
+        let mut triggers = Triggers::new();
+        triggers.on(move |f| f.id() == id).call(|_| {});
+        PreTriggerCapture::new(triggers, pre).with_post_trigger(post)
+    }
+
+    #[test]
+    fn trigger_modes() {
+        let mut triggers = Triggers::new();
+        triggers.on(|f| f.id() == 1).call(|_| {});
+        triggers.on(|f| f.id() == 1).once().call(|_| {});
+        triggers.on(|f| f.id() == 1).after(3).call(|_| {});
+        assert_eq!(triggers.armed(), 3);
+
+        assert_eq!(triggers.observe(&frame(1)), 2);
+        assert_eq!(triggers.observe(&frame(2)), 0);
+        assert_eq!(triggers.observe(&frame(1)), 1);
+        assert_eq!(triggers.observe(&frame(1)), 2);
+        assert_eq!(triggers.armed(), 1);
+
+        triggers.rearm();
+        assert_eq!(triggers.armed(), 3);
+        assert_eq!(triggers.observe(&frame(1)), 2);
+    }
+
+    #[test]
+    fn notify_sends_firing_frames() {
+        let mut triggers = Triggers::new();
+        let mut fired = triggers.on_id(0x10, |data| data[0] > 5).notify();
+        triggers.observe(&CanFrame::new(0x10, &[1]).unwrap());
+        triggers.observe(&CanFrame::new(0x10, &[9]).unwrap());
+        assert_eq!(fired.try_recv().unwrap().data(), &[9]);
+        assert!(fired.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pre_trigger_window() {
+        let mut capture = capture_on(9, Duration::from_millis(100), Duration::ZERO);
+        capture.observe(&frame(1));
+        advance(Duration::from_millis(150)).await;
+        capture.observe(&frame(2));
+        advance(Duration::from_millis(50)).await;
+        capture.observe(&frame(3));
+
+        let result = capture.observe(&frame(9)).unwrap();
+        assert_eq!(ids(&result), vec![2, 3, 9]);
+        assert_eq!(result.trigger_index, 2);
+        assert_eq!(result.trigger().id(), 9);
+        assert!(!capture.is_capturing());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn capacity_limits_pre_trigger_frames() {
+        let mut capture = capture_on(9, Duration::from_secs(1), Duration::ZERO).with_capacity(2);
+        for id in 1..=4 {
+            capture.observe(&frame(id));
+        }
+        assert_eq!(ids(&capture.observe(&frame(9)).unwrap()), vec![3, 4, 9]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn post_trigger_window() {
+        let mut capture = capture_on(9, Duration::from_secs(1), Duration::from_millis(100));
+        capture.observe(&frame(1));
+        assert!(capture.observe(&frame(9)).is_none());
+        assert!(capture.is_capturing());
+        advance(Duration::from_millis(50)).await;
+        assert!(capture.observe(&frame(2)).is_none());
+        advance(Duration::from_millis(60)).await;
+
+        let result = capture.observe(&frame(3)).unwrap();
+        assert_eq!(ids(&result), vec![1, 9, 2]);
+        assert_eq!(result.trigger_index, 1);
+        assert!(!capture.is_capturing());
+
+        // The frame that ended the window is kept for the next capture
+        assert!(capture.observe(&frame(9)).is_none());
+        assert_eq!(ids(&capture.finish().unwrap()), vec![3, 9]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn frame_ending_window_can_trigger() {
+        let mut capture = capture_on(9, Duration::from_secs(1), Duration::from_millis(100));
+        capture.observe(&frame(9));
+        capture.observe(&frame(1));
+        advance(Duration::from_millis(150)).await;
+
+        let first = capture.observe(&frame(9)).unwrap();
+        assert_eq!(ids(&first), vec![9, 1]);
+        assert!(capture.is_capturing());
+        capture.observe(&frame(2));
+        let second = capture.finish().unwrap();
+        assert_eq!(ids(&second), vec![9, 2]);
+        assert_eq!(second.trigger_index, 0);
+    }
+}
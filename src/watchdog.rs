@@ -0,0 +1,153 @@
+///
+/// watchdog.rs
+///
+/// Supervises periodic CAN traffic and reports IDs that stop arriving.
+///
+use crate::can::{CanFrame, FrameKey};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Events reported by a FrameWatchdog
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// No frame with this ID was seen within its configured interval
+    Missed {
+        id: u32,
+        extended: bool,
+        last_seen: Option<Instant>,
+    },
+    /// A frame with a previously missed ID arrived again after `silence`
+    Recovered {
+        id: u32,
+        extended: bool,
+        silence: Duration,
+    },
+}
+
+struct Supervised {
+    interval: Duration,
+    deadline: Instant,
+    last_seen: Option<Instant>,
+    missed_at: Option<Instant>,
+}
+
+/// Watches for frames that are expected at fixed intervals
+///
+/// Feed every received frame into `observe()` and await `next_event()` alongside the read loop
+/// (e.g. in `tokio::select!`). A `Missed` event fires once per outage and a `Recovered` event is
+/// returned by `observe()` when the ID shows up again.
+#[derive(Default)]
+pub struct FrameWatchdog {
+    supervised: HashMap<FrameKey, Supervised>,
+}
+
+impl FrameWatchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect frames with `id` at least once every `interval`, starting from now
+    pub fn expect(&mut self, id: u32, extended: bool, interval: Duration) {
+        self.supervised.insert(
+            FrameKey::from_id(id, extended),
+            Supervised {
+                interval,
+                deadline: Instant::now() + interval,
+                last_seen: None,
+                missed_at: None,
+            },
+        );
+    }
+
+    /// Stop supervising `id`
+    pub fn remove(&mut self, id: u32, extended: bool) {
+        self.supervised.remove(&FrameKey::from_id(id, extended));
+    }
+
+    /// Returns the keys of the IDs that are currently considered missing
+    pub fn missing(&self) -> Vec<FrameKey> {
+        self.supervised
+            .iter()
+            .filter(|(_, s)| s.missed_at.is_some())
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Record a received frame. Returns a `Recovered` event if its ID was previously missed.
+    pub fn observe(&mut self, frame: &CanFrame) -> Option<WatchdogEvent> {
+        let supervised = self.supervised.get_mut(&FrameKey::id(frame))?;
+        let now = Instant::now();
+        supervised.last_seen = Some(now);
+        supervised.deadline = now + supervised.interval;
+
+        supervised
+            .missed_at
+            .take()
+            .map(|missed_at| WatchdogEvent::Recovered {
+                id: frame.id(),
+                extended: frame.is_extended(),
+                silence: now - missed_at + supervised.interval,
+            })
+    }
+
+    /// Waits for the next supervised ID to miss its deadline
+    ///
+    /// This is cancellation safe. If nothing is being supervised the future never resolves.
+    pub async fn next_event(&mut self) -> WatchdogEvent {
+        let next = self
+            .supervised
+            .iter()
+            .filter(|(_, s)| s.missed_at.is_none())
+            .min_by_key(|(_, s)| s.deadline)
+            .map(|(key, s)| (*key, s.deadline));
+
+        let Some((key, deadline)) = next else {
+            return std::future::pending().await;
+        };
+        tokio::time::sleep_until(deadline).await;
+
+        let supervised = self.supervised.get_mut(&key).unwrap();
+        supervised.missed_at = Some(deadline);
+        WatchdogEvent::Missed {
+            id: key.can_id(),
+            extended: key.is_extended(),
+            last_seen: supervised.last_seen,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn standard_and_extended_ids_are_supervised_separately() {
+        let mut watchdog = FrameWatchdog::new();
+        watchdog.expect(0x100, false, Duration::from_millis(10));
+
+        // An extended frame with the same numeric ID does not feed the standard ID
+        assert_eq!(watchdog.observe(&CanFrame::extended(0x100, &[])), None);
+        let missed = watchdog.next_event().await;
+        assert!(matches!(
+            missed,
+            WatchdogEvent::Missed {
+                id: 0x100,
+                extended: false,
+                ..
+            }
+        ));
+        assert_eq!(watchdog.missing(), vec![FrameKey::from_id(0x100, false)]);
+
+        let recovered = watchdog.observe(&CanFrame::standard(0x100, &[]));
+        assert!(matches!(
+            recovered,
+            Some(WatchdogEvent::Recovered {
+                id: 0x100,
+                extended: false,
+                ..
+            })
+        ));
+        assert!(watchdog.missing().is_empty());
+    }
+}
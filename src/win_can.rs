@@ -4,64 +4,246 @@
 /// Implementation of CanInterface for Windows using pipes.
 /// Will require an existing pipe server to be connected to a CAN port using the 'win_can_utils' package.
 ///
-use crate::{CanInterface, can::CanFrame};
+pub use crate::win_pipe::{ImpersonationLevel, PipeSecurity};
+pub use crate::wire::{CanServerConfig, ProtocolError, WriteError};
+use crate::{
+    CanInterface,
+    can::{CanErrorCounters, CanFrame, InterfaceInfo},
+    win_pipe::{connect_pipe, open_pipe},
+    wire::{
+        CAPABILITY_KEEPALIVE, CAPABILITY_SESSIONS, CAPABILITY_WRITE_ACK, FrameDecoder,
+        KEEPALIVE_PING, KEEPALIVE_SERVER_TIMEOUT, SessionReply, SessionRequest, WriteAck,
+    },
+};
 use bincode;
-use serde::{Deserialize, Serialize};
 use std::io::{Error as IoError, ErrorKind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient};
+use tokio::net::windows::named_pipe::NamedPipeClient;
+use tokio::sync::Notify;
+use tokio::time::Instant;
 
 // The CanInterface will fail to open a connection to a win_can_utils canserver if it isn't the matching version.
-const WIN_CAN_UTILS_TARGET_VERSION: &str = "0.2.0";
+const WIN_CAN_UTILS_TARGET_VERSION: &str = "0.3.0";
+
+// Minimum free space reserved in the receive buffer before each pipe read
+const READ_CHUNK_LEN: usize = 1024;
+
+/// Default time `WindowsCan::open()` waits for a busy server
+pub const DEFAULT_OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default interval between keepalives sent to servers with `CAPABILITY_KEEPALIVE`
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+/// Default time without an answer to a keepalive after which the server is considered hung
+pub const DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Settings of `WindowsCan::open_with()`
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    security: PipeSecurity,
+    timeout: Duration,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            security: PipeSecurity::default(),
+            timeout: DEFAULT_OPEN_TIMEOUT,
+        }
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects only to pipes that meet `security`
+    ///
+    /// On machines shared between users, use this to make sure the pipes belong to a server
+    /// started by this user.
+    pub fn with_security(mut self, security: PipeSecurity) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Gives up opening the device after `timeout`. Defaults to `DEFAULT_OPEN_TIMEOUT`
+    ///
+    /// Also bounds every later `get_config()`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
 
 pub struct WindowsCan {
-    reader: Option<BufReader<NamedPipeClient>>,
+    reader: Option<NamedPipeClient>,
     writer: Option<NamedPipeClient>,
     channel: String,
+    session: Option<u32>,
+    decoder: FrameDecoder,
+    ack: Option<AckReader>,
+    keepalive: Option<Keepalive>,
+    options: OpenOptions,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CanServerConfig {
-    pub bitrate: Option<u32>,
-    pub version: String,
+// Reads the acknowledgements of written frames from servers with CAPABILITY_WRITE_ACK
+struct AckReader {
+    pipe: NamedPipeClient,
+    decoder: FrameDecoder<WriteAck>,
+    // Frames written whose acknowledgement has not been read yet
+    pending: usize,
 }
 
-impl CanInterface for WindowsCan {
-    /// Open a CAN device
-    ///
-    /// Can device is usually attached to a serial COM port (i.e. COM5). This method will open two separate pipes for reading and writing.
-    async fn open(channel: &str) -> tokio::io::Result<Self> {
-        let sanitized = channel
-            .chars()
-            .map(|c| if c.is_alphanumeric() { c } else { '_' })
-            .collect::<String>();
-        let out_pipe_name = format!(r"\\.\pipe\can_{}_out", sanitized);
-        let out_pipe = ClientOptions::new().open(&out_pipe_name)?;
+impl AckReader {
+    async fn open(channel: &str, security: &PipeSecurity) -> std::io::Result<Self> {
+        let pipe_name = format!(r"\\.\pipe\can_{}_ack_out", channel);
+        Ok(Self {
+            pipe: connect_pipe(&pipe_name, security).await?,
+            decoder: FrameDecoder::new(),
+            pending: 0,
+        })
+    }
 
-        let in_pipe_name = format!(r"\\.\pipe\can_{}_in", sanitized);
-        let in_pipe = ClientOptions::new().open(&in_pipe_name)?;
+    async fn next(&mut self) -> std::io::Result<WriteAck> {
+        loop {
+            if let Some(ack) = self.decoder.decode()? {
+                return Ok(ack);
+            }
+            self.decoder.buf.reserve(READ_CHUNK_LEN);
+            if self.pipe.read_buf(&mut self.decoder.buf).await? == 0 {
+                return Err(IoError::new(
+                    ErrorKind::UnexpectedEof,
+                    "Acknowledgement pipe closed",
+                ));
+            }
+        }
+    }
 
-        let interface = Self {
-            reader: Some(BufReader::new(out_pipe)),
-            writer: Some(in_pipe),
-            channel: sanitized,
-        };
+    // Waits for the acknowledgements of the last `count` frames written and returns the first
+    // failure. Acknowledgements of writes whose call was cancelled are read and discarded first.
+    async fn wait(&mut self, count: usize) -> std::io::Result<()> {
+        let mut stale = self.pending;
+        self.pending += count;
+        let mut result = Ok(());
+        while self.pending > 0 {
+            let ack = self.next().await?;
+            self.pending -= 1;
+            if stale > 0 {
+                stale -= 1;
+            } else if let (Ok(()), Err(e)) = (&result, ack) {
+                result = Err(IoError::other(e));
+            }
+        }
+        result
+    }
+}
 
-        // Check the version number of the win_can_utils package that we are connecting to
-        let ver = interface.get_config().await?.version;
-        if ver != WIN_CAN_UTILS_TARGET_VERSION.to_string() {
-            return Err(IoError::new(
-                ErrorKind::InvalidData,
-                format!(
-                    "Installed win_can_utils is version {:?}. Version {:?} is required.",
-                    ver, WIN_CAN_UTILS_TARGET_VERSION
-                ),
-            ));
+// Settings and result of a keepalive task, shared with the interface
+struct KeepaliveState {
+    interval_ms: AtomicU64,
+    timeout_ms: AtomicU64,
+    expired: AtomicBool,
+    notify: Notify,
+}
+
+// Pings a server with CAPABILITY_KEEPALIVE from a task of its own
+struct Keepalive {
+    state: Arc<KeepaliveState>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Keepalive {
+    async fn start(channel: &str, security: &PipeSecurity) -> std::io::Result<Self> {
+        let pipe_name = format!(r"\\.\pipe\can_{}_keepalive", channel);
+        let pipe = connect_pipe(&pipe_name, security).await?;
+        let state = Arc::new(KeepaliveState {
+            interval_ms: AtomicU64::new(DEFAULT_KEEPALIVE_INTERVAL.as_millis() as u64),
+            timeout_ms: AtomicU64::new(DEFAULT_KEEPALIVE_TIMEOUT.as_millis() as u64),
+            expired: AtomicBool::new(false),
+            notify: Notify::new(),
+        });
+        let task = tokio::spawn(run_keepalive(pipe, state.clone()));
+        Ok(Self { state, task })
+    }
+
+    // Resolves once the server stopped answering
+    async fn expired(&self) -> IoError {
+        loop {
+            let notified = self.state.notify.notified();
+            if self.state.expired.load(Ordering::Acquire) {
+                return IoError::new(
+                    ErrorKind::TimedOut,
+                    "The canserver stopped answering keepalives",
+                );
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run_keepalive(mut pipe: NamedPipeClient, state: Arc<KeepaliveState>) {
+    let mut last_answer = Instant::now();
+    let mut next_ping = Instant::now();
+    let mut buf = [0u8; 64];
+    loop {
+        let interval = Duration::from_millis(state.interval_ms.load(Ordering::Relaxed));
+        let deadline =
+            last_answer + Duration::from_millis(state.timeout_ms.load(Ordering::Relaxed));
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_ping) => {
+                // A hung server may also stop draining the pipe, so the write has the same deadline
+                match tokio::time::timeout_at(deadline, pipe.write_all(&[KEEPALIVE_PING])).await {
+                    Ok(Ok(())) => next_ping = Instant::now() + interval,
+                    _ => break,
+                }
+            }
+            result = pipe.read(&mut buf) => match result {
+                Ok(len) if len > 0 => last_answer = Instant::now(),
+                _ => break,
+            },
+            _ = tokio::time::sleep_until(deadline) => break,
         }
+    }
+    state.expired.store(true, Ordering::Release);
+    state.notify.notify_waiters();
+}
 
-        Ok(interface)
+// Runs `io` until it completes or the keepalive finds the server hung
+async fn guarded<R>(
+    keepalive: &Option<Keepalive>,
+    io: impl Future<Output = std::io::Result<R>>,
+) -> std::io::Result<R> {
+    match keepalive {
+        Some(keepalive) => tokio::select! {
+            result = io => result,
+            e = keepalive.expired() => Err(e),
+        },
+        None => io.await,
     }
+}
 
+impl CanInterface for WindowsCan {
+    /// Open a CAN device
+    ///
+    /// Can device is usually attached to a serial COM port (i.e. COM5). This method will open two separate pipes for reading and writing.
+    /// If the server supports sessions, the pipes are this client's own and other processes can
+    /// use the same device at the same time.
+    async fn open(channel: &str) -> tokio::io::Result<Self> {
+        Self::open_with(channel, OpenOptions::default()).await
+    }
+
+    /// Read a single CAN frame from the interface
+    ///
+    /// This method is cancellation safe and can be used in `tokio::select!`.
     async fn read_frame(&mut self) -> tokio::io::Result<CanFrame> {
         let reader = match &mut self.reader {
             Some(r) => r,
@@ -73,32 +255,43 @@ impl CanInterface for WindowsCan {
             }
         };
 
-        // Helper function to check if BufReader.read_exact() is returning zero bytes (issue has occured)
-        let check_bytes = |num_bytes: usize| {
-            if num_bytes == 0 {
+        // All partial data is kept in the decoder, so cancelling this future between reads is safe
+        loop {
+            if let Some(frame) = self.decoder.decode()? {
+                return Ok(frame);
+            }
+
+            self.decoder.buf.reserve(READ_CHUNK_LEN);
+            let read = reader.read_buf(&mut self.decoder.buf);
+            if guarded(&self.keepalive, read).await? == 0 {
                 return Err(IoError::new(
                     ErrorKind::UnexpectedEof,
-                    "Pipe closed. EOF was reached (closed connection) or buffer was full",
+                    "Pipe closed. EOF was reached (closed connection)",
                 ));
             }
-            Ok(())
-        };
-
-        // Read the length prefix of next CanFrame (always 1 byte)
-        let mut len_prefix = [0u8; 1];
-        check_bytes(reader.read_exact(&mut len_prefix).await?)?;
-
-        // Read the bytes for the next CanFrame
-        let mut buf = vec![0u8; len_prefix[0] as usize];
-        check_bytes(reader.read_exact(&mut buf).await?)?;
+        }
+    }
 
-        // Deserialize CanFrame bytes into struct
-        match bincode::serde::decode_from_slice::<CanFrame, _>(&buf, bincode::config::standard()) {
-            Ok((frame, _)) => Ok(frame),
-            Err(e) => Err(IoError::new(ErrorKind::Other, e)),
+    async fn read_frames(&mut self) -> tokio::io::Result<Vec<CanFrame>> {
+        let mut frames = vec![self.read_frame().await?];
+        // Hand out everything else that has already been received without waiting on the pipe
+        loop {
+            match self.decoder.decode() {
+                Ok(Some(frame)) => frames.push(frame),
+                Ok(None) => break,
+                Err(e) => {
+                    self.decoder.pending_error = Some(e);
+                    break;
+                }
+            }
         }
+        Ok(frames)
     }
 
+    /// Write a single CAN frame to the interface
+    ///
+    /// If the server acknowledges writes (`CAPABILITY_WRITE_ACK`), returns once it transmitted the
+    /// frame, or a `WriteError` wrapped in an `std::io::Error` if it could not.
     async fn write_frame(&mut self, frame: CanFrame) -> tokio::io::Result<()> {
         let writer = match &mut self.writer {
             Some(r) => r,
@@ -110,24 +303,165 @@ impl CanInterface for WindowsCan {
             }
         };
 
-        match bincode::serde::encode_to_vec(frame, bincode::config::standard()) {
-            Ok(data) => {
-                writer.write_all(&data).await?;
-                writer.write_all(b"\n").await?;
-                writer.flush().await?;
-                Ok(())
+        let mut data = bincode::serde::encode_to_vec(frame, bincode::config::standard())
+            .map_err(IoError::other)?;
+        data.push(b'\n');
+        let write = async {
+            writer.write_all(&data).await?;
+            writer.flush().await
+        };
+        guarded(&self.keepalive, write).await?;
+        self.wait_acks(1).await
+    }
+
+    /// Write several CAN frames to the interface, in order
+    ///
+    /// If the server acknowledges writes, returns the `WriteError` of the first frame it could not
+    /// transmit. The other frames are still sent.
+    async fn write_frames(&mut self, frames: &[CanFrame]) -> tokio::io::Result<()> {
+        let writer = match &mut self.writer {
+            Some(r) => r,
+            None => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "No write pipe has been opened",
+                ));
             }
-            Err(e) => Err(IoError::new(ErrorKind::Other, e)),
+        };
+
+        // Encode everything up front so the whole burst goes out in a single pipe write
+        let mut data = Vec::new();
+        for frame in frames {
+            bincode::serde::encode_into_std_write(frame, &mut data, bincode::config::standard())
+                .map_err(IoError::other)?;
+            data.push(b'\n');
         }
+        let write = async {
+            writer.write_all(&data).await?;
+            writer.flush().await
+        };
+        guarded(&self.keepalive, write).await?;
+        self.wait_acks(frames.len()).await
     }
 
     async fn get_bitrate(&mut self) -> std::io::Result<Option<u32>> {
         let config = self.get_config().await?;
         Ok(config.bitrate)
     }
+
+    async fn get_error_counters(&mut self) -> std::io::Result<Option<CanErrorCounters>> {
+        let config = self.get_config().await?;
+        Ok(config.error_counters)
+    }
+
+    /// Returns the adapter details from the server config. Servers that predate them only report
+    /// their version, as the driver.
+    async fn get_info(&mut self) -> std::io::Result<InterfaceInfo> {
+        let config = self.get_config().await?;
+        Ok(config.info.unwrap_or_else(|| InterfaceInfo {
+            driver: Some(format!("win_can_utils {}", config.version)),
+            ..InterfaceInfo::default()
+        }))
+    }
+
+    async fn tx_pending(&mut self) -> std::io::Result<usize> {
+        // Every write is flushed into the pipe before write_frame returns
+        Ok(0)
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.writer {
+            Some(w) => w.flush().await,
+            None => Ok(()),
+        }
+    }
 }
 
 impl WindowsCan {
+    /// Open a CAN device with `options`
+    ///
+    /// Waits while the server's pipes are busy with other connecting clients, for at most the
+    /// timeout of `options`, then fails with an error of kind `TimedOut`. Pipes that fail the
+    /// security checks, and denied access, are reported as `PermissionDenied` errors naming the
+    /// likely cause.
+    pub async fn open_with(channel: &str, options: OpenOptions) -> tokio::io::Result<Self> {
+        let timeout = options.timeout;
+        tokio::time::timeout(timeout, Self::connect(channel, options))
+            .await
+            .map_err(|_| {
+                IoError::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "Timed out after {:?} connecting to the canserver of {}",
+                        timeout, channel
+                    ),
+                )
+            })?
+    }
+
+    async fn connect(channel: &str, options: OpenOptions) -> tokio::io::Result<Self> {
+        let sanitized = channel
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        // Servers with a versioned config are compatible unless they require a newer schema. Older
+        // ones must be the exact win_can_utils version this crate was written against.
+        let security = &options.security;
+        let config = read_config(&sanitized, security).await?;
+        if config.schema > 0 {
+            config.check_schema()?;
+        } else if config.version != WIN_CAN_UTILS_TARGET_VERSION {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Installed win_can_utils is version {:?}. Version {:?} is required.",
+                    config.version, WIN_CAN_UTILS_TARGET_VERSION
+                ),
+            ));
+        }
+
+        // Servers with sessions serve this client on pipes of its own, so other processes can
+        // share the adapter
+        let session = if config.has_capability(CAPABILITY_SESSIONS) {
+            Some(request_session(&sanitized, security).await?)
+        } else {
+            None
+        };
+        let prefix = match session {
+            Some(session) => format!("{}_{}", sanitized, session),
+            None => sanitized.clone(),
+        };
+
+        let out_pipe_name = format!(r"\\.\pipe\can_{}_out", prefix);
+        let out_pipe = connect_pipe(&out_pipe_name, security).await?;
+
+        let in_pipe_name = format!(r"\\.\pipe\can_{}_in", prefix);
+        let in_pipe = connect_pipe(&in_pipe_name, security).await?;
+
+        let ack = if config.has_capability(CAPABILITY_WRITE_ACK) {
+            Some(AckReader::open(&prefix, security).await?)
+        } else {
+            None
+        };
+        let keepalive = if config.has_capability(CAPABILITY_KEEPALIVE) {
+            Some(Keepalive::start(&prefix, security).await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            reader: Some(out_pipe),
+            writer: Some(in_pipe),
+            channel: sanitized,
+            session,
+            decoder: FrameDecoder::default(),
+            ack,
+            keepalive,
+            options,
+        })
+    }
+
     /// Open a read-only CAN device
     ///
     /// Can device is usually attached to a serial COM port (i.e. COM5). This method will a single pipe for reading CAN messages. Attempting to write to the port later will throw an InvalidData error.
@@ -138,12 +472,17 @@ impl WindowsCan {
             .collect::<String>();
         let out_pipe_name = format!(r"\\.\pipe\can_{}_out", sanitized);
 
-        let out_pipe = ClientOptions::new().open(&out_pipe_name)?;
+        let out_pipe = open_pipe(&out_pipe_name, &PipeSecurity::default())?;
 
         Ok(Self {
-            reader: Some(BufReader::new(out_pipe)),
+            reader: Some(out_pipe),
             writer: None,
             channel: sanitized,
+            session: None,
+            decoder: FrameDecoder::default(),
+            ack: None,
+            keepalive: None,
+            options: OpenOptions::default(),
         })
     }
 
@@ -157,28 +496,117 @@ impl WindowsCan {
             .collect::<String>();
         let in_pipe_name = format!(r"\\.\pipe\can_{}_in", sanitized);
 
-        let in_pipe = ClientOptions::new().open(&in_pipe_name)?;
+        let in_pipe = open_pipe(&in_pipe_name, &PipeSecurity::default())?;
 
         Ok(Self {
             reader: None,
             writer: Some(in_pipe),
             channel: sanitized,
+            session: None,
+            decoder: FrameDecoder::default(),
+            ack: None,
+            keepalive: None,
+            options: OpenOptions::default(),
         })
     }
 
-    pub async fn get_config(&self) -> std::io::Result<CanServerConfig> {
-        // Connect to config pipe
-        let config_pipe_name = format!(r"\\.\pipe\can_{}_config_out", self.channel);
-        let config_pipe = ClientOptions::new().open(&config_pipe_name)?;
-        let mut config_reader = BufReader::new(config_pipe);
+    /// Returns true if the server acknowledges writes, so transmit failures are reported by the
+    /// write calls. Interfaces opened with `open_write_only()` never wait for acknowledgements.
+    pub fn has_write_ack(&self) -> bool {
+        self.ack.is_some()
+    }
+
+    /// Returns true if the server answers keepalives. Reads and writes then fail with an error of
+    /// kind `TimedOut` once it stopped answering, instead of waiting for the pipes.
+    pub fn has_keepalive(&self) -> bool {
+        self.keepalive.is_some()
+    }
+
+    /// Sets how often keepalives are sent and how long to wait for an answer before giving up
+    ///
+    /// Only has an effect if `has_keepalive()`. `interval` must be shorter than `timeout` and than
+    /// `KEEPALIVE_SERVER_TIMEOUT`, after which the server may end the connection.
+    pub fn set_keepalive(&self, interval: Duration, timeout: Duration) -> std::io::Result<()> {
+        if interval.is_zero() || interval >= timeout || interval >= KEEPALIVE_SERVER_TIMEOUT {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Invalid keepalive interval {:?} for a timeout of {:?}",
+                    interval, timeout
+                ),
+            ));
+        }
+        if let Some(keepalive) = &self.keepalive {
+            let state = &keepalive.state;
+            state
+                .interval_ms
+                .store(interval.as_millis() as u64, Ordering::Relaxed);
+            state
+                .timeout_ms
+                .store(timeout.as_millis() as u64, Ordering::Relaxed);
+        }
+        Ok(())
+    }
 
-        // Read the config struct
-        let mut buf = Vec::new();
-        config_reader.read_to_end(&mut buf).await?;
+    async fn wait_acks(&mut self, count: usize) -> std::io::Result<()> {
+        match &mut self.ack {
+            Some(ack) => guarded(&self.keepalive, ack.wait(count)).await,
+            None => Ok(()),
+        }
+    }
 
-        // Deserialize CanFrame bytes into struct
-        let config = serde_json::from_slice::<CanServerConfig>(&buf)?;
+    /// Returns the number of bytes discarded while resynchronizing to frame boundaries
+    pub fn skipped_bytes(&self) -> u64 {
+        self.decoder.skipped_bytes()
+    }
 
-        Ok(config)
+    /// Returns the session granted by a server with `CAPABILITY_SESSIONS`, or None if this client
+    /// uses the shared pipes
+    pub fn session(&self) -> Option<u32> {
+        self.session
     }
+
+    pub async fn get_config(&self) -> std::io::Result<CanServerConfig> {
+        let read = read_config(&self.channel, &self.options.security);
+        tokio::time::timeout(self.options.timeout, read)
+            .await
+            .map_err(|_| {
+                IoError::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "Timed out after {:?} reading the config",
+                        self.options.timeout
+                    ),
+                )
+            })?
+    }
+}
+
+async fn read_config(channel: &str, security: &PipeSecurity) -> std::io::Result<CanServerConfig> {
+    // Connect to config pipe
+    let config_pipe_name = format!(r"\\.\pipe\can_{}_config_out", channel);
+    let config_pipe = connect_pipe(&config_pipe_name, security).await?;
+    let mut config_reader = BufReader::new(config_pipe);
+
+    // Read the config struct
+    let mut buf = Vec::new();
+    config_reader.read_to_end(&mut buf).await?;
+
+    CanServerConfig::parse(&buf)
+}
+
+// Negotiates a session of this process with a server that has CAPABILITY_SESSIONS
+async fn request_session(channel: &str, security: &PipeSecurity) -> std::io::Result<u32> {
+    let session_pipe_name = format!(r"\\.\pipe\can_{}_session", channel);
+    let mut pipe = connect_pipe(&session_pipe_name, security).await?;
+
+    let client = format!("crosscan pid {}", std::process::id());
+    let mut request = serde_json::to_vec(&SessionRequest::new(&client))?;
+    request.push(b'\n');
+    pipe.write_all(&request).await?;
+    pipe.flush().await?;
+
+    let mut reply = Vec::new();
+    pipe.read_to_end(&mut reply).await?;
+    serde_json::from_slice::<SessionReply>(&reply)?.into_session()
 }
@@ -0,0 +1,250 @@
+///
+/// win_pipe.rs
+///
+/// Opens the canserver pipes with the caller's security requirements and explains access errors.
+///
+use std::io::{Error as IoError, ErrorKind};
+use std::os::windows::io::AsRawHandle;
+use std::time::Duration;
+use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient};
+use windows_sys::Win32::Foundation::{CloseHandle, ERROR_PIPE_BUSY, HANDLE, LocalFree};
+use windows_sys::Win32::Security::Authorization::{
+    ConvertSidToStringSidW, GetSecurityInfo, SE_KERNEL_OBJECT,
+};
+use windows_sys::Win32::Security::{
+    EqualSid, GetTokenInformation, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID,
+    TOKEN_QUERY, TOKEN_USER, TokenUser,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    SECURITY_ANONYMOUS, SECURITY_DELEGATION, SECURITY_IDENTIFICATION, SECURITY_IMPERSONATION,
+};
+use windows_sys::Win32::System::Pipes::GetNamedPipeServerSessionId;
+use windows_sys::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+use windows_sys::Win32::System::Threading::{
+    GetCurrentProcess, GetCurrentProcessId, OpenProcessToken,
+};
+
+// Pause between attempts to connect to a pipe whose instances are all busy
+const PIPE_BUSY_RETRY: Duration = Duration::from_millis(50);
+
+/// How far the canserver may act on behalf of this process after it connects
+///
+/// Sent as the security quality of service when opening the pipes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImpersonationLevel {
+    /// The server cannot identify this process
+    Anonymous,
+    /// The server can identify this process but not act as it
+    #[default]
+    Identification,
+    /// The server can act as this process on the local machine
+    Impersonation,
+    /// The server can act as this process on other machines too
+    Delegation,
+}
+
+impl ImpersonationLevel {
+    fn qos_flags(self) -> u32 {
+        match self {
+            ImpersonationLevel::Anonymous => SECURITY_ANONYMOUS,
+            ImpersonationLevel::Identification => SECURITY_IDENTIFICATION,
+            ImpersonationLevel::Impersonation => SECURITY_IMPERSONATION,
+            ImpersonationLevel::Delegation => SECURITY_DELEGATION,
+        }
+    }
+}
+
+/// Security requirements checked when connecting to the canserver pipes
+///
+/// By default any pipe this user is allowed to open is accepted. On machines shared between
+/// users, `with_same_user()` and `with_same_session()` make sure the pipes belong to a server
+/// started by this user rather than to another user's process that created them first.
+#[derive(Clone, Debug, Default)]
+pub struct PipeSecurity {
+    impersonation: ImpersonationLevel,
+    same_user: bool,
+    same_session: bool,
+}
+
+impl PipeSecurity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how far the server may impersonate this process. Defaults to `Identification`
+    pub fn with_impersonation(mut self, level: ImpersonationLevel) -> Self {
+        self.impersonation = level;
+        self
+    }
+
+    /// Requires the pipes to be owned by the user running this process
+    ///
+    /// Pipes of a server running as a service or elevated are owned by SYSTEM or the
+    /// Administrators group and fail this check.
+    pub fn with_same_user(mut self, required: bool) -> Self {
+        self.same_user = required;
+        self
+    }
+
+    /// Requires the server to run in the same Windows session (logon) as this process
+    pub fn with_same_session(mut self, required: bool) -> Self {
+        self.same_session = required;
+        self
+    }
+}
+
+/// Opens the client end of the pipe `name` and checks it against `security`
+///
+/// Errors are of kind `PermissionDenied` if the pipe may not be opened or fails a check, with a
+/// message naming the likely cause.
+pub(crate) fn open_pipe(name: &str, security: &PipeSecurity) -> std::io::Result<NamedPipeClient> {
+    let pipe = ClientOptions::new()
+        .security_qos_flags(security.impersonation.qos_flags())
+        .open(name)
+        .map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => IoError::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "Access denied to {}. The canserver probably runs as another user or in \
+                     another session; start it as this user, or create its pipes with a security \
+                     descriptor granting this user access (see wire::PIPE_SDDL)",
+                    name
+                ),
+            ),
+            _ => e,
+        })?;
+    let handle = pipe.as_raw_handle() as HANDLE;
+
+    if security.same_session {
+        let mut server = 0u32;
+        let mut own = 0u32;
+        // SAFETY: handle is an open pipe handle and both outputs are valid u32s
+        let ok = unsafe {
+            GetNamedPipeServerSessionId(handle, &mut server) != 0
+                && ProcessIdToSessionId(GetCurrentProcessId(), &mut own) != 0
+        };
+        if !ok {
+            return Err(IoError::last_os_error());
+        }
+        if server != own {
+            return Err(IoError::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "{} is served from Windows session {}, this process runs in session {}",
+                    name, server, own
+                ),
+            ));
+        }
+    }
+
+    if security.same_user {
+        check_owner(name, handle)?;
+    }
+    Ok(pipe)
+}
+
+/// Like `open_pipe()`, but waits while all instances of the pipe are busy
+///
+/// Servers create the next instance of a pipe only after a client connected to the previous one, so
+/// clients connecting at the same time briefly see ERROR_PIPE_BUSY. Retries until an instance is
+/// free, so callers bound it with a timeout.
+pub(crate) async fn connect_pipe(
+    name: &str,
+    security: &PipeSecurity,
+) -> std::io::Result<NamedPipeClient> {
+    loop {
+        match open_pipe(name, security) {
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) => {
+                tokio::time::sleep(PIPE_BUSY_RETRY).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Fails unless the owner of the pipe `handle` is the user of the current process token
+fn check_owner(name: &str, handle: HANDLE) -> std::io::Result<()> {
+    let mut owner: PSID = std::ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+    // SAFETY: handle is an open pipe handle. owner points into descriptor, which is freed below
+    let result = unsafe {
+        GetSecurityInfo(
+            handle,
+            SE_KERNEL_OBJECT,
+            OWNER_SECURITY_INFORMATION,
+            &mut owner,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+    if result != 0 {
+        return Err(IoError::from_raw_os_error(result as i32));
+    }
+
+    let checked = current_user().and_then(|user| {
+        let user_sid = user_sid(&user);
+        // SAFETY: both SIDs are valid for the duration of the call
+        if unsafe { EqualSid(owner, user_sid) } != 0 {
+            return Ok(());
+        }
+        Err(IoError::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "{} is owned by {}, not by the current user {}",
+                name,
+                sid_string(owner),
+                sid_string(user_sid)
+            ),
+        ))
+    });
+    // SAFETY: descriptor was allocated by GetSecurityInfo and is no longer used
+    unsafe { LocalFree(descriptor) };
+    checked
+}
+
+// Returns the TOKEN_USER block of the current process token, aligned for TOKEN_USER
+fn current_user() -> std::io::Result<Vec<u64>> {
+    let mut token: HANDLE = std::ptr::null_mut();
+    // SAFETY: plain OpenProcessToken call on the pseudo handle of the current process
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    let mut len = 0u32;
+    // SAFETY: a null buffer of length 0 only queries the required length
+    unsafe { GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut len) };
+    let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+    // SAFETY: buf holds at least len bytes
+    let ok =
+        unsafe { GetTokenInformation(token, TokenUser, buf.as_mut_ptr().cast(), len, &mut len) }
+            != 0;
+    let error = IoError::last_os_error();
+    // SAFETY: token was opened above and is not used afterwards
+    unsafe { CloseHandle(token) };
+    if !ok {
+        return Err(error);
+    }
+    Ok(buf)
+}
+
+fn user_sid(token_user: &[u64]) -> PSID {
+    // SAFETY: the buffer was filled with a TOKEN_USER by GetTokenInformation and is aligned for it
+    unsafe { (*token_user.as_ptr().cast::<TOKEN_USER>()).User.Sid }
+}
+
+// Formats a SID like S-1-5-18, for error messages
+fn sid_string(sid: PSID) -> String {
+    let mut wide: *mut u16 = std::ptr::null_mut();
+    // SAFETY: sid is valid, the returned string is read up to its NUL and then freed
+    unsafe {
+        if ConvertSidToStringSidW(sid, &mut wide) == 0 {
+            return "an unknown account".to_string();
+        }
+        let len = (0..).take_while(|&i| *wide.add(i) != 0).count();
+        let text = String::from_utf16_lossy(std::slice::from_raw_parts(wide, len));
+        LocalFree(wide.cast());
+        text
+    }
+}
@@ -0,0 +1,367 @@
+///
+/// wire.rs
+///
+/// Messages exchanged with the win_can_utils canserver over its pipes: the framed frame stream, the
+/// config message, session negotiation and keepalives.
+///
+use crate::can::{CanErrorCounters, CanFrame, InterfaceInfo};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::io::{Error as IoError, ErrorKind};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+// Every frame sent by the canserver starts with these bytes, followed by a little-endian u16 length
+const FRAME_MAGIC: [u8; 2] = [0xCA, 0x4E];
+/// Upper bound on an encoded frame. Anything larger is treated as a corrupted length prefix.
+pub const MAX_FRAME_LEN: usize = 4096;
+/// Size of the magic bytes plus the length prefix
+pub const FRAME_HEADER_LEN: usize = 4;
+
+/// Errors in the framing of data received from the canserver
+///
+/// Returned wrapped in an `std::io::Error` of kind `InvalidData`. The reader stays usable: the next
+/// `read_frame()` skips ahead to the next frame boundary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// The length prefix is zero or larger than any valid frame
+    InvalidLength(usize),
+    /// The frame body could not be decoded
+    Decode(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::InvalidLength(len) => write!(f, "Invalid frame length prefix: {}", len),
+            ProtocolError::Decode(e) => write!(f, "Failed to decode frame: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ProtocolError> for IoError {
+    fn from(e: ProtocolError) -> Self {
+        IoError::new(ErrorKind::InvalidData, e)
+    }
+}
+
+/// Security descriptor, in SDDL, that servers should create their pipes with
+///
+/// Grants full access to SYSTEM, administrators and the owner of the pipe, and read/write access
+/// without the right to create pipe instances to interactively logged on users. A server started
+/// with the default descriptor of its process only admits its own user, so other users and
+/// services get Access Denied. Servers that should only serve one account can replace `IU` with
+/// that account's SID.
+pub const PIPE_SDDL: &str = "D:P(A;;GA;;;SY)(A;;GA;;;BA)(A;;GA;;;OW)(A;;0x12019b;;;IU)";
+
+/// Capability of servers that acknowledge every frame written to the `in` pipe
+///
+/// Such a server sends one `WriteAck` per received frame, in order, on the pipe
+/// `\\.\pipe\can_{channel}_ack_out`, framed like the frames on the `out` pipe.
+pub const CAPABILITY_WRITE_ACK: &str = "write_ack";
+
+/// Capability of servers that answer keepalives, so that clients notice a hung server quickly
+///
+/// The client writes a `KEEPALIVE_PING` byte to the pipe `\\.\pipe\can_{channel}_keepalive`
+/// every interval, and the server answers each with a `KEEPALIVE_PONG` byte. Servers answer from the
+/// loop that serves the adapter, so that a stuck adapter is noticed as well. A client that gets no
+/// answer within its timeout treats the server as hung, and a server that gets no ping for
+/// `KEEPALIVE_SERVER_TIMEOUT` may end the client's session. With `CAPABILITY_SESSIONS`, the pipe is
+/// `can_{channel}_{session}_keepalive`.
+pub const CAPABILITY_KEEPALIVE: &str = "keepalive";
+/// Sent by the client on the keepalive pipe
+pub const KEEPALIVE_PING: u8 = 0x01;
+/// Sent by the server for every `KEEPALIVE_PING`
+pub const KEEPALIVE_PONG: u8 = 0x02;
+/// Time without a ping after which a server may consider a client gone
+pub const KEEPALIVE_SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why the canserver could not transmit a written frame
+///
+/// Returned by writes wrapped in an `std::io::Error` of kind `Other`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    /// The controller is bus-off and does not transmit
+    BusOff,
+    /// The adapter rejected the frame as invalid
+    InvalidFrame(String),
+    /// Transmission failed for another reason, e.g. an adapter or serial port error
+    Failed(String),
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::BusOff => write!(f, "The CAN controller is bus-off"),
+            WriteError::InvalidFrame(e) => write!(f, "The adapter rejected the frame: {}", e),
+            WriteError::Failed(e) => write!(f, "Failed to transmit the frame: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// Acknowledgement of one written frame sent by servers with `CAPABILITY_WRITE_ACK`
+pub type WriteAck = Result<(), WriteError>;
+
+/// Decodes the bincode body of a single frame, without the magic bytes and length prefix
+///
+/// Bodies longer than `MAX_FRAME_LEN` are rejected before decoding.
+pub fn decode_body(body: &[u8]) -> Result<CanFrame, ProtocolError> {
+    decode_message(body)
+}
+
+/// Encodes a frame the way the canserver sends it: magic bytes, length prefix and bincode body
+pub fn encode_frame(frame: &CanFrame) -> std::io::Result<Vec<u8>> {
+    encode_message(frame)
+}
+
+/// Like `decode_body()`, for any message sent with the same framing
+pub fn decode_message<T: DeserializeOwned>(body: &[u8]) -> Result<T, ProtocolError> {
+    if body.len() > MAX_FRAME_LEN {
+        return Err(ProtocolError::InvalidLength(body.len()));
+    }
+    let config = bincode::config::standard().with_limit::<MAX_FRAME_LEN>();
+    bincode::serde::decode_from_slice::<T, _>(body, config)
+        .map(|(message, _)| message)
+        .map_err(|e| ProtocolError::Decode(e.to_string()))
+}
+
+/// Like `encode_frame()`, for any message sent with the same framing
+pub fn encode_message<T: Serialize>(message: &T) -> std::io::Result<Vec<u8>> {
+    let body = bincode::serde::encode_to_vec(message, bincode::config::standard())
+        .map_err(IoError::other)?;
+    if body.len() > MAX_FRAME_LEN {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!("Message of {} bytes is too long", body.len()),
+        ));
+    }
+    let mut data = Vec::with_capacity(FRAME_HEADER_LEN + body.len());
+    data.extend_from_slice(&FRAME_MAGIC);
+    data.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    data.extend_from_slice(&body);
+    Ok(data)
+}
+
+/// Incremental decoder for the framed canserver output stream
+///
+/// Decodes frames by default, or other messages with the same framing such as `WriteAck`. Holds
+/// all partially received data, so no bytes are lost if a read is cancelled half way. Once
+/// `decode()` returned `Ok(None)`, at most `FRAME_HEADER_LEN + MAX_FRAME_LEN` bytes are buffered,
+/// whatever was received.
+pub struct FrameDecoder<T = CanFrame> {
+    pub(crate) buf: Vec<u8>,
+    skipped_bytes: u64,
+    // Error hit while draining a batch, reported by the next decode
+    pub(crate) pending_error: Option<ProtocolError>,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for FrameDecoder<T> {
+    fn default() -> Self {
+        Self {
+            buf: Vec::new(),
+            skipped_bytes: 0,
+            pending_error: None,
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> FrameDecoder<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends received bytes. Call `decode()` until it returns `Ok(None)` before pushing more.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Decodes the next complete message from the buffer. Returns None if more data is needed.
+    pub fn decode(&mut self) -> Result<Option<T>, ProtocolError> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+
+        // Skip to the start of the next frame. Anything before the magic bytes is lost data.
+        match self.buf.windows(2).position(|w| w == FRAME_MAGIC) {
+            Some(start) => self.skip(start),
+            None => {
+                // Keep a trailing byte that may be the first half of the magic
+                let keep = usize::from(self.buf.last() == Some(&FRAME_MAGIC[0]));
+                self.skip(self.buf.len() - keep);
+                return Ok(None);
+            }
+        }
+        if self.buf.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+
+        let len = u16::from_le_bytes([self.buf[2], self.buf[3]]) as usize;
+        if len == 0 || len > MAX_FRAME_LEN {
+            // Drop the magic so the next call searches for a new frame boundary
+            self.buf.drain(..FRAME_MAGIC.len());
+            return Err(ProtocolError::InvalidLength(len));
+        }
+        if self.buf.len() < FRAME_HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        let result = decode_message(&self.buf[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len]);
+        self.buf.drain(..FRAME_HEADER_LEN + len);
+        result.map(Some)
+    }
+
+    /// Returns the number of bytes skipped so far while searching for frame boundaries
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Returns the number of bytes received but not decoded yet
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn skip(&mut self, count: usize) {
+        self.buf.drain(..count);
+        self.skipped_bytes += count as u64;
+    }
+}
+
+/// Schema version of `CanServerConfig` written and understood by this crate
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// Config and capabilities reported by a canserver on its config pipe, as JSON
+///
+/// The message is forward compatible: fields are tagged by name, fields added by newer servers are
+/// ignored (and kept in `extra`), and fields missing from older servers take their defaults. A
+/// server only raises `min_schema` for changes that older clients cannot safely ignore.
+///
+/// ```json
+/// {"bitrate": 500000, "version": "0.4.0", "schema": 1, "min_schema": 1, "capabilities": ["error_counters"]}
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CanServerConfig {
+    pub bitrate: Option<u32>,
+    /// Version of the server package
+    pub version: String,
+    /// Controller error counters, if the adapter reports them
+    #[serde(default)]
+    pub error_counters: Option<CanErrorCounters>,
+    /// Schema version of this message. 0 for servers that predate schema versioning
+    #[serde(default)]
+    pub schema: u32,
+    /// Oldest schema version a client must understand to use the server
+    #[serde(default)]
+    pub min_schema: u32,
+    /// Optional features supported by the server, e.g. `error_counters`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Details of the adapter, if the server reports them
+    #[serde(default)]
+    pub info: Option<InterfaceInfo>,
+    /// Fields unknown to this version, kept so that the message can be passed on unchanged
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CanServerConfig {
+    /// Creates a config of the current schema version for a server of version `version`
+    pub fn new(version: &str) -> Self {
+        Self {
+            version: version.to_string(),
+            schema: CONFIG_SCHEMA_VERSION,
+            min_schema: CONFIG_SCHEMA_VERSION,
+            ..Self::default()
+        }
+    }
+
+    /// Parses a config message of any schema version
+    pub fn parse(json: &[u8]) -> std::io::Result<Self> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    pub fn to_json(&self) -> std::io::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Returns an error if the server requires a newer schema than this crate understands
+    ///
+    /// Servers without a schema version (`schema` 0) are not checked here, they can only be
+    /// identified by their `version`.
+    pub fn check_schema(&self) -> std::io::Result<()> {
+        if self.min_schema > CONFIG_SCHEMA_VERSION {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Server {} requires config schema {}, this client supports up to {}",
+                    self.version, self.min_schema, CONFIG_SCHEMA_VERSION
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Capability of servers that give every client its own pipes, so several processes can share
+/// one adapter
+///
+/// A client writes a `SessionRequest` as JSON, followed by a newline, to the pipe
+/// `\\.\pipe\can_{channel}_session` and reads a `SessionReply` until the server closes the pipe.
+/// The granted session `n` is then served on the pipes `can_{channel}_{n}_out`, `_in` and, with
+/// `CAPABILITY_WRITE_ACK`, `_ack_out`. Every session receives all frames from the bus plus the
+/// frames written by the other sessions. The server ends a session once the client closes its
+/// `out` pipe, and keeps serving the plain `can_{channel}_*` pipes to one client that does not
+/// negotiate a session.
+pub const CAPABILITY_SESSIONS: &str = "sessions";
+
+/// Request for a session, sent by the client on the session pipe
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SessionRequest {
+    /// Describes the client in the server's logs, e.g. its process name and id
+    pub client: String,
+    /// Schema version of the session messages understood by the client
+    #[serde(default)]
+    pub schema: u32,
+}
+
+impl SessionRequest {
+    pub fn new(client: &str) -> Self {
+        Self {
+            client: client.to_string(),
+            schema: CONFIG_SCHEMA_VERSION,
+        }
+    }
+}
+
+/// Answer to a `SessionRequest`: the granted session, or why none was granted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SessionReply {
+    #[serde(default)]
+    pub session: Option<u32>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl SessionReply {
+    /// Returns the granted session, or the server's reason as an error of kind `ConnectionRefused`
+    pub fn into_session(self) -> std::io::Result<u32> {
+        match (self.session, self.error) {
+            (Some(session), None) => Ok(session),
+            (_, error) => Err(IoError::new(
+                ErrorKind::ConnectionRefused,
+                format!(
+                    "The canserver refused the session: {}",
+                    error.as_deref().unwrap_or("no reason given")
+                ),
+            )),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+///
+/// zenoh.rs
+///
+/// Publishes CAN data over zenoh.
+///
+use crate::{CanInterface, FramesDropped, can::CanFrame, pipeline::Annotated};
+use std::io::Error as IoError;
+
+/// Publishes frames and decoded signals on zenoh key expressions
+///
+/// Frames are put on `<prefix>/<channel>/<id>` in the JSON format of `CanFrame`, signals on
+/// `<prefix>/<channel>/<id>/<signal>` as plain decimal numbers. The ID is in hex, 3 digits for
+/// standard and 8 for extended IDs.
+pub struct ZenohPublisher {
+    session: zenoh::Session,
+    prefix: String,
+}
+
+impl ZenohPublisher {
+    /// Creates a publisher for `channel` with the key prefix `can`
+    pub fn new(session: zenoh::Session, channel: &str) -> Self {
+        Self {
+            session,
+            prefix: format!("can/{}", channel),
+        }
+    }
+
+    /// Replaces the `can/<channel>` part of the keys
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub async fn publish_frame(&self, frame: &CanFrame) -> std::io::Result<()> {
+        let payload = serde_json::to_vec(frame).map_err(IoError::other)?;
+        self.session
+            .put(format!("{}/{}", self.prefix, frame.id_hex()), payload)
+            .await
+            .map_err(IoError::other)
+    }
+
+    /// Publishes each decoded signal of a pipeline frame
+    pub async fn publish_signals(&self, frame: &Annotated) -> std::io::Result<()> {
+        for (name, value) in &frame.signals {
+            self.session
+                .put(
+                    format!("{}/{}/{}", self.prefix, frame.frame.id_hex(), name),
+                    value.to_string(),
+                )
+                .await
+                .map_err(IoError::other)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes every frame read from `can` until an I/O error occurs
+    pub async fn run<T: CanInterface>(&self, can: &mut T) -> std::io::Result<()> {
+        loop {
+            match can.read_frame().await {
+                Ok(frame) => self.publish_frame(&frame).await?,
+                Err(e) if e.get_ref().is_some_and(|e| e.is::<FramesDropped>()) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}